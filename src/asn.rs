@@ -0,0 +1,100 @@
+use std::fs::read_to_string;
+use std::net::IpAddr;
+use std::path::Path;
+use std::time::Instant;
+
+use crate::etc::log;
+use crate::ipdata::trie::IpTrie;
+
+// origin AS per prefix from ~/.config/pacmon/asn. takes the caida routeviews
+// pfx2as files as they come (prefix, length, asn - tab separated, multi
+// origin as 13335_1234) or plain "cidr asn" lines //
+pub struct Asns {
+    asns: IpTrie<u32>
+}
+
+impl Asns {
+    pub fn new() -> Self {
+        Asns { asns: IpTrie::new() }
+    }
+
+    pub fn load(path: &Path) -> Self {
+        let start = Instant::now();
+        let mut asns = Asns::new();
+        match read_to_string(path) {
+            Ok(txt) => {
+                let errs = asns.add(&txt);
+                if errs > 0 {
+                    eprintln!("{}: skipped {} lines", path.display(), errs);
+                }
+            }
+            Err(err) => eprintln!("{}: {}", path.display(), err)
+        }
+        asns.asns.shrink_to_fit();
+        log(format!("asn: loaded {} prefixes took {:?}", asns.asns.len(), start.elapsed()));
+        asns
+    }
+
+    // returns the number of lines it couldn't use //
+    fn add(&mut self, txt: &str) -> usize {
+        let mut errs = 0;
+        for line in txt.lines().map(|l| l.split('#').next().unwrap().trim()).filter(|l| !l.is_empty()) {
+            let words: Vec<&str> = line.split([' ', '\t', ',']).filter(|w| !w.is_empty()).collect();
+            let (cidr, asn) = match words.as_slice() {
+                [cidr, asn, ..] if cidr.contains('/') => (cidr.to_string(), *asn),
+                [prefix, len, asn, ..] => (format!("{}/{}", prefix, len), *asn),
+                _ => {
+                    errs += 1;
+                    continue;
+                }
+            };
+            match parse_asn(asn).map(|asn| self.asns.insert_subnet(&cidr, asn)) {
+                Some(Ok(_)) => {}
+                _ => errs += 1
+            }
+        }
+        errs
+    }
+
+    pub fn lookup(&self, addr: &IpAddr) -> Option<u32> {
+        self.asns.lookup(addr).cloned()
+    }
+}
+
+// "13335", "AS13335", "13335_1234", "13335,1234" //
+fn parse_asn(txt: &str) -> Option<u32> {
+    let txt = txt.trim_start_matches("AS").trim_start_matches("as");
+    let digits: String = txt.chars().take_while(|c| c.is_ascii_digit()).collect();
+    digits.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::asn::{Asns, parse_asn};
+    use crate::subnets::addr;
+
+    #[test]
+    fn test_formats() {
+        let mut asns = Asns::new();
+        assert_eq!(2, asns.add("
+1.0.0.0	24	13335
+1.0.4.0	22	38803_56203
+2600:1f14::	35	16509
+8.8.8.0/24 AS15169
+9.9.9.0/24
+bogus	24	1
+"));
+        assert_eq!(Some(13335), asns.lookup(&addr("1.0.0.1")));
+        assert_eq!(Some(38803), asns.lookup(&addr("1.0.5.1")));
+        assert_eq!(Some(16509), asns.lookup(&addr("2600:1f14::1")));
+        assert_eq!(Some(15169), asns.lookup(&addr("8.8.8.8")));
+        assert_eq!(None, asns.lookup(&addr("9.9.9.9")));
+    }
+
+    #[test]
+    fn test_parse_asn() {
+        assert_eq!(Some(13335), parse_asn("AS13335"));
+        assert_eq!(Some(13335), parse_asn("13335_1"));
+        assert_eq!(None, parse_asn("ASX"));
+    }
+}
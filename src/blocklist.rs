@@ -0,0 +1,121 @@
+use std::fs;
+use std::fs::read_to_string;
+use std::net::IpAddr;
+use std::path::Path;
+use std::time::Instant;
+
+use crate::etc::log;
+use crate::ipdata::trie::IpTrie;
+
+// ip/cidr lists (tor exit nodes, firehol, spamhaus drop, internal denylists..)
+// one file per list in ~/.config/pacmon/blocklists/, named after the file.
+// anything after '#' or ';' is a comment. the first address-like token on a
+// line is used so the likes of "ExitAddress 1.2.3.4 2024-06-01" work too.
+pub struct Blocklists {
+    names: Vec<String>,
+    addrs: IpTrie<u16>
+}
+
+impl Blocklists {
+    pub fn new() -> Self {
+        Blocklists { names: Vec::new(), addrs: IpTrie::new() }
+    }
+
+    pub fn load(dir:&Path) -> Self {
+        let start = Instant::now();
+        let mut blocklists = Blocklists::new();
+
+        let mut paths = match fs::read_dir(dir) {
+            Ok(entries) => entries.filter_map(|e| e.ok()).map(|e| e.path()).collect::<Vec<_>>(),
+            Err(err) => {
+                eprintln!("{}: {}", dir.display(), err);
+                return blocklists;
+            }
+        };
+        paths.sort();
+
+        for path in paths.iter().filter(|p| p.is_file()) {
+            let name = path.file_stem().unwrap().to_string_lossy().to_string();
+            match read_to_string(path) {
+                Ok(txt) => {
+                    let n = blocklists.add(&name, &txt);
+                    log(format!("blocklist: {} entries from {}", n, path.display()));
+                }
+                Err(err) => eprintln!("{}: {} - skipping", path.display(), err)
+            }
+        }
+
+        blocklists.addrs.shrink_to_fit();
+        log(format!("blocklist: loaded {} lists took {:?}", blocklists.names.len(), start.elapsed()));
+        blocklists
+    }
+
+    fn add(&mut self, name:&str, txt:&str) -> usize {
+        let idx = self.names.len() as u16;
+        self.names.push(name.to_string());
+
+        let mut n = 0;
+        for line in txt.lines() {
+            let line = line.split(['#', ';']).next().unwrap();
+            for token in line.split_whitespace() {
+                if self.addrs.insert_subnet(token, idx).is_ok() {
+                    n += 1;
+                    break;
+                }
+            }
+        }
+        n
+    }
+
+    // name of the (most specific) list the address is on //
+    pub fn lookup(&self, addr:&IpAddr) -> Option<&str> {
+        self.addrs.lookup(addr).map(|&idx| self.names[idx as usize].as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::blocklist::Blocklists;
+    use crate::subnets::addr;
+
+    #[test]
+    fn test_formats() {
+        let mut blocklists = Blocklists::new();
+
+        assert_eq!(2, blocklists.add("drop", "\
+; Spamhaus DROP List 2024/06/01
+1.10.16.0/20 ; SBL256894
+1.19.0.0/16 ; SBL434604
+"));
+        assert_eq!(2, blocklists.add("firehol", "\
+#
+# firehol_level1
+#
+5.188.10.0/23
+31.184.196.75
+"));
+        assert_eq!(1, blocklists.add("tor", "\
+ExitNode 0011BD2485AD45D984EC4159C88FC066E5E3300E
+Published 2024-06-01 08:41:12
+LastStatus 2024-06-01 10:00:00
+ExitAddress 185.220.101.1 2024-06-01 10:15:53
+"));
+
+        assert_eq!(Some("drop"), blocklists.lookup(&addr("1.10.20.1")));
+        assert_eq!(Some("firehol"), blocklists.lookup(&addr("5.188.11.255")));
+        assert_eq!(Some("firehol"), blocklists.lookup(&addr("31.184.196.75")));
+        assert_eq!(None, blocklists.lookup(&addr("31.184.196.76")));
+        assert_eq!(Some("tor"), blocklists.lookup(&addr("185.220.101.1")));
+        assert_eq!(None, blocklists.lookup(&addr("8.8.8.8")));
+    }
+
+    #[test]
+    fn test_most_specific_list_wins() {
+        let mut blocklists = Blocklists::new();
+        blocklists.add("wide", "10.0.0.0/8\n");
+        blocklists.add("narrow", "10.1.1.1\n2001:db8::/32\n");
+        assert_eq!(Some("wide"), blocklists.lookup(&addr("10.1.1.2")));
+        assert_eq!(Some("narrow"), blocklists.lookup(&addr("10.1.1.1")));
+        assert_eq!(Some("narrow"), blocklists.lookup(&addr("2001:db8::5")));
+    }
+}
@@ -0,0 +1,290 @@
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
+
+use crate::config::{Config, Value};
+use crate::filter::Filter;
+use crate::store;
+use crate::ui::columns;
+use crate::ui::sort::SortKey;
+
+// anything given here wins over the config file //
+#[derive(Parser, Debug)]
+#[command(name = "pacmon", version, about = "who is your machine talking to, and how much")]
+pub struct Cli {
+    #[arg(short, long, help = "capture on this interface instead of the default one")]
+    pub interface: Option<String>,
+
+    #[arg(short, long, value_parser = check_filter, help = "only show streams matching, eg \"proc:firefox !cc:US\"")]
+    pub filter: Option<String>,
+
+    #[arg(short = 'd', long, value_name = "MS", value_parser = clap::value_parser!(u32).range(200..), help = "redraw, and write to --json, --csv and the like, every so many milliseconds")]
+    pub interval: Option<u32>,
+
+    #[arg(short, long, value_parser = ["normal", "corp", "cloud", "graph"], help = "what to show first")]
+    pub mode: Option<String>,
+
+    #[arg(short, long, value_parser = check_sort, help = "rate in-rate out-rate in-total out-total total packets age proc host cc corp")]
+    pub sort: Option<String>,
+
+    #[arg(short = 'S', long, help = "reverse the sort")]
+    pub reverse: bool,
+
+    #[arg(short, long, help = "show addresses and ports rather than names")]
+    pub numeric: bool,
+
+    #[arg(long, value_parser = check_columns, help = "normal mode columns, eg \"proc,remote,rate,cc,corp\"")]
+    pub columns: Option<String>,
+
+    #[arg(short, long, value_name = "FILE", help = "read this config file instead of ~/.config/pacmon/config.toml")]
+    pub config: Option<PathBuf>,
+
+    #[arg(long, help = "write the settings back to the config file on quit")]
+    pub save: bool,
+
+    #[arg(short, long, help = "print the table to stdout every interval instead of running the ui, like top -b")]
+    pub batch: bool,
+
+    #[arg(long, value_name = "N", help = "with --batch, stop after this many tables")]
+    pub count: Option<u32>,
+
+    #[arg(long, value_name = "SECS", help = "with --batch, stop after this long")]
+    pub duration: Option<u32>,
+
+    #[arg(long, value_name = "COLS", help = "with --batch, fit the table to this many columns (default no limit)")]
+    pub width: Option<u32>,
+
+    #[arg(long, value_name = "N", help = "with --batch, only the top so many rows")]
+    pub rows: Option<u32>,
+
+    #[arg(long, value_name = "FILE", help = "append a json line per active stream per interval, - for stdout (runs without the ui)")]
+    pub json: Option<String>,
+
+    #[arg(long, value_name = "FILE", help = "append a csv row per active stream per interval, - for stdout (runs without the ui)")]
+    pub csv: Option<String>,
+
+    #[arg(long, value_name = "ADDR", help = "serve prometheus metrics on this address (or localhost port) at /metrics")]
+    pub prometheus: Option<String>,
+
+    #[arg(long, value_name = "N", help = "with --prometheus, --statsd or --graphite, the most values a label takes before the rest are \"other\" (default 20). prometheus labels go first come first served and keep their place, so a later heavy talker may only be \"other\"")]
+    pub top: Option<u32>,
+
+    #[arg(long, value_name = "HOST:PORT", help = "push per proc and corp byte rates to statsd over udp every interval")]
+    pub statsd: Option<String>,
+
+    #[arg(long, value_name = "HOST:PORT", help = "likewise in graphite's plaintext format")]
+    pub graphite: Option<String>,
+
+    #[arg(long, help = "name statsd and graphite metrics <prefix>.<proc|corp>.<name>.<in|out> (default pacmon)")]
+    pub prefix: Option<String>,
+
+    #[arg(long, value_name = "HOST:PORT", help = "export flow records, with proc and pid, to this netflow/ipfix collector")]
+    pub flows: Option<String>,
+
+    #[arg(long, value_parser = ["ipfix", "v9"], help = "with --flows, ipfix (default) or netflow v9")]
+    pub flow_format: Option<String>,
+
+    #[arg(long, value_name = "SECS", help = "with --flows, export streams that are still going this often (default 60)")]
+    pub active_timeout: Option<u32>,
+
+    #[arg(long, value_name = "SECS", help = "with --flows, export streams once they've been quiet this long (default 15)")]
+    pub idle_timeout: Option<u32>,
+
+    #[arg(long, value_name = "ADDR", help = "show netflow v5/v9, ipfix or sflow sent to this udp address (or port) instead of capturing")]
+    pub collect: Option<String>,
+
+    #[arg(long, value_name = "CIDRS", help = "with --collect, the networks on our side, eg \"10.0.0.0/8,fd00::/8\" (default rfc 1918 and fc00::/7)")]
+    pub networks: Option<String>,
+
+    #[arg(long, value_name = "FILE", help = "keep per interval totals by proc, corp, cc and host in this sqlite file, for pacmon query. it's opened as --user")]
+    pub history: Option<PathBuf>,
+
+    #[arg(long, value_name = "DAYS", help = "with --history, forget anything older (default 30)")]
+    pub retention: Option<u32>,
+
+    #[arg(long, value_name = "HOURS", help = "with --history, roll intervals older than this up into hours (default 24)")]
+    pub downsample: Option<u32>,
+
+    #[arg(long, value_name = "FILE", help = "on the way out, write a summary of the session: totals, peak rate and the busiest procs, streams, corps, countries and hosts, - for stdout")]
+    pub summary: Option<String>,
+
+    #[arg(long, value_parser = ["text", "json", "html"], help = "with --summary, text, json or html (default by the file's extension, else text)")]
+    pub summary_format: Option<String>,
+
+    #[arg(long, value_name = "N", help = "with --summary, rows per table (default 10)")]
+    pub summary_top: Option<u32>,
+
+    #[arg(long, help = "run without the ui or tables, just the outputs above, eg as a service")]
+    pub headless: bool,
+
+    #[arg(short, long, help = "create ./pacmon.log")]
+    pub log: bool,
+
+    #[arg(short, long, help = "run as this user once capturing, when started as root (default $SUDO_USER or nobody)")]
+    pub user: Option<String>,
+
+    #[arg(long, value_name = "FILE", help = "instead of ~/.config/pacmon/labels")]
+    pub labels: Option<PathBuf>,
+
+    #[arg(long, value_name = "DIR", help = "instead of ~/.config/pacmon/cloud")]
+    pub cloud: Option<PathBuf>,
+
+    #[arg(long, value_name = "DIR", help = "instead of ~/.config/pacmon/blocklists")]
+    pub blocklists: Option<PathBuf>,
+
+    #[arg(long, value_name = "FILE", help = "instead of ~/.config/pacmon/asn")]
+    pub asn: Option<PathBuf>,
+
+    #[arg(long, value_name = "FILE", help = "instead of ~/.config/pacmon/theme")]
+    pub theme: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    #[command(short_flag = 'x', about = "\"cidr,rest\" lines on stdin to \"cidr,int,rest\" on stdout, for the ipdata tables")]
+    Convert,
+
+    #[command(about = "what pacmon knows about some addresses - host, corp, cc, city, asn, labels, lists")]
+    Lookup {
+        #[arg(required = true)]
+        addrs: Vec<String>
+    },
+
+    #[command(about = "the busiest procs, corps, ccs or hosts from --history, eg query corp --from \"yesterday 2pm\" --to \"yesterday 3pm\"")]
+    Query {
+        #[arg(value_parser = store::KINDS, default_value = "corp")]
+        by: String,
+
+        #[arg(long, allow_hyphen_values = true, help = "now, -2h, 14:00, 2pm, yesterday 14:00, 2024-05-01 14:00.. (default -24h)")]
+        from: Option<String>,
+
+        #[arg(long, allow_hyphen_values = true, help = "likewise (default now)")]
+        to: Option<String>,
+
+        #[arg(short = 'n', long, default_value_t = 10, help = "this many rows")]
+        top: usize
+    }
+}
+
+impl Cli {
+    pub fn config(&self) -> Config {
+        let mut config = match &self.config {
+            Some(path) => Config::load_from(path.clone()),
+            None => Config::load()
+        };
+
+        let mut set = |key: &str, value: Option<Value>| {
+            if let Some(value) = value {
+                config.set(key, value);
+            }
+        };
+
+        let str = |txt: &Option<String>| txt.as_ref().map(|txt| Value::Str(txt.to_string()));
+        let path = |path: &Option<PathBuf>| path.as_ref().map(|path| Value::Str(path.display().to_string()));
+        let flag = |on: bool| Some(Value::Bool(true)).filter(|_| on);
+
+        set("interface", str(&self.interface));
+        set("filter", str(&self.filter));
+        set("interval", self.interval.map(|ms| Value::Int(ms as i64)));
+        set("mode", str(&self.mode));
+        set("sort", str(&self.sort));
+        set("reverse", flag(self.reverse));
+        set("resolve", Some(Value::Bool(false)).filter(|_| self.numeric));
+        set("columns", str(&self.columns));
+        set("save", flag(self.save));
+        set("log", flag(self.log));
+        set("user", str(&self.user));
+        set("batch", flag(self.batch));
+        set("count", self.count.map(|n| Value::Int(n as i64)));
+        set("duration", self.duration.map(|secs| Value::Int(secs as i64)));
+        set("width", self.width.map(|cols| Value::Int(cols as i64)));
+        set("rows", self.rows.map(|n| Value::Int(n as i64)));
+        set("json", str(&self.json));
+        set("csv", str(&self.csv));
+        set("prometheus", str(&self.prometheus));
+        set("top", self.top.map(|n| Value::Int(n as i64)));
+        set("statsd", str(&self.statsd));
+        set("graphite", str(&self.graphite));
+        set("prefix", str(&self.prefix));
+        set("flows", str(&self.flows));
+        set("flow_format", str(&self.flow_format));
+        set("active_timeout", self.active_timeout.map(|secs| Value::Int(secs as i64)));
+        set("idle_timeout", self.idle_timeout.map(|secs| Value::Int(secs as i64)));
+        set("collect", str(&self.collect));
+        set("networks", str(&self.networks));
+        set("history", path(&self.history));
+        set("retention", self.retention.map(|days| Value::Int(days as i64)));
+        set("downsample", self.downsample.map(|hours| Value::Int(hours as i64)));
+        set("summary", str(&self.summary));
+        set("summary_format", str(&self.summary_format));
+        set("summary_top", self.summary_top.map(|n| Value::Int(n as i64)));
+        set("headless", flag(self.headless));
+        set("paths.labels", path(&self.labels));
+        set("paths.cloud", path(&self.cloud));
+        set("paths.blocklists", path(&self.blocklists));
+        set("paths.asn", path(&self.asn));
+        set("paths.theme", path(&self.theme));
+
+        config
+    }
+}
+
+fn check_filter(txt: &str) -> Result<String, String> {
+    Filter::parse(txt).map(|_| txt.to_string())
+}
+
+fn check_sort(txt: &str) -> Result<String, String> {
+    match SortKey::parse(txt) {
+        Some(key) => Ok(key.name().to_string()),
+        None => Err("see --help for the choices".to_string())
+    }
+}
+
+fn check_columns(txt: &str) -> Result<String, String> {
+    columns::parse_list(txt).map(|_| txt.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::{CommandFactory, Parser};
+
+    use crate::cli::{Cli, Command};
+
+    #[test]
+    fn test_cli() {
+        Cli::command().debug_assert();
+
+        let cli = Cli::try_parse_from(["pacmon", "-c", "/dev/null", "-i", "eth1", "-d", "2000", "-s", "CC", "-S", "-n", "--columns", "proc,remote,cc"]).unwrap();
+        let config = cli.config();
+        assert_eq!(Some("eth1".to_string()), config.get_str("interface"));
+        assert_eq!(Some(2000), config.get_int("interval"));
+        assert_eq!(Some("cc".to_string()), config.get_str("sort"));
+        assert_eq!(Some(true), config.get_bool("reverse"));
+        assert_eq!(Some(false), config.get_bool("resolve"));
+        assert_eq!(Some("proc,remote,cc".to_string()), config.get_str("columns"));
+
+        let config = Cli::try_parse_from(["pacmon", "-c", "/dev/null", "-b", "--count", "3", "--width", "100"]).unwrap().config();
+        assert_eq!(Some(true), config.get_bool("batch"));
+        assert_eq!(Some(3), config.get_int("count"));
+        assert_eq!(Some(100), config.get_int("width"));
+
+        let config = Cli::try_parse_from(["pacmon", "-c", "/dev/null", "--top", "5", "--summary-top", "3"]).unwrap().config();
+        assert_eq!((Some(5), Some(3)), (config.get_int("top"), config.get_int("summary_top")));
+
+        assert!(Cli::try_parse_from(["pacmon", "--bogus"]).is_err());
+        assert!(Cli::try_parse_from(["pacmon", "-d", "10"]).is_err());
+        assert!(Cli::try_parse_from(["pacmon", "-m", "sideways"]).is_err());
+        assert!(Cli::try_parse_from(["pacmon", "-s", "nope"]).is_err());
+        assert!(Cli::try_parse_from(["pacmon", "--columns", "proc,nope"]).is_err());
+        assert!(Cli::try_parse_from(["pacmon", "-f", "nosuch:x"]).is_err());
+
+        assert!(matches!(Cli::try_parse_from(["pacmon", "-x"]).unwrap().command, Some(Command::Convert)));
+        assert!(matches!(Cli::try_parse_from(["pacmon", "lookup", "1.1.1.1"]).unwrap().command, Some(Command::Lookup { .. })));
+        assert!(Cli::try_parse_from(["pacmon", "lookup"]).is_err());
+        assert!(matches!(Cli::try_parse_from(["pacmon", "query", "proc", "--from", "-2h"]).unwrap().command, Some(Command::Query { top: 10, .. })));
+        assert!(Cli::try_parse_from(["pacmon", "query", "nope"]).is_err());
+    }
+}
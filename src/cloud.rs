@@ -0,0 +1,261 @@
+use std::fs;
+use std::fs::read_to_string;
+use std::net::IpAddr;
+use std::path::Path;
+use std::time::Instant;
+
+use serde_json::Value;
+
+use crate::etc::log;
+use crate::ipdata::trie::IpTrie;
+
+// attributes addresses to cloud provider/service/region using the range
+// files the providers publish. drop any of these in ~/.config/pacmon/cloud/
+//   aws:        https://ip-ranges.amazonaws.com/ip-ranges.json
+//   gcp:        https://www.gstatic.com/ipranges/cloud.json
+//   azure:      ServiceTags_Public_*.json from the microsoft download center
+//   cloudflare: https://www.cloudflare.com/ips-v4 (and ips-v6)
+pub struct CloudRanges {
+    ranges: IpTrie<Cloud>
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cloud {
+    pub provider: &'static str,
+    pub service: String,
+    pub region: String
+}
+
+impl Cloud {
+    fn new(provider:&'static str, service:&str, region:&str) -> Self {
+        Cloud { provider, service: service.to_string(), region: region.to_string() }
+    }
+
+    // eg "AWS S3 eu-west-1" //
+    pub fn label(&self) -> String {
+        [self.provider, &self.service, &self.region].iter()
+            .filter(|s| !s.is_empty())
+            .cloned()
+            .collect::<Vec<&str>>()
+            .join(" ")
+    }
+}
+
+// (cidr, cloud, generic) - generic entries are superseded by specific ones for the same prefix //
+type Entry = (String, Cloud, bool);
+
+impl CloudRanges {
+    pub fn new() -> Self {
+        CloudRanges { ranges: IpTrie::new() }
+    }
+
+    pub fn load(dir:&Path) -> Self {
+        let start = Instant::now();
+        let mut cloud = CloudRanges::new();
+
+        let mut paths = match fs::read_dir(dir) {
+            Ok(entries) => entries.filter_map(|e| e.ok()).map(|e| e.path()).collect::<Vec<_>>(),
+            Err(err) => {
+                eprintln!("{}: {}", dir.display(), err);
+                return cloud;
+            }
+        };
+        paths.sort();
+
+        for path in paths {
+            match read_to_string(&path).map_err(|e| e.to_string()).and_then(|txt| parse(&path, &txt)) {
+                Ok(entries) => {
+                    log(format!("cloud: {} ranges from {}", entries.len(), path.display()));
+                    cloud.add(entries);
+                }
+                Err(msg) => eprintln!("{}: {} - skipping", path.display(), msg)
+            }
+        }
+
+        cloud.ranges.shrink_to_fit();
+        log(format!("cloud: loaded {} ranges took {:?}", cloud.ranges.len(), start.elapsed()));
+        cloud
+    }
+
+    fn add(&mut self, mut entries:Vec<Entry>) {
+        // last one wins in the trie so the generic ones go in first //
+        entries.sort_by_key(|(_, _, generic)| !generic);
+        for (cidr, cloud, _) in entries {
+            if let Err(msg) = self.ranges.insert_subnet(&cidr, cloud) {
+                log(format!("cloud: {}", msg));
+            }
+        }
+    }
+
+    pub fn lookup(&self, addr:&IpAddr) -> Option<&Cloud> {
+        self.ranges.lookup(addr)
+    }
+}
+
+fn parse(path:&Path, txt:&str) -> Result<Vec<Entry>, String> {
+    let name = path.file_name().map(|s| s.to_string_lossy().to_lowercase()).unwrap_or_default();
+
+    if !txt.trim_start().starts_with('{') {
+        return match name.contains("cloudflare") || name.starts_with("ips-v") {
+            true => Ok(parse_cloudflare(txt)),
+            false => Err("unrecognised file".to_string())
+        };
+    }
+
+    let json:Value = serde_json::from_str(txt).map_err(|e| e.to_string())?;
+
+    if json["values"].is_array() {
+        Ok(parse_azure(&json))
+    } else if json["prefixes"].as_array().is_some_and(|p| p.iter().any(|p| p["ip_prefix"].is_string())) {
+        Ok(parse_aws(&json))
+    } else if json["prefixes"].is_array() {
+        Ok(parse_gcp(&json))
+    } else {
+        Err("unrecognised json".to_string())
+    }
+}
+
+// {"prefixes": [{"ip_prefix": "3.5.140.0/22", "region": "ap-northeast-2", "service": "AMAZON"}, ..],
+//  "ipv6_prefixes": [{"ipv6_prefix": "2600:1f14::/35", "region": "us-west-2", "service": "EC2"}, ..]}
+fn parse_aws(json:&Value) -> Vec<Entry> {
+    let mut ret = Vec::new();
+    for (list, key) in [("prefixes", "ip_prefix"), ("ipv6_prefixes", "ipv6_prefix")] {
+        for prefix in json[list].as_array().into_iter().flatten() {
+            if let Some(cidr) = prefix[key].as_str() {
+                // every range is listed under AMAZON as well as the service using it //
+                let service = prefix["service"].as_str().unwrap_or("");
+                let generic = service == "AMAZON";
+                let service = if generic { "" } else { service };
+                let region = prefix["region"].as_str().unwrap_or("");
+                ret.push((cidr.to_string(), Cloud::new("AWS", service, region), generic));
+            }
+        }
+    }
+    ret
+}
+
+// {"prefixes": [{"ipv4Prefix": "34.1.208.0/20", "service": "Google Cloud", "scope": "africa-south1"}, ..]}
+fn parse_gcp(json:&Value) -> Vec<Entry> {
+    let mut ret = Vec::new();
+    for prefix in json["prefixes"].as_array().into_iter().flatten() {
+        let cidr = match (prefix["ipv4Prefix"].as_str(), prefix["ipv6Prefix"].as_str()) {
+            (Some(cidr), _) | (None, Some(cidr)) => cidr,
+            _ => continue
+        };
+        let service = match prefix["service"].as_str() {
+            Some("Google Cloud") | None => "",
+            Some(service) => service
+        };
+        let region = prefix["scope"].as_str().unwrap_or("");
+        ret.push((cidr.to_string(), Cloud::new("GCP", service, region), region.is_empty()));
+    }
+    ret
+}
+
+// {"values": [{"name": "Storage.WestEurope",
+//              "properties": {"region": "westeurope", "systemService": "AzureStorage", "addressPrefixes": [..]}}, ..]}
+fn parse_azure(json:&Value) -> Vec<Entry> {
+    let mut ret = Vec::new();
+    for value in json["values"].as_array().into_iter().flatten() {
+        let properties = &value["properties"];
+        let service = properties["systemService"].as_str().unwrap_or("");
+        let region = properties["region"].as_str().unwrap_or("");
+        // the likes of AzureCloud cover everything the service tags do //
+        let generic = service.is_empty() || region.is_empty();
+        for cidr in properties["addressPrefixes"].as_array().into_iter().flatten().filter_map(|p| p.as_str()) {
+            ret.push((cidr.to_string(), Cloud::new("Azure", service, region), generic));
+        }
+    }
+    ret
+}
+
+fn parse_cloudflare(txt:&str) -> Vec<Entry> {
+    txt.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|cidr| (cidr.to_string(), Cloud::new("Cloudflare", "", ""), false))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::cloud::{Cloud, CloudRanges, parse};
+    use crate::subnets::addr;
+
+    fn load(name:&str, txt:&str) -> CloudRanges {
+        let mut cloud = CloudRanges::new();
+        cloud.add(parse(Path::new(name), txt).unwrap());
+        cloud
+    }
+
+    fn label(cloud:&CloudRanges, txt:&str) -> Option<String> {
+        cloud.lookup(&addr(txt)).map(Cloud::label)
+    }
+
+    #[test]
+    fn test_aws() {
+        let cloud = load("ip-ranges.json", r#"{
+            "syncToken": "1717000000",
+            "prefixes": [
+                {"ip_prefix": "3.5.0.0/16", "region": "eu-west-1", "service": "S3", "network_border_group": "eu-west-1"},
+                {"ip_prefix": "3.5.0.0/16", "region": "eu-west-1", "service": "AMAZON", "network_border_group": "eu-west-1"},
+                {"ip_prefix": "3.0.0.0/8", "region": "GLOBAL", "service": "AMAZON", "network_border_group": "GLOBAL"}
+            ],
+            "ipv6_prefixes": [
+                {"ipv6_prefix": "2600:1f14::/35", "region": "us-west-2", "service": "EC2", "network_border_group": "us-west-2"}
+            ]
+        }"#);
+        assert_eq!(Some("AWS S3 eu-west-1".to_string()), label(&cloud, "3.5.1.1"));
+        assert_eq!(Some("AWS GLOBAL".to_string()), label(&cloud, "3.6.1.1"));
+        assert_eq!(Some("AWS EC2 us-west-2".to_string()), label(&cloud, "2600:1f14::1"));
+        assert_eq!(None, label(&cloud, "4.0.0.1"));
+    }
+
+    #[test]
+    fn test_gcp() {
+        let cloud = load("cloud.json", r#"{
+            "syncToken": "1717000000",
+            "creationTime": "2024-06-01T00:00:00",
+            "prefixes": [
+                {"ipv4Prefix": "34.1.208.0/20", "service": "Google Cloud", "scope": "africa-south1"},
+                {"ipv6Prefix": "2600:1900:8000::/44", "service": "Google Cloud", "scope": "us-east4"}
+            ]
+        }"#);
+        assert_eq!(Some("GCP africa-south1".to_string()), label(&cloud, "34.1.208.1"));
+        assert_eq!(Some("GCP us-east4".to_string()), label(&cloud, "2600:1900:8000::1"));
+    }
+
+    #[test]
+    fn test_azure() {
+        let cloud = load("ServiceTags_Public_20240603.json", r#"{
+            "changeNumber": 300,
+            "cloud": "Public",
+            "values": [
+                {"name": "AzureCloud", "id": "AzureCloud",
+                 "properties": {"region": "", "systemService": "", "addressPrefixes": ["20.0.0.0/8"]}},
+                {"name": "Storage.WestEurope", "id": "Storage.WestEurope",
+                 "properties": {"region": "westeurope", "systemService": "AzureStorage", "addressPrefixes": ["20.38.108.0/24", "20.38.108.0/24"]}},
+                {"name": "AzureCloud.westeurope", "id": "AzureCloud.westeurope",
+                 "properties": {"region": "westeurope", "systemService": "", "addressPrefixes": ["20.38.108.0/24"]}}
+            ]
+        }"#);
+        assert_eq!(Some("Azure AzureStorage westeurope".to_string()), label(&cloud, "20.38.108.9"));
+        assert_eq!(Some("Azure".to_string()), label(&cloud, "20.1.1.1"));
+    }
+
+    #[test]
+    fn test_cloudflare() {
+        let cloud = load("ips-v4", "173.245.48.0/20\n103.21.244.0/22\n");
+        assert_eq!(Some("Cloudflare".to_string()), label(&cloud, "173.245.50.1"));
+        assert_eq!(None, label(&cloud, "173.245.64.1"));
+    }
+
+    #[test]
+    fn test_unrecognised() {
+        assert!(parse(Path::new("notes.txt"), "hello").is_err());
+        assert!(parse(Path::new("x.json"), "{\"a\": 1}").is_err());
+        assert!(parse(Path::new("x.json"), "{").is_err());
+    }
+}
@@ -0,0 +1,735 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, UdpSocket};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::Sender;
+
+use chrono::{DateTime, Utc};
+use etherparse::{IpNumber, LaxNetSlice, LaxSlicedPacket, TransportSlice};
+
+use crate::config::Config;
+use crate::etc::log;
+use crate::pacdat::PacDat;
+use crate::subnets::parse_cidr;
+
+// flow records from routers and switches in place of packets off the wire:
+// netflow v5 and v9, ipfix and sflow v5 over udp. each record becomes a
+// PacDat carrying its byte and packet counts, scaled up by the sampling rate,
+// so the rest of pacmon is none the wiser. none of it is ours though, so
+// every stream is foreign and there are no processes //
+pub struct Collector {
+    templates: HashMap<(IpAddr, u32, u16), Vec<Field>>,   // exporter, domain, id
+    per_exporter: HashMap<IpAddr, usize>,
+    refused: bool                                         // logged that we're full
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Field {
+    kind: u16,      // 0 for enterprise fields, which we skip
+    len: u16
+}
+
+#[derive(Default)]
+struct Flow {
+    src: Option<IpAddr>,
+    dst: Option<IpAddr>,
+    src_port: u16,
+    dst_port: u16,
+    proto: u8,
+    bytes: u64,
+    packets: u64,
+    rate: u64,
+    end: Option<i64>,           // ms
+    since_boot: Option<i64>     // or ms since the exporter booted
+}
+
+// for turning the record's times into wall clock times, ms //
+struct Clock {
+    export: i64,
+    uptime: Option<i64>
+}
+
+const VARIABLE: u16 = 65535;
+
+// more than any one record could honestly be, scaled up: a broken or
+// hostile exporter, which mustn't overflow the running totals //
+const MAX_BYTES: u64 = 1 << 50;
+
+// templates are whatever anyone sends the port, so there's only so many //
+const MAX_TEMPLATES: usize = 4096;
+const MAX_PER_EXPORTER: usize = 512;
+
+// what counts as our side when there's no interface to go by //
+const NETWORKS: [&str; 4] = ["10.0.0.0/8", "172.16.0.0/12", "192.168.0.0/16", "fc00::/7"];
+
+// a bare port listens on all addresses //
+pub fn bind(addr: &str) -> Result<UdpSocket, String> {
+    let addr = match addr.contains(':') {
+        true => addr.to_string(),
+        false => format!("0.0.0.0:{}", addr)
+    };
+    UdpSocket::bind(&addr).map_err(|e| format!("collect: {}: {}", addr, e))
+}
+
+pub fn networks(config: &Config) -> Result<Vec<(IpAddr, IpAddr)>, String> {
+    match config.get_str("networks") {
+        Some(txt) => txt.split([',', ' ']).filter(|cidr| !cidr.is_empty()).map(parse_cidr).collect(),
+        None => NETWORKS.iter().map(|cidr| parse_cidr(cidr)).collect()
+    }
+}
+
+impl Collector {
+    pub fn new() -> Self {
+        Collector { templates: HashMap::new(), per_exporter: HashMap::new(), refused: false }
+    }
+
+    pub fn decode(&mut self, exporter: IpAddr, buf: &[u8]) -> Vec<PacDat> {
+        let mut rd = Reader::new(buf);
+        let ret = match rd.u16() {
+            Some(5) => v5(rd),
+            Some(9) => self.v9(exporter, rd),
+            Some(10) => self.ipfix(exporter, rd),
+            Some(0) if rd.u16() == Some(5) => sflow(rd),
+            version => {
+                log(format!("collector: {} sent version {:?}?", exporter, version));
+                Some(vec![])
+            }
+        };
+        ret.unwrap_or_else(|| {
+            log(format!("collector: short datagram from {}", exporter));
+            vec![]
+        })
+    }
+
+    fn v9(&mut self, exporter: IpAddr, mut rd: Reader) -> Option<Vec<PacDat>> {
+        rd.skip(2)?;    // count
+        let uptime = rd.u32()? as i64;
+        let secs = rd.u32()? as i64;
+        rd.skip(4)?;    // sequence
+        let domain = rd.u32()?;
+        self.sets(exporter, domain, rd, Clock { export: secs * 1000, uptime: Some(uptime) }, false)
+    }
+
+    fn ipfix(&mut self, exporter: IpAddr, mut rd: Reader) -> Option<Vec<PacDat>> {
+        rd.skip(2)?;    // length
+        let secs = rd.u32()? as i64;
+        rd.skip(4)?;    // sequence
+        let domain = rd.u32()?;
+        self.sets(exporter, domain, rd, Clock { export: secs * 1000, uptime: None }, true)
+    }
+
+    // v9 calls them flowsets and numbers them differently, otherwise the same //
+    fn sets(&mut self, exporter: IpAddr, domain: u32, mut rd: Reader, clock: Clock, ipfix: bool) -> Option<Vec<PacDat>> {
+        let (template, options) = if ipfix { (2, 3) } else { (0, 1) };
+        let mut ret = Vec::new();
+
+        while rd.remaining() >= 4 {
+            let id = rd.u16()?;
+            let len = (rd.u16()? as usize).checked_sub(4)?;
+            let mut set = rd.sub(len)?;
+
+            if id == template {
+                self.templates(exporter, domain, set, ipfix)?;
+            } else if id == options || id < 256 {
+                // sampling options and the like - we only go by what's in the records
+            } else {
+                match self.templates.get(&(exporter, domain, id)) {
+                    Some(fields) => {
+                        let min = min_len(fields);
+                        while set.remaining() >= min {
+                            let before = set.remaining();
+                            let flow = record(&mut set, fields)?;
+                            if set.remaining() == before {
+                                break;
+                            }
+                            ret.extend(flow.pac_dat(&clock));
+                        }
+                    }
+                    None => log(format!("collector: no template {} from {} yet", id, exporter))
+                }
+            }
+        }
+
+        Some(ret)
+    }
+
+    fn templates(&mut self, exporter: IpAddr, domain: u32, mut set: Reader, ipfix: bool) -> Option<()> {
+        while set.remaining() >= 4 {
+            let id = set.u16()?;
+            let count = set.u16()?;
+            let mut fields = Vec::new();
+            for _ in 0..count {
+                let kind = set.u16()?;
+                let len = set.u16()?;
+                match ipfix && kind & 0x8000 != 0 {
+                    true => {
+                        set.skip(4)?;
+                        fields.push(Field { kind: 0, len });
+                    }
+                    false => fields.push(Field { kind, len })
+                }
+            }
+            // a withdrawal, or a template there'd be no reading records by //
+            match min_len(&fields) {
+                0 => self.forget(exporter, domain, id),
+                _ => self.learn(exporter, domain, id, fields)
+            }
+        }
+        Some(())
+    }
+
+    // a new template past either cap is refused, one we have is still replaced //
+    fn learn(&mut self, exporter: IpAddr, domain: u32, id: u16, fields: Vec<Field>) {
+        let key = (exporter, domain, id);
+        if !self.templates.contains_key(&key) {
+            let count = self.per_exporter.get(&exporter).copied().unwrap_or(0);
+            if self.templates.len() >= MAX_TEMPLATES || count >= MAX_PER_EXPORTER {
+                if !self.refused {
+                    self.refused = true;
+                    log(format!("collector: too many templates, refusing new ones from {} and anyone else", exporter));
+                }
+                return;
+            }
+            self.per_exporter.insert(exporter, count + 1);
+        }
+        self.templates.insert(key, fields);
+    }
+
+    fn forget(&mut self, exporter: IpAddr, domain: u32, id: u16) {
+        if self.templates.remove(&(exporter, domain, id)).is_some() {
+            match self.per_exporter.get_mut(&exporter) {
+                Some(count) if *count > 1 => *count -= 1,
+                _ => { self.per_exporter.remove(&exporter); }
+            }
+        }
+    }
+}
+
+// the least a record can take up, a byte for each variable length field //
+fn min_len(fields: &[Field]) -> usize {
+    fields.iter().map(|field| if field.len == VARIABLE { 1 } else { field.len as usize }).sum()
+}
+
+fn v5(mut rd: Reader) -> Option<Vec<PacDat>> {
+    let count = rd.u16()?;
+    let uptime = rd.u32()? as i64;
+    let secs = rd.u32()? as i64;
+    let nsecs = rd.u32()? as i64;
+    rd.skip(6)?;    // sequence, engine type and id
+    let rate = (rd.u16()? & 0x3fff).max(1) as u64;
+    let clock = Clock { export: secs * 1000 + nsecs / 1_000_000, uptime: Some(uptime) };
+
+    let mut ret = Vec::new();
+    for _ in 0..count {
+        let mut flow = Flow { rate, ..Default::default() };
+        flow.src = Some(IpAddr::V4(rd.v4()?));
+        flow.dst = Some(IpAddr::V4(rd.v4()?));
+        rd.skip(8)?;    // next hop, interfaces
+        flow.packets = rd.u32()? as u64;
+        flow.bytes = rd.u32()? as u64;
+        rd.skip(4)?;    // first
+        flow.since_boot = Some(rd.u32()? as i64);
+        flow.src_port = rd.u16()?;
+        flow.dst_port = rd.u16()?;
+        rd.skip(2)?;    // pad, tcp flags
+        flow.proto = rd.u8()?;
+        rd.skip(9)?;    // tos, as numbers, masks, pad
+        ret.extend(flow.pac_dat(&clock));
+    }
+    Some(ret)
+}
+
+fn record(set: &mut Reader, fields: &[Field]) -> Option<Flow> {
+    let mut flow = Flow { rate: 1, ..Default::default() };
+    for field in fields {
+        let len = match field.len {
+            VARIABLE => match set.u8()? {
+                255 => set.u16()? as usize,
+                len => len as usize
+            },
+            len => len as usize
+        };
+        let data = set.bytes(len)?;
+        let n = || data.iter().take(8).fold(0u64, |acc, b| acc << 8 | *b as u64);
+
+        match field.kind {
+            1 => flow.bytes = n(),
+            2 => flow.packets = n(),
+            4 => flow.proto = n() as u8,
+            7 => flow.src_port = n() as u16,
+            11 => flow.dst_port = n() as u16,
+            8 if len == 4 => flow.src = Some(IpAddr::V4(Ipv4Addr::from(n() as u32))),
+            12 if len == 4 => flow.dst = Some(IpAddr::V4(Ipv4Addr::from(n() as u32))),
+            27 if len == 16 => flow.src = Some(IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(data).ok()?))),
+            28 if len == 16 => flow.dst = Some(IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(data).ok()?))),
+            34 => flow.rate = n().max(1),
+            21 => flow.since_boot = Some(n() as i64),
+            151 => flow.end = i64::try_from(n()).ok().and_then(|secs| secs.checked_mul(1000)),
+            153 => flow.end = i64::try_from(n()).ok(),
+            _ => {}
+        }
+    }
+    Some(flow)
+}
+
+impl Flow {
+    fn pac_dat(&self, clock: &Clock) -> Option<PacDat> {
+        let ip_number = IpNumber(self.proto);
+        if (ip_number != IpNumber::TCP && ip_number != IpNumber::UDP) || self.bytes.saturating_add(self.packets) == 0 {
+            return None;
+        }
+
+        // and none that make no sense: more packets than bytes, or too much of either //
+        let bytes = self.bytes.saturating_mul(self.rate);
+        let packets = self.packets.max(1).saturating_mul(self.rate);
+        if (self.bytes > 0 && self.packets > self.bytes) || bytes > MAX_BYTES || packets > MAX_BYTES {
+            return None;
+        }
+
+        let end = self.end
+            .or(self.since_boot.map(|ms| clock.since_boot(ms)))
+            .unwrap_or(clock.export);
+
+        Some(PacDat {
+            ts: DateTime::from_timestamp_millis(end).unwrap_or_else(Utc::now),
+            len: Some(bytes),
+            packets,
+            ip_number: Some(ip_number),
+            src_addr: Some(self.src?), dst_addr: Some(self.dst?),
+            src_port: Some(self.src_port), dst_port: Some(self.dst_port),
+            dir: None, foreign: None, local_traffic: None, sni: None
+        })
+    }
+}
+
+impl Clock {
+    fn since_boot(&self, ms: i64) -> i64 {
+        match self.uptime {
+            Some(uptime) => self.export.saturating_sub(uptime.saturating_sub(ms)),
+            None => self.export
+        }
+    }
+}
+
+// only flow samples with raw packet headers - counters tell us nothing about
+// who's talking //
+fn sflow(mut rd: Reader) -> Option<Vec<PacDat>> {
+    let agent = rd.u32()?;
+    rd.skip(if agent == 2 { 16 } else { 4 })?;
+    rd.skip(12)?;   // sub agent, sequence, uptime
+    let samples = rd.u32()?;
+
+    let mut ret = Vec::new();
+    for _ in 0..samples {
+        let format = rd.u32()?;
+        let len = rd.u32()? as usize;
+        let mut sample = rd.sub(len)?;
+
+        let expanded = match format {
+            1 => false,
+            3 => true,
+            _ => continue
+        };
+        sample.skip(if expanded { 12 } else { 8 })?;    // sequence, source
+        let rate = sample.u32()?.max(1) as u64;
+        sample.skip(if expanded { 24 } else { 16 })?;   // pool, drops, interfaces
+
+        for _ in 0..sample.u32()? {
+            let format = sample.u32()?;
+            let len = sample.u32()? as usize;
+            let mut record = sample.sub(len)?;
+            if format != 1 {
+                continue;
+            }
+
+            let proto = record.u32()?;
+            let frame_len = record.u32()? as u64;
+            record.skip(4)?;    // stripped
+            let header_len = record.u32()? as usize;
+            ret.extend(sampled(record.bytes(header_len)?, proto, frame_len * rate, rate));
+        }
+    }
+    Some(ret)
+}
+
+// the first so many bytes of one packet in every `rate` //
+fn sampled(header: &[u8], proto: u32, bytes: u64, rate: u64) -> Option<PacDat> {
+    let packet = match proto {
+        1 => LaxSlicedPacket::from_ethernet(header).ok()?,
+        11 | 12 => LaxSlicedPacket::from_ip(header).ok()?,
+        _ => return None
+    };
+
+    let (src, dst, ip_number) = match packet.net? {
+        LaxNetSlice::Ipv4(ip) => (IpAddr::V4(ip.header().source_addr()), IpAddr::V4(ip.header().destination_addr()), ip.payload().ip_number),
+        LaxNetSlice::Ipv6(ip) => (IpAddr::V6(ip.header().source_addr()), IpAddr::V6(ip.header().destination_addr()), ip.payload().ip_number)
+    };
+    let (src_port, dst_port) = match packet.transport? {
+        TransportSlice::Tcp(tcp) => (tcp.source_port(), tcp.destination_port()),
+        TransportSlice::Udp(udp) => (udp.source_port(), udp.destination_port()),
+        _ => return None
+    };
+
+    let flow = Flow {
+        src: Some(src), dst: Some(dst), src_port, dst_port, proto: ip_number.0, bytes, packets: rate, rate: 1, end: None, since_boot: None
+    };
+    flow.pac_dat(&Clock { export: Utc::now().timestamp_millis(), uptime: None })
+}
+
+pub fn run(sock: UdpSocket, tx: Sender<PacDat>, q_depth: Arc<AtomicU64>) {
+    let mut collector = Collector::new();
+    let mut buf = [0u8; 65535];
+    loop {
+        match sock.recv_from(&mut buf) {
+            Ok((len, from)) => {
+                for pac_dat in collector.decode(from.ip(), &buf[..len]) {
+                    match tx.send(pac_dat) {
+                        Ok(_) => q_depth.fetch_add(1, Ordering::Relaxed),
+                        Err(err) => panic!("tx failed: {}", err)
+                    };
+                }
+            }
+            Err(err) => log(format!("collector: {}", err))
+        }
+    }
+}
+
+// big endian, None once it runs out //
+struct Reader<'a> {
+    buf: &'a [u8],
+    at: usize
+}
+
+impl<'a> Reader<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Reader { buf, at: 0 }
+    }
+
+    fn remaining(&self) -> usize {
+        self.buf.len() - self.at
+    }
+
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let ret = self.buf.get(self.at..self.at.checked_add(len)?)?;
+        self.at += len;
+        Some(ret)
+    }
+
+    fn sub(&mut self, len: usize) -> Option<Reader<'a>> {
+        self.bytes(len).map(Reader::new)
+    }
+
+    fn skip(&mut self, len: usize) -> Option<()> {
+        self.bytes(len).map(|_| ())
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.bytes(1).map(|b| b[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.bytes(2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.bytes(4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn v4(&mut self) -> Option<Ipv4Addr> {
+        self.u32().map(Ipv4Addr::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv6Addr;
+
+    use etherparse::{IpNumber, PacketBuilder};
+
+    use crate::collector::{Clock, Collector, Field, Flow, MAX_BYTES, MAX_PER_EXPORTER, MAX_TEMPLATES, Reader, record};
+    use crate::pacstream::PacStream;
+    use crate::subnets::addr;
+
+    const SECS: u32 = 1_700_000_000;
+
+    fn put16(buf: &mut Vec<u8>, n: u16) {
+        buf.extend(n.to_be_bytes());
+    }
+
+    fn put32(buf: &mut Vec<u8>, n: u32) {
+        buf.extend(n.to_be_bytes());
+    }
+
+    #[test]
+    fn test_v5() {
+        let mut buf = Vec::new();
+        put16(&mut buf, 5);
+        put16(&mut buf, 1);
+        put32(&mut buf, 60_000);        // uptime
+        put32(&mut buf, SECS);
+        put32(&mut buf, 0);
+        put32(&mut buf, 0);             // sequence
+        put16(&mut buf, 0);             // engine
+        put16(&mut buf, 0x4000 | 10);   // sampled 1 in 10
+
+        buf.extend([10, 0, 0, 5, 1, 1, 1, 1, 0, 0, 0, 0]);
+        put32(&mut buf, 0);             // interfaces
+        put32(&mut buf, 3);
+        put32(&mut buf, 4500);
+        put32(&mut buf, 50_000);        // first
+        put32(&mut buf, 58_000);        // last
+        put16(&mut buf, 50000);
+        put16(&mut buf, 443);
+        buf.extend([0, 0, 6, 0]);
+        buf.extend([0; 8]);
+
+        let pac_dats = Collector::new().decode(addr("10.0.0.1"), &buf);
+        assert_eq!(1, pac_dats.len());
+        let pac_dat = &pac_dats[0];
+        assert_eq!(Some(addr("10.0.0.5")), pac_dat.src_addr);
+        assert_eq!(Some(addr("1.1.1.1")), pac_dat.dst_addr);
+        assert_eq!(Some(443), pac_dat.dst_port);
+        assert_eq!(Some(IpNumber::TCP), pac_dat.ip_number);
+        assert_eq!(Some(45000), pac_dat.len);
+        assert_eq!(30, pac_dat.packets);
+        assert_eq!(SECS as i64 * 1000 - 2000, pac_dat.ts.timestamp_millis());
+
+        assert!(Collector::new().decode(addr("10.0.0.1"), &buf[..40]).is_empty());
+    }
+
+    // a template with an enterprise field, then a record for it //
+    #[test]
+    fn test_ipfix() {
+        let mut sets = Vec::new();
+        put16(&mut sets, 2);
+        put16(&mut sets, 4 + 4 + 7 * 4 + 4);
+        put16(&mut sets, 300);
+        put16(&mut sets, 7);
+        for (kind, len) in [(27u16, 16u16), (28, 16), (7, 2), (11, 2), (4, 1), (1, 4), (0x8001, 4)] {
+            put16(&mut sets, kind);
+            put16(&mut sets, len);
+            if kind & 0x8000 != 0 {
+                put32(&mut sets, 32473);
+            }
+        }
+
+        put16(&mut sets, 300);
+        put16(&mut sets, 4 + 45 + 3);
+        sets.extend("2606:4700::1111".parse::<Ipv6Addr>().unwrap().octets());
+        sets.extend("fd00::2".parse::<Ipv6Addr>().unwrap().octets());
+        put16(&mut sets, 53);
+        put16(&mut sets, 40000);
+        sets.push(17);
+        put32(&mut sets, 512);
+        put32(&mut sets, 0xdeadbeef);
+        sets.extend([0; 3]);
+
+        let mut buf = Vec::new();
+        put16(&mut buf, 10);
+        put16(&mut buf, 16 + sets.len() as u16);
+        put32(&mut buf, SECS);
+        put32(&mut buf, 0);
+        put32(&mut buf, 0);
+        buf.extend(sets);
+
+        let mut collector = Collector::new();
+        let pac_dats = collector.decode(addr("10.0.0.1"), &buf);
+        assert_eq!(1, pac_dats.len());
+        assert_eq!(Some(addr("2606:4700::1111")), pac_dats[0].src_addr);
+        assert_eq!(Some(addr("fd00::2")), pac_dats[0].dst_addr);
+        assert_eq!(Some(40000), pac_dats[0].dst_port);
+        assert_eq!(Some(512), pac_dats[0].len);
+        // no packet count, so at least the one //
+        assert_eq!(1, pac_dats[0].packets);
+    }
+
+    // a withdrawal and a template of nothing, each with a data set, mustn't
+    // go round forever //
+    #[test]
+    fn test_empty_template() {
+        let mut buf = Vec::new();
+        put16(&mut buf, 10);
+        put16(&mut buf, 16 + 12 + 16);
+        put32(&mut buf, SECS);
+        put32(&mut buf, 0);
+        put32(&mut buf, 0);
+
+        put16(&mut buf, 2);
+        put16(&mut buf, 4 + 4 + 8);
+        put16(&mut buf, 300);
+        put16(&mut buf, 0);
+        put16(&mut buf, 301);
+        put16(&mut buf, 1);
+        put16(&mut buf, 1);
+        put16(&mut buf, 0);
+
+        for id in [300, 301] {
+            put16(&mut buf, id);
+            put16(&mut buf, 4 + 4);
+            put32(&mut buf, 0xdeadbeef);
+        }
+
+        let mut collector = Collector::new();
+        collector.learn(addr("10.0.0.1"), 0, 300, vec![Field { kind: 1, len: 4 }]);
+        assert!(collector.decode(addr("10.0.0.1"), &buf).is_empty());
+        assert!(collector.templates.is_empty());
+        assert!(collector.per_exporter.is_empty());
+    }
+
+    #[test]
+    fn test_too_many_templates() {
+        let fields = vec![Field { kind: 1, len: 4 }];
+        let mut collector = Collector::new();
+        for id in 0..=MAX_PER_EXPORTER as u16 {
+            collector.learn(addr("10.0.0.1"), 0, 256 + id, fields.clone());
+        }
+        assert_eq!(MAX_PER_EXPORTER, collector.templates.len());
+        assert!(collector.refused);
+
+        // replacing one is fine, and a withdrawal makes room //
+        collector.learn(addr("10.0.0.1"), 0, 256, vec![Field { kind: 2, len: 4 }]);
+        assert_eq!(2, collector.templates[&(addr("10.0.0.1"), 0, 256)][0].kind);
+        collector.forget(addr("10.0.0.1"), 0, 256);
+        collector.learn(addr("10.0.0.1"), 1, 256, fields.clone());
+        assert_eq!(MAX_PER_EXPORTER, collector.templates.len());
+
+        let mut n = 0;
+        while collector.templates.len() < MAX_TEMPLATES {
+            n += 1;
+            collector.learn(std::net::IpAddr::from([10, 1, (n >> 8) as u8, n as u8]), 0, 256, fields.clone());
+        }
+        collector.learn(addr("10.2.0.1"), 0, 256, fields);
+        assert_eq!(MAX_TEMPLATES, collector.templates.len());
+        assert!(!collector.templates.contains_key(&(addr("10.2.0.1"), 0, 256)));
+    }
+
+    #[test]
+    fn test_v9() {
+        let mut buf = Vec::new();
+        put16(&mut buf, 9);
+        put16(&mut buf, 2);
+        put32(&mut buf, 100_000);
+        put32(&mut buf, SECS);
+        put32(&mut buf, 0);
+        put32(&mut buf, 7);
+
+        put16(&mut buf, 0);
+        put16(&mut buf, 4 + 4 + 8 * 4);
+        put16(&mut buf, 256);
+        put16(&mut buf, 8);
+        for (kind, len) in [(8u16, 4u16), (12, 4), (7, 2), (11, 2), (4, 1), (1, 4), (2, 4), (21, 4)] {
+            put16(&mut buf, kind);
+            put16(&mut buf, len);
+        }
+
+        put16(&mut buf, 256);
+        put16(&mut buf, 4 + 2 * 25 + 2);
+        for (src, packets) in [([192, 168, 1, 9], 2u32), ([192, 168, 1, 10], 5)] {
+            buf.extend(src);
+            buf.extend([8, 8, 8, 8]);
+            put16(&mut buf, 5353);
+            put16(&mut buf, 53);
+            buf.push(17);
+            put32(&mut buf, packets * 100);
+            put32(&mut buf, packets);
+            put32(&mut buf, 99_000);
+        }
+        buf.extend([0; 2]);
+
+        let mut collector = Collector::new();
+        let pac_dats = collector.decode(addr("10.0.0.1"), &buf);
+        assert_eq!(2, pac_dats.len());
+        assert_eq!(Some(addr("192.168.1.10")), pac_dats[1].src_addr);
+        assert_eq!(Some(500), pac_dats[1].len);
+        assert_eq!(5, pac_dats[1].packets);
+        assert_eq!(SECS as i64 * 1000 - 1000, pac_dats[1].ts.timestamp_millis());
+
+        // templates are per exporter //
+        assert!(collector.decode(addr("10.0.0.2"), &buf[..20].iter().chain(&buf[60..]).cloned().collect::<Vec<u8>>()).is_empty());
+    }
+
+    // the exporter's numbers, however big //
+    #[test]
+    fn test_nonsense() {
+        let clock = Clock { export: SECS as i64 * 1000, uptime: None };
+        let flow = |bytes, packets, rate| Flow {
+            src: Some(addr("10.0.0.5")), dst: Some(addr("1.1.1.1")), proto: 6, bytes, packets, rate, ..Default::default()
+        };
+        assert_eq!(Some(10_000), flow(1000, 10, 10).pac_dat(&clock).unwrap().len);
+        assert!(flow(u64::MAX, u64::MAX, 1).pac_dat(&clock).is_none());
+        assert!(flow(1000, 10, u64::MAX).pac_dat(&clock).is_none());
+        assert!(flow(10, 1000, 1).pac_dat(&clock).is_none());
+
+        let secs = u64::MAX.to_be_bytes();
+        assert_eq!(None, record(&mut Reader::new(&secs), &[Field { kind: 151, len: 8 }]).unwrap().end);
+        assert_eq!(-i64::MAX, Clock { export: 0, uptime: Some(0) }.since_boot(i64::MIN));
+    }
+
+    // each record passes on its own, a flood of them mustn't wrap the totals //
+    #[test]
+    fn test_flood() {
+        let clock = Clock { export: SECS as i64 * 1000, uptime: None };
+        let mut pac_dat = Flow {
+            src: Some(addr("10.0.0.5")), dst: Some(addr("1.1.1.1")), proto: 6, bytes: MAX_BYTES, packets: MAX_BYTES, rate: 1, ..Default::default()
+        }.pac_dat(&clock).unwrap();
+        pac_dat.foreign = Some(true);
+        pac_dat.local_traffic = Some(false);
+        let mut stream = PacStream::new(&pac_dat);
+        for _ in 0..20_000 {
+            stream.tally(&pac_dat);
+        }
+        assert_eq!(u64::MAX, stream.bytes_recv);
+        assert_eq!(u64::MAX, stream.packets_in);
+        assert_eq!(u64::MAX, stream.bytes());
+        assert_eq!(u64::MAX, stream.bytes_last());
+        stream.push_history(1);
+    }
+
+    #[test]
+    fn test_sflow() {
+        let builder = PacketBuilder::ethernet2([1, 2, 3, 4, 5, 6], [7, 8, 9, 10, 11, 12])
+            .ipv4([192, 168, 1, 2], [140, 82, 112, 3], 64)
+            .tcp(50000, 443, 1, 1000);
+        let payload = [0u8; 1000];
+        let mut frame = Vec::new();
+        builder.write(&mut frame, &payload).unwrap();
+        let header = &frame[..128];
+
+        let mut record = Vec::new();
+        put32(&mut record, 1);                          // ethernet
+        put32(&mut record, frame.len() as u32);
+        put32(&mut record, 4);
+        put32(&mut record, header.len() as u32);
+        record.extend(header);
+
+        let mut sample = Vec::new();
+        put32(&mut sample, 1);                          // sequence
+        put32(&mut sample, 3);                          // source
+        put32(&mut sample, 400);                        // rate
+        sample.extend([0; 16]);
+        put32(&mut sample, 1);
+        put32(&mut sample, 1);                          // raw header
+        put32(&mut sample, record.len() as u32);
+        sample.extend(record);
+
+        let mut buf = Vec::new();
+        put32(&mut buf, 5);
+        put32(&mut buf, 1);
+        buf.extend([10, 0, 0, 1]);
+        buf.extend([0; 12]);
+        put32(&mut buf, 2);
+        put32(&mut buf, 2);                             // counters, skipped
+        put32(&mut buf, 4);
+        buf.extend([0; 4]);
+        put32(&mut buf, 1);
+        put32(&mut buf, sample.len() as u32);
+        buf.extend(sample);
+
+        let pac_dats = Collector::new().decode(addr("10.0.0.1"), &buf);
+        assert_eq!(1, pac_dats.len());
+        assert_eq!(Some(addr("140.82.112.3")), pac_dats[0].dst_addr);
+        assert_eq!(Some(443), pac_dats[0].dst_port);
+        assert_eq!(Some(frame.len() as u64 * 400), pac_dats[0].len);
+        assert_eq!(400, pac_dats[0].packets);
+    }
+}
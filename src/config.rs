@@ -0,0 +1,476 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::fs::read_to_string;
+use std::path::PathBuf;
+
+use crate::etc;
+use crate::etc::log;
+
+// ~/.config/pacmon/config.toml (or /etc/pacmon.toml) - the defaults for a session:
+//
+//   interval = 2000             # redraw, and write the outputs below, ms
+//   sort = "total"              # rate in-rate out-rate in-total out-total total packets age proc host cc corp
+//   reverse = false
+//   mode = "corp"               # normal corp cloud graph
+//   resolve = true
+//   interface = "eth0"
+//   filter = "!cc:US"
+//   columns = ["proc", "remote", "rate", "total", "cc", "corp"]
+//   log = false                 # ./pacmon.log
+//   save = false                # write the above back on quit
+//   user = "nobody"             # who to run as once capturing, when started as root
+//   batch = false               # plain text tables on stdout instead of curses..
+//   count = 10                  # ..this many of them
+//   duration = 60               # ..or for this many seconds
+//   width = 120                 # ..fitted to this many columns
+//   rows = 20                   # ..this many rows each
+//   json = "/var/log/pacmon.jsonl"   # a line per active stream per interval, "-" for stdout (no ui)
+//   csv = "/var/log/pacmon.csv"      # likewise
+//   prometheus = "127.0.0.1:9474"    # serve /metrics here, a bare port for localhost
+//   top = 20                    # most values a metrics label takes before "other"
+//   statsd = "127.0.0.1:8125"   # push per proc and corp byte rates here each interval..
+//   graphite = "carbon:2003"    # ..or here, over udp
+//   prefix = "pacmon"           # ..named <prefix>.<proc|corp>.<name>.<in|out>
+//   flows = "collector:4739"    # export flow records, with proc and pid, here..
+//   flow_format = "ipfix"       # ..as ipfix or v9
+//   active_timeout = 60         # ..every so many seconds for a stream that's still going
+//   idle_timeout = 15           # ..or once it's been quiet this long
+//   collect = 2055              # take netflow/ipfix/sflow from routers on this udp port instead of capturing..
+//   networks = ["10.0.0.0/8", "192.168.0.0/16"]   # ..with these as our side (default rfc 1918 and fc00::/7)
+//   history = "~/.local/share/pacmon/history.db"  # keep per interval totals here, see pacmon query..
+//   retention = 30              # ..for this many days
+//   downsample = 24             # ..rolled up into hours after this many hours
+//   headless = false            # no ui and no tables, for running as an agent
+//   summary = "-"               # what the session added up to, on quit, "-" for stdout..
+//   summary_format = "text"     # ..as text, json or html (default by the file's extension)..
+//   summary_top = 10            # ..with this many rows per table
+//
+//   [paths]                     # instead of ~/.config/pacmon/<name>
+//   labels = "/srv/pacmon/labels"
+//   cloud = "/srv/pacmon/cloud"
+//   blocklists = "/srv/pacmon/blocklists"
+//   asn = "/srv/pacmon/pfx2as.txt"
+//   theme = "/srv/pacmon/theme"
+//   columns = "/srv/pacmon/columns"
+//
+//   [colours]                   # same as the theme file
+//   heat3 = "red,default,bold"
+//
+// only as much toml as that needs: strings, integers, booleans, one line arrays and tables
+pub struct Config {
+    path: Option<PathBuf>,
+    values: BTreeMap<String, Value>
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Value {
+    Str(String),
+    Int(i64),
+    Bool(bool),
+    List(Vec<String>)
+}
+
+const KEYS: [&str; 36] = [
+    "interval", "sort", "reverse", "mode", "resolve", "interface", "filter", "columns", "log", "save", "user",
+    "batch", "count", "duration", "width", "rows", "json", "csv", "prometheus", "top", "headless",
+    "statsd", "graphite", "prefix", "flows", "flow_format", "active_timeout", "idle_timeout",
+    "collect", "networks", "history", "retention", "downsample", "summary", "summary_format", "summary_top"
+];
+const SECTIONS: [&str; 2] = ["paths", "colours"];
+
+// checked as they're read, so a getter has nothing to complain about later
+// (by which time curses may have the terminal). the rest take anything //
+const INTS: [&str; 11] = [
+    "interval", "count", "duration", "width", "rows", "top", "active_timeout", "idle_timeout", "retention", "downsample",
+    "summary_top"
+];
+const BOOLS: [&str; 6] = ["reverse", "resolve", "log", "save", "batch", "headless"];
+
+impl Config {
+    pub fn new() -> Self {
+        Config { path: None, values: BTreeMap::new() }
+    }
+
+    pub fn load() -> Self {
+        let path = etc::config_file("config.toml")
+            .or_else(|| Some(PathBuf::from("/etc/pacmon.toml")).filter(|path| path.exists()));
+
+        match path {
+            Some(path) => Config::load_from(path),
+            None => Config::new()
+        }
+    }
+
+    pub fn load_from(path: PathBuf) -> Self {
+        let mut config = Config::new();
+        match read_to_string(&path) {
+            Ok(txt) => {
+                for msg in config.parse(&txt) {
+                    eprintln!("{}: {}", path.display(), msg);
+                }
+            }
+            Err(err) => eprintln!("{}: {}", path.display(), err)
+        }
+        config.path = Some(path);
+        config
+    }
+
+    // returns what it couldn't make sense of //
+    fn parse(&mut self, txt: &str) -> Vec<String> {
+        let mut errs = Vec::new();
+        let mut section = String::new();
+
+        for (n, line) in txt.lines().enumerate() {
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if line.starts_with('[') && line.ends_with(']') {
+                section = line[1..line.len() - 1].trim().to_string();
+                if !SECTIONS.contains(&section.as_str()) {
+                    errs.push(format!("line {}: unknown table [{}]", n + 1, section));
+                }
+                continue;
+            }
+
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim().trim_matches('"'), value.trim()),
+                None => {
+                    errs.push(format!("line {}: expected key = value", n + 1));
+                    continue;
+                }
+            };
+
+            if section.is_empty() && !KEYS.contains(&key) {
+                errs.push(format!("line {}: unknown key [{}]", n + 1, key));
+                continue;
+            }
+
+            match parse_value(value) {
+                Ok(value) if section.is_empty() && INTS.contains(&key) && !matches!(value, Value::Int(_)) => {
+                    errs.push(format!("line {}: {} should be a number", n + 1, key));
+                }
+                Ok(value) if section.is_empty() && BOOLS.contains(&key) && !matches!(value, Value::Bool(_)) => {
+                    errs.push(format!("line {}: {} should be true or false", n + 1, key));
+                }
+                Ok(value) => {
+                    let key = match section.is_empty() {
+                        true => key.to_string(),
+                        false => format!("{}.{}", section, key)
+                    };
+                    self.values.insert(key, value);
+                }
+                Err(msg) => errs.push(format!("line {}: {}", n + 1, msg))
+            }
+        }
+        errs
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.values.get(key)
+    }
+
+    pub fn get_str(&self, key: &str) -> Option<String> {
+        match self.get(key) {
+            Some(Value::Str(txt)) => Some(txt.to_string()),
+            Some(Value::List(list)) => Some(list.join(" ")),
+            Some(Value::Int(n)) => Some(n.to_string()),
+            Some(Value::Bool(b)) => Some(b.to_string()),
+            None => None
+        }
+    }
+
+    pub fn get_int(&self, key: &str) -> Option<i64> {
+        match self.get(key) {
+            Some(Value::Int(n)) => Some(*n),
+            _ => None
+        }
+    }
+
+    pub fn get_bool(&self, key: &str) -> Option<bool> {
+        match self.get(key) {
+            Some(Value::Bool(b)) => Some(*b),
+            _ => None
+        }
+    }
+
+    pub fn set(&mut self, key: &str, value: Value) {
+        self.values.insert(key.to_string(), value);
+    }
+
+    // a [paths] entry or else the usual place //
+    pub fn path(&self, name: &str) -> Option<PathBuf> {
+        match self.get_str(&format!("paths.{}", name)) {
+            Some(path) => Some(expand(&path)),
+            None => etc::config_file(name)
+        }
+    }
+
+    // a top level key naming a file, ~ and all //
+    pub fn get_path(&self, key: &str) -> Option<PathBuf> {
+        self.get_str(key).map(|path| expand(&path))
+    }
+
+    // the [colours] table as theme file lines //
+    pub fn colours(&self) -> String {
+        self.values.keys()
+            .filter_map(|key| key.strip_prefix("colours."))
+            .map(|name| format!("{} = {}\n", name, self.get_str(&format!("colours.{}", name)).unwrap()))
+            .collect()
+    }
+
+    // writes the given top level keys back, leaving everything else as it was //
+    pub fn save(&self, keys: &[&str]) -> Result<PathBuf, String> {
+        let path = match &self.path {
+            Some(path) => path.clone(),
+            None => match env::var("XDG_CONFIG_HOME").or_else(|_| env::var("HOME").map(|home| home + "/.config")) {
+                Ok(dir) => PathBuf::from(dir).join("pacmon/config.toml"),
+                Err(_) => return Err("nowhere to save the config".to_string())
+            }
+        };
+
+        let mut txt = read_to_string(&path).unwrap_or_default();
+        for key in keys {
+            if let Some(value) = self.values.get(*key) {
+                txt = update(&txt, key, value);
+            }
+        }
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        }
+        fs::write(&path, txt).map_err(|e| format!("{}: {}", path.display(), e))?;
+        log(format!("config: saved {:?} to {}", keys, path.display()));
+        Ok(path)
+    }
+}
+
+// replace the key's line if it's there, otherwise add it ahead of the first table //
+fn update(txt: &str, key: &str, value: &Value) -> String {
+    let new_line = format!("{} = {}", key, value);
+    let mut lines: Vec<String> = txt.lines().map(|l| l.to_string()).collect();
+
+    let top = lines.iter().position(|l| l.trim_start().starts_with('[')).unwrap_or(lines.len());
+    let existing = lines[..top].iter().position(|l| {
+        let l = strip_comment(l);
+        l.split_once('=').is_some_and(|(k, _)| k.trim() == key)
+    });
+
+    match existing {
+        Some(i) => {
+            // keep any comment on the end of the line //
+            let comment = &lines[i][strip_comment(&lines[i]).len()..];
+            lines[i] = match comment.is_empty() {
+                true => new_line,
+                false => format!("{}   {}", new_line, comment.trim_start())
+            };
+        }
+        None => {
+            // after the last top level setting rather than after a blank line //
+            let mut at = top;
+            while at > 0 && lines[at - 1].trim().is_empty() {
+                at -= 1;
+            }
+            lines.insert(at, new_line);
+        }
+    }
+
+    let mut ret = lines.join("\n");
+    ret.push('\n');
+    ret
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(q), c) if c == q && !escaped => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '#') => return &line[..i],
+            _ => {}
+        }
+        escaped = false;
+    }
+    line
+}
+
+fn parse_value(txt: &str) -> Result<Value, String> {
+    if txt == "true" || txt == "false" {
+        return Ok(Value::Bool(txt == "true"));
+    }
+
+    if txt.starts_with('[') {
+        let inner = txt.strip_suffix(']').ok_or(format!("unterminated array [{}]", txt))?[1..].trim();
+        let mut list = Vec::new();
+        for item in split_items(inner) {
+            match parse_value(item.trim())? {
+                Value::Str(txt) => list.push(txt),
+                Value::Int(n) => list.push(n.to_string()),
+                _ => return Err(format!("arrays of strings only [{}]", txt))
+            }
+        }
+        return Ok(Value::List(list));
+    }
+
+    if let Some(inner) = txt.strip_prefix('"') {
+        let inner = inner.strip_suffix('"').ok_or(format!("unterminated string [{}]", txt))?;
+        return unescape(inner).map(Value::Str);
+    }
+
+    if let Some(inner) = txt.strip_prefix('\'') {
+        let inner = inner.strip_suffix('\'').ok_or(format!("unterminated string [{}]", txt))?;
+        return Ok(Value::Str(inner.to_string()));
+    }
+
+    match txt.replace('_', "").parse::<i64>() {
+        Ok(n) => Ok(Value::Int(n)),
+        Err(_) => Err(format!("can't make sense of [{}] - strings need quotes", txt))
+    }
+}
+
+// commas outside of quotes, ignoring a trailing one //
+fn split_items(txt: &str) -> Vec<&str> {
+    let mut ret = Vec::new();
+    let mut quote = None;
+    let mut start = 0;
+    for (i, c) in txt.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, ',') => {
+                ret.push(&txt[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    ret.push(&txt[start..]);
+    ret.into_iter().filter(|item| !item.trim().is_empty()).collect()
+}
+
+fn unescape(txt: &str) -> Result<String, String> {
+    let mut ret = String::new();
+    let mut chars = txt.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            ret.push(c);
+            continue;
+        }
+        ret.push(match chars.next() {
+            Some('"') => '"',
+            Some('\\') => '\\',
+            Some('n') => '\n',
+            Some('t') => '\t',
+            other => return Err(format!("unsupported escape \\{}", other.map(String::from).unwrap_or_default()))
+        });
+    }
+    Ok(ret)
+}
+
+fn expand(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), env::var("HOME")) {
+        (Some(rest), Ok(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(path)
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn quote(txt: &str) -> String {
+            format!("\"{}\"", txt.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n").replace('\t', "\\t"))
+        }
+        match self {
+            Value::Str(txt) => write!(f, "{}", quote(txt)),
+            Value::Int(n) => write!(f, "{}", n),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::List(list) => write!(f, "[{}]", list.iter().map(|item| quote(item)).collect::<Vec<String>>().join(", "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{BOOLS, Config, INTS, KEYS, parse_value, update, Value};
+
+    #[test]
+    fn test_parse() {
+        let mut config = Config::new();
+        let errs = config.parse(r#"
+# pacmon
+interval = 2_000          # ms
+sort = "total"
+reverse = true
+filter = "host:\"x\" # not a comment"
+columns = ["proc", 'remote', "cc",]
+mode = corp
+colour = "red"
+
+[paths]
+labels = "~/labels"
+
+[colours]
+in = "blue"
+heat3 = "red,default,bold"
+
+[nope]
+"#);
+        assert_eq!(3, errs.len(), "{:?}", errs);
+        assert_eq!(Some(2000), config.get_int("interval"));
+        assert_eq!(Some("total".to_string()), config.get_str("sort"));
+        assert_eq!(Some(true), config.get_bool("reverse"));
+        assert_eq!(Some("host:\"x\" # not a comment".to_string()), config.get_str("filter"));
+        assert_eq!(Some("proc remote cc".to_string()), config.get_str("columns"));
+        assert_eq!(None, config.get("mode"));
+        assert_eq!(Some("~/labels".to_string()), config.get_str("paths.labels"));
+        assert_eq!("heat3 = red,default,bold\nin = blue\n", config.colours());
+    }
+
+    // caught while reading, before there's a ui to scribble over //
+    #[test]
+    fn test_types() {
+        let mut config = Config::new();
+        let errs = config.parse("interval = \"fast\"\nsave = 1\nresolve = false\ntop = 5\ncollect = 2055\nuser = true\n");
+        assert_eq!(vec!["line 1: interval should be a number", "line 2: save should be true or false"], errs);
+        assert_eq!((None, None), (config.get("interval"), config.get("save")));
+        assert_eq!((Some(false), Some(5)), (config.get_bool("resolve"), config.get_int("top")));
+        assert_eq!(Some("2055".to_string()), config.get_str("collect"));
+        assert_eq!(Some("true".to_string()), config.get_str("user"));
+        assert!(INTS.iter().chain(&BOOLS).all(|key| KEYS.contains(key)));
+    }
+
+    #[test]
+    fn test_values() {
+        assert_eq!(Ok(Value::Int(-5)), parse_value("-5"));
+        assert_eq!(Ok(Value::List(vec![])), parse_value("[]"));
+        assert_eq!(Ok(Value::List(vec!["a,b".to_string(), "1".to_string()])), parse_value(r#"["a,b", 1]"#));
+        assert!(parse_value("\"open").is_err());
+        assert!(parse_value("[true]").is_err());
+        assert!(parse_value("\"\\q\"").is_err());
+
+        for value in [Value::Str("a \"b\" \\ c".to_string()), Value::Int(3), Value::Bool(false), Value::List(vec!["x".to_string(), "y\"".to_string()])] {
+            assert_eq!(Ok(value.clone()), parse_value(&value.to_string()));
+        }
+    }
+
+    #[test]
+    fn test_update() {
+        let txt = "# mine\ninterval = 1000   # fast\n\n[paths]\nlabels = \"x\"\n";
+        let txt = update(txt, "interval", &Value::Int(2000));
+        assert_eq!("# mine\ninterval = 2000   # fast\n\n[paths]\nlabels = \"x\"\n", txt);
+        let txt = update(&txt, "sort", &Value::Str("cc".to_string()));
+        assert_eq!("# mine\ninterval = 2000   # fast\nsort = \"cc\"\n\n[paths]\nlabels = \"x\"\n", txt);
+
+        // labels in [paths] isn't a top level key //
+        let txt = update(&txt, "labels", &Value::Bool(true));
+        assert!(txt.contains("sort = \"cc\"\nlabels = true\n\n[paths]\nlabels = \"x\""), "{}", txt);
+
+        assert_eq!("resolve = false\n", update("", "resolve", &Value::Bool(false)));
+    }
+}
@@ -1,8 +1,9 @@
 use std::time::Duration;
 
-use std::{fs};
+use std::{env, fs};
 use std::fs::{File, OpenOptions};
 use std::io::Write;
+use std::path::PathBuf;
 use std::sync::Mutex;
 use chrono::{DateTime, Local, NaiveDateTime, Utc};
 
@@ -77,6 +78,20 @@ pub fn fmt_duration(duration: Duration) -> String {
     }
 }
 
+// first of ~/.config/pacmon/<name> or /etc/pacmon/<name> that exists //
+pub fn config_file(name:&str) -> Option<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Ok(dir) = env::var("XDG_CONFIG_HOME") {
+        dirs.push(PathBuf::from(dir).join("pacmon"));
+    }
+    if let Ok(home) = env::var("HOME") {
+        dirs.push(PathBuf::from(home).join(".config/pacmon"));
+    }
+    dirs.push(PathBuf::from("/etc/pacmon"));
+
+    dirs.into_iter().map(|dir| dir.join(name)).find(|path| path.exists())
+}
+
 pub fn millitime() -> i64 {
     Utc::now().timestamp_millis()
 }
@@ -0,0 +1,315 @@
+use std::fmt;
+use std::fmt::Formatter;
+
+use regex::Regex;
+
+use crate::etc;
+use crate::pacstream::PacStream;
+
+// a little expression language for narrowing down the streams shown:
+//
+//   <firefox>              proc is firefox
+//   cc:CN                  field matches value (case insensitive, * and ? are wildcards)
+//   host:*.github.com
+//   port:5432 port>1024    local or remote port. also lport, rport, pid, uid, bytes
+//   !cc:US  -proc:sshd     negated
+//   flagged                on a blocklist
+//   github                 bare words match proc, hosts, corp, cc, cloud and tag
+//   cc:CN or cc:RU         terms are and'ed, 'or' separates alternatives
+//
+// fields: proc pid uid user host local addr port lport rport svc sni cc city corp asn tag cloud list proto bytes
+pub struct Filter {
+    txt: String,
+    alternatives: Vec<Vec<Term>>
+}
+
+struct Term {
+    negate: bool,
+    test: Test
+}
+
+enum Test {
+    Text(Field, Regex),
+    Num(Field, Op, u64),
+    Any(Regex),
+    Flagged
+}
+
+#[derive(Clone, Copy)]
+enum Field {
+    Proc, Pid, Uid, User, Host, Local, Addr, Port, LPort, RPort, Svc, Sni, Cc, City, Corp, Asn, Tag, Cloud, List, Proto, Bytes
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Op {
+    Eq, Ne, Gt, Lt
+}
+
+impl Filter {
+    pub fn parse(txt:&str) -> Result<Filter, String> {
+        let mut alternatives = vec![vec![]];
+
+        for word in txt.split_whitespace() {
+            if word.eq_ignore_ascii_case("or") || word == "|" {
+                alternatives.push(vec![]);
+                continue;
+            }
+            alternatives.last_mut().unwrap().push(Term::parse(word)?);
+        }
+
+        if alternatives.iter().any(|terms| terms.is_empty()) {
+            return Err(format!("empty alternative in [{}]", txt));
+        }
+
+        Ok(Filter { txt: txt.trim().to_string(), alternatives })
+    }
+
+    pub fn matches(&self, stream:&PacStream) -> bool {
+        self.alternatives.iter().any(|terms| terms.iter().all(|term| term.matches(stream)))
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.txt)
+    }
+}
+
+impl Term {
+    fn parse(word:&str) -> Result<Term, String> {
+        let (negate, word) = match word.strip_prefix(['!', '-']) {
+            Some(rest) if !rest.is_empty() => (true, rest),
+            _ => (false, word)
+        };
+
+        if word.eq_ignore_ascii_case("flagged") {
+            return Ok(Term { negate, test: Test::Flagged });
+        }
+
+        if word.len() > 2 && word.starts_with('<') && word.ends_with('>') {
+            let value = &word[1..word.len() - 1];
+            return Ok(Term { negate, test: Test::Text(Field::Proc, glob(value)?) });
+        }
+
+        let pos = match word.find([':', '=', '>', '<', '!']) {
+            Some(pos) if pos > 0 => pos,
+            _ => return Ok(Term { negate, test: Test::Any(glob(&format!("*{}*", word))?) })
+        };
+
+        let field = Field::parse(&word[..pos])?;
+        let (op, value) = match &word[pos..] {
+            rest if rest.starts_with("!=") => (Op::Ne, &rest[2..]),
+            rest if rest.starts_with(':') || rest.starts_with('=') => (Op::Eq, &rest[1..]),
+            rest if rest.starts_with('>') => (Op::Gt, &rest[1..]),
+            rest if rest.starts_with('<') => (Op::Lt, &rest[1..]),
+            rest => return Err(format!("bad operator [{}]", rest))
+        };
+
+        if value.is_empty() {
+            return Err(format!("missing value in [{}]", word));
+        }
+
+        let negate = negate ^ (op == Op::Ne);
+
+        let test = match (field.numeric(), op) {
+            (true, _) => Test::Num(field, op, parse_num(value)?),
+            (false, Op::Eq | Op::Ne) => Test::Text(field, glob(value)?),
+            (false, _) => return Err(format!("can't compare [{}]", word))
+        };
+
+        Ok(Term { negate, test })
+    }
+
+    fn matches(&self, stream:&PacStream) -> bool {
+        let ret = match &self.test {
+            Test::Flagged => stream.flag.is_some(),
+            Test::Any(regex) => [&stream.proc, &stream.remote_host, &stream.local_host, &stream.corp, &stream.cc]
+                .iter().any(|txt| regex.is_match(txt))
+                || [&stream.cloud, &stream.tag].iter().any(|txt| txt.as_ref().is_some_and(|txt| regex.is_match(txt))),
+            Test::Text(field, regex) => field.texts(stream).iter().any(|txt| regex.is_match(txt)),
+            Test::Num(field, op, value) => field.nums(stream).iter().any(|num| match op {
+                Op::Eq | Op::Ne => num == value,
+                Op::Gt => num > value,
+                Op::Lt => num < value
+            })
+        };
+        ret != self.negate
+    }
+}
+
+impl Field {
+    fn parse(txt:&str) -> Result<Field, String> {
+        Ok(match txt.to_lowercase().as_str() {
+            "proc" => Field::Proc,
+            "pid" => Field::Pid,
+            "uid" => Field::Uid,
+            "user" => Field::User,
+            "host" => Field::Host,
+            "local" => Field::Local,
+            "addr" => Field::Addr,
+            "port" => Field::Port,
+            "lport" => Field::LPort,
+            "rport" => Field::RPort,
+            "svc" => Field::Svc,
+            "sni" => Field::Sni,
+            "cc" => Field::Cc,
+            "city" => Field::City,
+            "corp" => Field::Corp,
+            "asn" => Field::Asn,
+            "tag" => Field::Tag,
+            "cloud" => Field::Cloud,
+            "list" => Field::List,
+            "proto" => Field::Proto,
+            "bytes" => Field::Bytes,
+            _ => return Err(format!("unknown field [{}]", txt))
+        })
+    }
+
+    fn numeric(&self) -> bool {
+        matches!(self, Field::Pid | Field::Uid | Field::Port | Field::LPort | Field::RPort | Field::Asn | Field::Bytes)
+    }
+
+    fn texts(&self, s:&PacStream) -> Vec<String> {
+        match self {
+            Field::Proc => vec![s.proc.to_string()],
+            Field::User => s.user.iter().cloned().collect(),
+            Field::Host => vec![s.remote_host.to_string()],
+            Field::Local => vec![s.local_host.to_string(), s.local_addr.to_string()],
+            Field::Addr => vec![s.remote_addr.to_string()],
+            Field::Svc => vec![s.remote_service.to_string(), s.local_service.to_string()],
+            Field::Sni => s.sni.iter().cloned().collect(),
+            Field::Cc => vec![s.cc.to_string()],
+            Field::City => s.city.iter().cloned().collect(),
+            Field::Corp => vec![s.corp.to_string()],
+            Field::Tag => s.tag.iter().cloned().collect(),
+            Field::Cloud => s.cloud.iter().cloned().collect(),
+            Field::List => s.flag.iter().cloned().collect(),
+            Field::Proto => vec![etc::str(s.ip_number)],
+            _ => vec![]
+        }
+    }
+
+    fn nums(&self, s:&PacStream) -> Vec<u64> {
+        match self {
+            Field::Pid => s.pid.iter().map(|&p| p as u64).collect(),
+            Field::Uid => s.uid.iter().map(|&u| u as u64).collect(),
+            Field::Port => vec![s.local_port as u64, s.remote_port as u64],
+            Field::LPort => vec![s.local_port as u64],
+            Field::RPort => vec![s.remote_port as u64],
+            Field::Asn => s.asn.iter().map(|&a| a as u64).collect(),
+            Field::Bytes => vec![s.bytes()],
+            _ => vec![]
+        }
+    }
+}
+
+// case insensitive, anchored. * and ? are the only special characters //
+fn glob(txt:&str) -> Result<Regex, String> {
+    let pat = regex::escape(txt).replace("\\*", ".*").replace("\\?", ".");
+    Regex::new(&format!("(?i)^{}$", pat)).map_err(|e| e.to_string())
+}
+
+// 1500, 10k, 2m, 1g //
+fn parse_num(txt:&str) -> Result<u64, String> {
+    let lower = txt.to_lowercase();
+    let (digits, scale) = match lower.chars().last() {
+        Some('k') => (&lower[..lower.len() - 1], 1_000),
+        Some('m') => (&lower[..lower.len() - 1], 1_000_000),
+        Some('g') => (&lower[..lower.len() - 1], 1_000_000_000),
+        _ => (lower.as_str(), 1)
+    };
+    match digits.parse::<u64>().ok().and_then(|n| n.checked_mul(scale)) {
+        Some(n) => Ok(n),
+        None => Err(format!("not a number [{}]", txt))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use etherparse::IpNumber;
+
+    use crate::filter::{Filter, parse_num};
+    use crate::pacdat::{Dir, PacDat};
+    use crate::pacstream::PacStream;
+    use crate::subnets::addr;
+
+    fn stream(proc:&str, remote_host:&str, remote_port:u16, cc:&str) -> PacStream {
+        let pac_dat = PacDat {
+            ts: Utc::now(), len: Some(100), packets: 1, ip_number: Some(IpNumber::TCP),
+            src_addr: Some(addr("192.168.1.2")), dst_addr: Some(addr("140.82.112.3")),
+            src_port: Some(50000), dst_port: Some(remote_port),
+            dir: Some(Dir::Out), foreign: Some(false), local_traffic: Some(false), sni: None
+        };
+        let mut stream = PacStream::new(&pac_dat);
+        stream.tally(&pac_dat);
+        stream.proc = proc.to_string();
+        stream.pid = Some(1234);
+        stream.remote_host = remote_host.to_string();
+        stream.cc = cc.to_string();
+        stream.corp = "GITHUB".to_string();
+        stream.asn = Some(36459);
+        stream.sni = Some("github.com".to_string());
+        stream
+    }
+
+    fn matches(txt:&str, stream:&PacStream) -> bool {
+        Filter::parse(txt).unwrap().matches(stream)
+    }
+
+    #[test]
+    fn test_fields() {
+        let s = stream("firefox", "lb-140-82-112-3-iad.github.com", 443, "US");
+        assert!(matches("<firefox>", &s));
+        assert!(!matches("<fire>", &s));
+        assert!(matches("proc:FIRE*", &s));
+        assert!(matches("cc:us", &s));
+        assert!(!matches("cc:CN", &s));
+        assert!(matches("host:*.github.com", &s));
+        assert!(matches("port:443", &s));
+        assert!(matches("port:50000", &s));
+        assert!(!matches("rport:50000", &s));
+        assert!(matches("lport>1024", &s));
+        assert!(matches("pid=1234", &s));
+        assert!(matches("proto:tcp", &s));
+        assert!(matches("addr:140.82.*", &s));
+        assert!(matches("bytes<1k", &s));
+        assert!(matches("asn:36459", &s));
+        assert!(matches("sni:*github*", &s));
+        assert!(!matches("city:*", &s));
+        assert!(!matches("flagged", &s));
+        assert!(matches("!flagged", &s));
+    }
+
+    #[test]
+    fn test_combinations() {
+        let s = stream("firefox", "github.com", 443, "US");
+        assert!(matches("github", &s));
+        assert!(matches("<firefox> cc:US", &s));
+        assert!(!matches("<firefox> cc:CN", &s));
+        assert!(matches("cc:CN or cc:US", &s));
+        assert!(matches("cc:CN | <firefox> port:443", &s));
+        assert!(!matches("-proc:firefox", &s));
+        assert!(matches("cc!=CN", &s));
+        assert!(!matches("!cc!=CN", &s));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(Filter::parse("nosuch:field").is_err());
+        assert!(Filter::parse("port:abc").is_err());
+        assert!(Filter::parse("cc>CN").is_err());
+        assert!(Filter::parse("cc:").is_err());
+        assert!(Filter::parse("cc:CN or").is_err());
+        assert_eq!("cc:CN", Filter::parse(" cc:CN ").unwrap().to_string());
+    }
+
+    #[test]
+    fn test_parse_num() {
+        assert_eq!(Ok(1500), parse_num("1500"));
+        assert_eq!(Ok(10_000), parse_num("10k"));
+        assert_eq!(Ok(2_000_000), parse_num("2M"));
+        assert!(parse_num("k").is_err());
+        assert!(parse_num("99999999999999g").is_err());
+    }
+}
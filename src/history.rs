@@ -0,0 +1,218 @@
+use std::cmp::min;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use crate::pacstream::PacStream;
+
+// how far back the graph can go //
+pub const MAX_MINUTES: i64 = 60;
+
+// what each redraw interval would otherwise throw away in reset_stats:
+// the totals plus a breakdown by process and by corp for the graph //
+pub struct History {
+    samples: VecDeque<Sample>     // newest first
+}
+
+struct Sample {
+    ts: i64,                      // millis at the end of the interval
+    interval: u64,
+    recv: u64,                    // bytes
+    sent: u64,
+    by_proc: HashMap<String, (u64, u64)>,
+    by_corp: HashMap<String, (u64, u64)>
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Group {
+    Proc,
+    Corp
+}
+
+// the window split into columns, each series a rate (bytes/s) per column.
+// the top k series by volume then "other" for the rest //
+pub struct Graph {
+    pub names: Vec<String>,
+    pub recv: Vec<Vec<u64>>,
+    pub sent: Vec<Vec<u64>>
+}
+
+pub const OTHER: &str = "other";
+
+impl History {
+    pub fn new() -> Self {
+        History { samples: VecDeque::new() }
+    }
+
+    pub fn record<K>(&mut self, ts: i64, interval: u64, by_stream: &BTreeMap<K, PacStream>, by_corp: &BTreeMap<String, PacStream>) {
+        if interval == 0 {
+            return;
+        }
+
+        let mut sample = Sample { ts, interval, recv: 0, sent: 0, by_proc: HashMap::new(), by_corp: HashMap::new() };
+
+        for stream in by_stream.values().filter(|s| s.bytes_last() > 0) {
+            sample.recv = sample.recv.saturating_add(stream.bytes_recv_last);
+            sample.sent = sample.sent.saturating_add(stream.bytes_sent_last);
+            // traffic we only see in passing has no process //
+            let name = match stream.foreign {
+                true => &stream.local_host,
+                false => &stream.proc
+            };
+            let entry = sample.by_proc.entry(name.to_string()).or_insert((0, 0));
+            entry.0 = entry.0.saturating_add(stream.bytes_recv_last);
+            entry.1 = entry.1.saturating_add(stream.bytes_sent_last);
+        }
+
+        for (corp, stream) in by_corp.iter().filter(|(_, s)| s.bytes_last() > 0) {
+            sample.by_corp.insert(corp.to_string(), (stream.bytes_recv_last, stream.bytes_sent_last));
+        }
+
+        self.add(sample);
+    }
+
+    fn add(&mut self, sample: Sample) {
+        let cutoff = sample.ts - MAX_MINUTES * 60_000;
+        self.samples.push_front(sample);
+        while self.samples.back().is_some_and(|s| s.ts < cutoff) {
+            self.samples.pop_back();
+        }
+    }
+
+    // (recv, sent) bytes/s for the last n intervals, newest first //
+    pub fn rates(&self, n: usize) -> VecDeque<(u64, u64)> {
+        self.samples.iter()
+            .take(n)
+            .map(|s| (s.recv.saturating_mul(1000) / s.interval, s.sent.saturating_mul(1000) / s.interval))
+            .collect()
+    }
+
+    pub fn graph(&self, group: Group, k: usize, now: i64, window: i64, cols: usize) -> Graph {
+        let start = now - window;
+        let samples: Vec<&Sample> = self.samples.iter().take_while(|s| s.ts > start).collect();
+
+        // the biggest k over the window get their own series //
+        let mut volumes: HashMap<&str, u64> = HashMap::new();
+        for sample in &samples {
+            for (name, (recv, sent)) in sample.groups(group) {
+                let volume = volumes.entry(name).or_insert(0);
+                *volume = volume.saturating_add(recv.saturating_add(sent));
+            }
+        }
+        let mut volumes: Vec<(&str, u64)> = volumes.into_iter().collect();
+        volumes.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+
+        let mut names: Vec<String> = volumes.iter().take(k).map(|(name, _)| name.to_string()).collect();
+        let index: HashMap<String, usize> = names.iter().enumerate().map(|(i, name)| (name.to_string(), i)).collect();
+        names.push(OTHER.to_string());
+
+        let mut graph = Graph {
+            recv: vec![vec![0; cols]; names.len()],
+            sent: vec![vec![0; cols]; names.len()],
+            names
+        };
+
+        if cols == 0 || window <= 0 {
+            return graph;
+        }
+
+        // spread each sample's rate over the columns its interval overlaps,
+        // weighted by the overlap so columns come out as average rates //
+        let col_millis = window as f64 / cols as f64;
+        for sample in &samples {
+            let from = ((sample.ts - sample.interval as i64 - start) as f64).max(0.0);
+            let to = (sample.ts - start) as f64;
+            let first = (from / col_millis) as usize;
+            let last = min((to / col_millis).ceil() as usize, cols);
+
+            for col in first..last {
+                let col_start = col as f64 * col_millis;
+                let overlap = (to.min(col_start + col_millis) - from.max(col_start)).max(0.0);
+                let weight = overlap / col_millis * 1000.0 / sample.interval as f64;
+
+                for (name, (recv, sent)) in sample.groups(group) {
+                    let series = *index.get(name).unwrap_or(&(graph.names.len() - 1));
+                    graph.recv[series][col] = graph.recv[series][col].saturating_add((recv as f64 * weight) as u64);
+                    graph.sent[series][col] = graph.sent[series][col].saturating_add((sent as f64 * weight) as u64);
+                }
+            }
+        }
+
+        graph
+    }
+}
+
+impl Sample {
+    fn groups(&self, group: Group) -> impl Iterator<Item=(&str, (u64, u64))> {
+        let map = match group {
+            Group::Proc => &self.by_proc,
+            Group::Corp => &self.by_corp
+        };
+        map.iter().map(|(name, bytes)| (name.as_str(), *bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::history::{Group, History, MAX_MINUTES, OTHER, Sample};
+
+    fn sample(ts: i64, interval: u64, procs: &[(&str, u64, u64)]) -> Sample {
+        Sample {
+            ts,
+            interval,
+            recv: procs.iter().map(|p| p.1).sum(),
+            sent: procs.iter().map(|p| p.2).sum(),
+            by_proc: procs.iter().map(|(name, recv, sent)| (name.to_string(), (*recv, *sent))).collect(),
+            by_corp: HashMap::new()
+        }
+    }
+
+    #[test]
+    fn test_rates() {
+        let mut history = History::new();
+        history.add(sample(1000, 1000, &[("a", 100, 10)]));
+        history.add(sample(1500, 500, &[("a", 100, 10)]));
+        assert_eq!(vec![(200, 20), (100, 10)], Vec::from(history.rates(5)));
+        assert_eq!(vec![(200, 20)], Vec::from(history.rates(1)));
+    }
+
+    #[test]
+    fn test_expiry() {
+        let mut history = History::new();
+        history.add(sample(0, 1000, &[]));
+        history.add(sample(1000, 1000, &[]));
+        history.add(sample(MAX_MINUTES * 60_000 + 500, 1000, &[]));
+        assert_eq!(2, history.rates(10).len());
+    }
+
+    #[test]
+    fn test_graph() {
+        let mut history = History::new();
+        // 4 one second intervals over a 4 column, 4 second window //
+        history.add(sample(7000, 1000, &[("big", 1000, 0), ("small", 10, 0)]));
+        history.add(sample(8000, 1000, &[("big", 2000, 0), ("tiny", 1, 0)]));
+        history.add(sample(9000, 1000, &[("small", 10, 100)]));
+        history.add(sample(10000, 1000, &[]));
+
+        let graph = history.graph(Group::Proc, 2, 10000, 4000, 4);
+        assert_eq!(vec!["big", "small", OTHER], graph.names);
+        assert_eq!(vec![1000, 2000, 0, 0], graph.recv[0]);
+        assert_eq!(vec![10, 0, 10, 0], graph.recv[1]);
+        assert_eq!(vec![0, 1, 0, 0], graph.recv[2]);
+        assert_eq!(vec![0, 0, 100, 0], graph.sent[1]);
+
+        // two columns per interval //
+        let graph = history.graph(Group::Proc, 1, 10000, 4000, 8);
+        assert_eq!(vec![1000, 1000, 2000, 2000, 0, 0, 0, 0], graph.recv[0]);
+        assert_eq!(vec![10, 10, 1, 1, 10, 10, 0, 0], graph.recv[1]);
+
+        // two intervals per column //
+        let graph = history.graph(Group::Proc, 1, 10000, 4000, 2);
+        assert_eq!(vec![1500, 0], graph.recv[0]);
+
+        // nothing for corps //
+        let graph = history.graph(Group::Corp, 5, 10000, 4000, 2);
+        assert_eq!(vec![OTHER], graph.names);
+        assert_eq!(vec![0, 0], graph.recv[0]);
+    }
+}
@@ -3,92 +3,74 @@ mod locations1;
 mod locations2;
 mod locations3;
 mod locations4;
+pub mod trie;
 
-use std::collections::BTreeMap;
 use std::net::IpAddr;
 use crate::etc::log;
 use std::time::Instant;
-use crate::subnets::{addr_to_int};
+use trie::IpTrie;
 
 pub struct IpData {
-    corps: BTreeMap<u128, Corp>,
-    locations: BTreeMap<u128, Location>,
-}
-
-pub struct Corp {
-    pub bit_mask: u128,
-    pub name: String
+    corps: IpTrie<&'static str>,
+    locations: IpTrie<Location>,
 }
 
 pub struct Location {
-    pub bit_mask: u128,
-    pub city: String,
-    pub country: String
+    pub city: &'static str,
+    pub country: &'static str
 }
 
 impl IpData {
     pub fn new() -> Self {
-        let mut corps: BTreeMap<u128, Corp> = BTreeMap::new();
+        let mut corps: IpTrie<&'static str> = IpTrie::new();
         {
             let start = Instant::now();
             let ccc = corps::load();
             for cc in ccc {
-                corps.insert(cc.0, Corp {
-                  bit_mask: bit_mask(cc.1, mask_width(cc.0)), 
-                  name: cc.2.to_string() 
-                });
+                corps.insert(cc.0, cc.1, mask_width(cc.0), cc.2);
             }
-            log(format!("ipdata::insert::corps took {:?}", start.elapsed()));
+            corps.shrink_to_fit();
+            log(format!("ipdata::insert::corps[{}] took {:?} size:{}k", corps.len(), start.elapsed(), corps.mem_size() / 1024));
         }
 
-        let mut locations: BTreeMap<u128, Location> = BTreeMap::new();
+        let mut locations: IpTrie<Location> = IpTrie::new();
         {
           let start = Instant::now();
           for loader in [locations1::load, locations2::load, locations3::load, locations4::load]
           {
               for cc in loader() {
-                  locations.insert(cc.0, Location {
-                      bit_mask: bit_mask(cc.1, mask_width(cc.0)),
-                      country:cc.2.to_string(),
-                      city:cc.3.to_string()
+                  locations.insert(cc.0, cc.1, mask_width(cc.0), Location {
+                      country:cc.2,
+                      city:cc.3
                   });
               }
           }
-          log(format!("ipdata::insert::locations took {:?}", start.elapsed()));
+          locations.shrink_to_fit();
+          log(format!("ipdata::insert::locations[{}] took {:?} size:{}k", locations.len(), start.elapsed(), locations.mem_size() / 1024));
         }
 
         IpData { corps, locations }
     }
 
     pub fn company(&self, addr:&IpAddr) -> Option<String> {
-        let ip_int = addr_to_int(addr);
-        if let Some((&subnet, &ref corp)) = self.corps.range(..=ip_int).next_back() {
-            if same_subnet(ip_int, subnet, corp.bit_mask) {
-              return Some(corp.name.to_string());
-            } 
+        self.corps.lookup(addr).map(|corp| corp.to_string())
+    }
+
+    pub fn city(&self, addr:&IpAddr) -> Option<String> {
+        match self.locations.lookup(addr) {
+            Some(location) if !location.city.is_empty() => Some(location.city.to_string()),
+            _ => None
         }
-        None
     }
 
     pub fn cc(&self, addr:&IpAddr) -> String {
-        let ip_int = addr_to_int(addr);
-        if let Some((&subnet, &ref location)) = self.locations.range(..=ip_int).next_back() {
-            if same_subnet(ip_int, subnet, location.bit_mask) {
-              return location.country.to_string();
-            }
+        match self.locations.lookup(addr) {
+            Some(location) => location.country.to_string(),
+            None => "?".to_string()
         }
-        return "?".to_string(); 
     }
 }
 
-
-fn bit_mask(bits:u32, width:u32) -> u128 {
-  if bits > width {
-    panic!("this should never happen");
-  }
-  ((1u128 << bits) - 1).wrapping_shl(width-bits)
-}
-
 fn mask_width(addr_int:u128) -> u32 {
   match addr_int <= 0xFFFFFFFF {
     true => 32,
@@ -96,15 +78,12 @@ fn mask_width(addr_int:u128) -> u32 {
   }
 }
 
-fn same_subnet(addr_int:u128, subnet_int:u128, mask:u128) -> bool {
-  addr_int & mask == subnet_int & mask
-}
-
 #[cfg(test)]
 mod tests {
 
     use crate::ipdata::*;
-    use crate::subnets::addr;
+    use crate::ipdata::trie::bit_mask;
+    use crate::subnets::{addr, addr_to_int};
 
     #[test]
     fn test_company() {
@@ -0,0 +1,424 @@
+use std::mem::size_of;
+use std::net::IpAddr;
+use std::net::IpAddr::{V4, V6};
+
+use crate::subnets::{addr_to_int, parse_subnet_to_int};
+
+const NONE: u32 = u32::MAX;
+
+// longest-prefix-match over subnets, one trie per address family //
+pub struct IpTrie<T> {
+    v4: PrefixTrie<u32, T>,
+    v6: PrefixTrie<u128, T>
+}
+
+impl<T> IpTrie<T> {
+    pub fn new() -> Self {
+        IpTrie {
+            v4: PrefixTrie::new(0),
+            v6: PrefixTrie::new(0)
+        }
+    }
+
+    // subnet is right aligned as per addr_to_int, width is 32 or 128 //
+    pub fn insert(&mut self, subnet:u128, bits:u32, width:u32, value:T) {
+        match width {
+            32 => self.v4.insert(subnet as u32, bits as u8, value),
+            128 => self.v6.insert(subnet, bits as u8, value),
+            _ => panic!("bad width:{}", width)
+        }
+    }
+
+    // eg "10.1.0.0/16" or "2001:db8::/32". a bare address is taken as a host route //
+    pub fn insert_subnet(&mut self, txt:&str, value:T) -> Result<(), String> {
+        let width = if txt.contains(':') { 128 } else { 32 };
+        let (subnet, bits) = match txt.split_once('/') {
+            Some((_, bits)) => match bits.parse::<u32>() {
+                Ok(bits) if bits <= width => (parse_subnet_to_int(txt)?, bits),
+                _ => return Err(format!("Failed to parse [{}]", txt))
+            },
+            None => (parse_subnet_to_int(&format!("{}/{}", txt, width))?, width)
+        };
+        self.insert(subnet, bits, width, value);
+        Ok(())
+    }
+
+    pub fn lookup(&self, addr:&IpAddr) -> Option<&T> {
+        match addr {
+            V4(_) => self.v4.lookup(addr_to_int(addr) as u32),
+            V6(_) => self.v6.lookup(addr_to_int(addr))
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.v4.values.len() + self.v6.values.len()
+    }
+
+    // call once loaded - the vecs tend to be half empty otherwise //
+    pub fn shrink_to_fit(&mut self) {
+        self.v4.shrink_to_fit();
+        self.v6.shrink_to_fit();
+    }
+
+    pub fn mem_size(&self) -> usize {
+        self.v4.mem_size() + self.v6.mem_size()
+    }
+}
+
+// the v4 trie gets u32 keys so its nodes are a fraction of the size //
+trait Key: Copy + Eq {
+    const BITS: u8;
+    fn mask(self, len:u8) -> Self;
+    fn bit_at(self, pos:u8) -> usize;
+    fn common_len(self, other:Self) -> u8;
+}
+
+impl Key for u32 {
+    const BITS: u8 = 32;
+
+    fn mask(self, len:u8) -> Self {
+        self & bit_mask(len as u32, 32) as u32
+    }
+
+    fn bit_at(self, pos:u8) -> usize {
+        ((self >> (31 - pos as u32)) & 1) as usize
+    }
+
+    fn common_len(self, other:Self) -> u8 {
+        (self ^ other).leading_zeros() as u8
+    }
+}
+
+impl Key for u128 {
+    const BITS: u8 = 128;
+
+    fn mask(self, len:u8) -> Self {
+        self & bit_mask(len as u32, 128)
+    }
+
+    fn bit_at(self, pos:u8) -> usize {
+        ((self >> (127 - pos as u32)) & 1) as usize
+    }
+
+    fn common_len(self, other:Self) -> u8 {
+        (self ^ other).leading_zeros() as u8
+    }
+}
+
+// path compressed binary trie. nodes refer to each other by index
+// which keeps them small and avoids a heap allocation per prefix.
+struct PrefixTrie<K, T> {
+    nodes: Vec<Node<K>>,
+    values: Vec<T>
+}
+
+struct Node<K> {
+    key: K,
+    len: u8,
+    value: u32,
+    child: [u32; 2]
+}
+
+impl<K:Key> Node<K> {
+    fn new(key:K, len:u8, value:u32) -> Self {
+        Node { key: key.mask(len), len, value, child: [NONE, NONE] }
+    }
+
+    fn matches(&self, key:K) -> bool {
+        key.mask(self.len) == self.key
+    }
+}
+
+impl<K:Key, T> PrefixTrie<K, T> {
+    fn new(zero:K) -> Self {
+        PrefixTrie {
+            nodes: vec![Node::new(zero, 0, NONE)],
+            values: Vec::new()
+        }
+    }
+
+    fn insert(&mut self, key:K, len:u8, value:T) {
+        if len > K::BITS {
+            panic!("prefix too long:{}", len);
+        }
+
+        let key = key.mask(len);
+        let mut idx = 0;
+
+        loop {
+            // invariant: nodes[idx] is a prefix of key/len //
+            if self.nodes[idx].len == len {
+                self.set_value(idx, value);
+                return;
+            }
+
+            let bit = key.bit_at(self.nodes[idx].len);
+            let child_idx = self.nodes[idx].child[bit];
+
+            if child_idx == NONE {
+                let leaf = self.push_node(key, len, Some(value));
+                self.nodes[idx].child[bit] = leaf;
+                return;
+            }
+
+            let child = &self.nodes[child_idx as usize];
+            let common = child.key.common_len(key).min(child.len).min(len);
+
+            if common == child.len {
+                idx = child_idx as usize;
+                continue;
+            }
+
+            // the child diverges from us part way along its prefix - split it //
+            let child_bit = child.key.bit_at(common);
+            let mid = self.push_node(key, common, None);
+            self.nodes[mid as usize].child[child_bit] = child_idx;
+            self.nodes[idx].child[bit] = mid;
+
+            if common == len {
+                self.set_value(mid as usize, value);
+            } else {
+                let leaf = self.push_node(key, len, Some(value));
+                self.nodes[mid as usize].child[key.bit_at(common)] = leaf;
+            }
+            return;
+        }
+    }
+
+    fn lookup(&self, key:K) -> Option<&T> {
+        let mut best = NONE;
+        let mut idx = 0;
+
+        loop {
+            let node = &self.nodes[idx];
+            if !node.matches(key) {
+                break;
+            }
+            if node.value != NONE {
+                best = node.value;
+            }
+            if node.len == K::BITS {
+                break;
+            }
+            match node.child[key.bit_at(node.len)] {
+                NONE => break,
+                child => idx = child as usize
+            }
+        }
+
+        match best {
+            NONE => None,
+            i => self.values.get(i as usize)
+        }
+    }
+
+    fn set_value(&mut self, idx:usize, value:T) {
+        match self.nodes[idx].value {
+            NONE => {
+                self.nodes[idx].value = self.values.len() as u32;
+                self.values.push(value);
+            }
+            // last one wins, same as the maps we replaced //
+            i => self.values[i as usize] = value
+        }
+    }
+
+    fn push_node(&mut self, key:K, len:u8, value:Option<T>) -> u32 {
+        let value_idx = match value {
+            Some(value) => {
+                self.values.push(value);
+                (self.values.len() - 1) as u32
+            }
+            None => NONE
+        };
+        self.nodes.push(Node::new(key, len, value_idx));
+        (self.nodes.len() - 1) as u32
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.nodes.shrink_to_fit();
+        self.values.shrink_to_fit();
+    }
+
+    fn mem_size(&self) -> usize {
+        self.nodes.capacity() * size_of::<Node<K>>() + self.values.capacity() * size_of::<T>()
+    }
+}
+
+pub fn bit_mask(bits:u32, width:u32) -> u128 {
+    if bits > width {
+        panic!("this should never happen");
+    }
+    if bits == 0 {
+        return 0;
+    }
+    (u128::MAX >> (128 - bits)).wrapping_shl(width - bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::mem::size_of;
+
+    use rand::Rng;
+
+    use crate::ipdata::trie::{bit_mask, IpTrie};
+    use crate::subnets::{addr, parse_subnet_to_int};
+
+    fn insert(trie:&mut IpTrie<&'static str>, subnet:&str, value:&'static str) {
+        let bits = subnet.split('/').nth(1).unwrap().parse::<u32>().unwrap();
+        let width = if subnet.contains(':') { 128 } else { 32 };
+        trie.insert(parse_subnet_to_int(subnet).unwrap(), bits, width, value);
+    }
+
+    fn lookup(trie:&IpTrie<&'static str>, txt:&str) -> Option<&'static str> {
+        trie.lookup(&addr(txt)).copied()
+    }
+
+    #[test]
+    fn test_nested() {
+        let mut trie = IpTrie::new();
+        insert(&mut trie, "10.0.0.0/8", "outer");
+        insert(&mut trie, "10.1.0.0/16", "middle");
+        insert(&mut trie, "10.1.2.0/24", "inner");
+        insert(&mut trie, "10.1.200.0/24", "other-inner");
+
+        assert_eq!(Some("outer"), lookup(&trie, "10.0.0.1"));
+        assert_eq!(Some("middle"), lookup(&trie, "10.1.0.1"));
+        assert_eq!(Some("inner"), lookup(&trie, "10.1.2.255"));
+        assert_eq!(Some("middle"), lookup(&trie, "10.1.3.0"));
+        // the case the old range(..=ip).next_back() lookup got wrong //
+        assert_eq!(Some("middle"), lookup(&trie, "10.1.255.1"));
+        assert_eq!(Some("outer"), lookup(&trie, "10.255.0.0"));
+        assert_eq!(None, lookup(&trie, "11.0.0.0"));
+        assert_eq!(None, lookup(&trie, "9.255.255.255"));
+    }
+
+    #[test]
+    fn test_insert_order() {
+        // inner first means the outer prefix has to split an existing edge //
+        let mut trie = IpTrie::new();
+        insert(&mut trie, "192.168.1.128/25", "inner");
+        insert(&mut trie, "192.168.1.0/24", "outer");
+        insert(&mut trie, "192.168.0.0/16", "outermost");
+
+        assert_eq!(Some("inner"), lookup(&trie, "192.168.1.200"));
+        assert_eq!(Some("outer"), lookup(&trie, "192.168.1.1"));
+        assert_eq!(Some("outermost"), lookup(&trie, "192.168.7.1"));
+        assert_eq!(3, trie.len());
+    }
+
+    #[test]
+    fn test_overlapping() {
+        let mut trie = IpTrie::new();
+        insert(&mut trie, "8.8.8.0/24", "first");
+        insert(&mut trie, "8.8.8.0/24", "second");
+        insert(&mut trie, "8.8.4.0/22", "wider");
+        insert(&mut trie, "8.8.8.8/32", "host");
+
+        assert_eq!(Some("second"), lookup(&trie, "8.8.8.1"));
+        assert_eq!(Some("host"), lookup(&trie, "8.8.8.8"));
+        assert_eq!(Some("wider"), lookup(&trie, "8.8.5.1"));
+        assert_eq!(Some("wider"), lookup(&trie, "8.8.7.255"));
+        assert_eq!(None, lookup(&trie, "8.8.3.255"));
+        assert_eq!(3, trie.len());
+    }
+
+    #[test]
+    fn test_default_route() {
+        let mut trie = IpTrie::new();
+        insert(&mut trie, "0.0.0.0/0", "default");
+        insert(&mut trie, "1.0.0.0/24", "one");
+        assert_eq!(Some("default"), lookup(&trie, "200.1.1.1"));
+        assert_eq!(Some("one"), lookup(&trie, "1.0.0.9"));
+        assert_eq!(None, lookup(&trie, "::1"));
+    }
+
+    #[test]
+    fn test_v6() {
+        let mut trie = IpTrie::new();
+        insert(&mut trie, "2001:db8::/32", "doc");
+        insert(&mut trie, "2001:db8:85a3::/48", "nested");
+        insert(&mut trie, "2001:db8:85a3::7334/128", "host");
+        insert(&mut trie, "0.0.0.0/8", "v4");
+
+        assert_eq!(Some("doc"), lookup(&trie, "2001:db8:1::1"));
+        assert_eq!(Some("nested"), lookup(&trie, "2001:db8:85a3::1"));
+        assert_eq!(Some("host"), lookup(&trie, "2001:db8:85a3::7334"));
+        assert_eq!(None, lookup(&trie, "2001:db9::1"));
+        // families don't bleed into each other //
+        assert_eq!(None, lookup(&trie, "::1"));
+        assert_eq!(Some("v4"), lookup(&trie, "0.0.0.1"));
+    }
+
+    #[test]
+    fn test_insert_subnet() {
+        let mut trie = IpTrie::new();
+        trie.insert_subnet("10.0.0.0/8", "net").unwrap();
+        trie.insert_subnet("10.0.0.7", "host").unwrap();
+        trie.insert_subnet("fd00::/8", "ula").unwrap();
+        assert_eq!(Some("net"), lookup(&trie, "10.0.0.6"));
+        assert_eq!(Some("host"), lookup(&trie, "10.0.0.7"));
+        assert_eq!(Some("ula"), lookup(&trie, "fd12::1"));
+        assert_eq!(None, lookup(&trie, "2001:db8::1"));
+        trie.insert_subnet("::/0", "default").unwrap();
+        assert_eq!(Some("default"), lookup(&trie, "2001:db8::1"));
+        assert!(trie.insert_subnet("10.0.0.0/33", "bad").is_err());
+        assert!(trie.insert_subnet("10.0.0/8", "bad").is_err());
+        assert!(trie.insert_subnet("fd00::/x", "bad").is_err());
+    }
+
+    #[test]
+    fn test_against_linear_scan() {
+        let mut rng = rand::thread_rng();
+        let mut trie = IpTrie::new();
+        let mut subnets = Vec::new();
+
+        for i in 0..2000u32 {
+            let bits = rng.gen_range(8..=32);
+            let subnet = (rng.gen::<u32>() & 0x0FFFFFFF) as u128 & bit_mask(bits, 32);
+            trie.insert(subnet, bits, 32, i);
+            subnets.retain(|&(s, b, _)| s != subnet || b != bits);
+            subnets.push((subnet, bits, i));
+        }
+
+        for _ in 0..20000 {
+            let ip = (rng.gen::<u32>() & 0x0FFFFFFF) as u128;
+            let expected = subnets.iter()
+                .filter(|(s, b, _)| ip & bit_mask(*b, 32) == *s)
+                .max_by_key(|(_, b, _)| *b)
+                .map(|(_, _, v)| v);
+            let a = std::net::Ipv4Addr::from(ip as u32);
+            assert_eq!(expected, trie.lookup(&std::net::IpAddr::V4(a)), "{}", a);
+        }
+    }
+
+    #[test]
+    #[ignore]
+    fn measure_memory() {
+        // rough comparison with the BTreeMap<u128, Corp> we used to have //
+        struct Corp { _bit_mask: u128, _name: String }
+        let n = 500_000;
+        let mut rng = rand::thread_rng();
+        let names:Vec<&'static str> = (0..20_000).map(|i| &*Box::leak(format!("SOME CORP NUMBER {}", i).into_boxed_str())).collect();
+
+        let mut trie = IpTrie::new();
+        let mut map = BTreeMap::new();
+        let mut heap = 0;
+        for _ in 0..n {
+            let bits = rng.gen_range(12..=24);
+            let subnet = rng.gen::<u32>() as u128 & bit_mask(bits, 32);
+            let name = names[rng.gen_range(0..names.len())];
+            trie.insert(subnet, bits, 32, name);
+            // keyed on the mask too so both hold the same prefixes //
+            if map.insert((subnet, bits), Corp { _bit_mask: bit_mask(bits, 32), _name: name.to_string() }).is_none() {
+                heap += name.len();
+            }
+        }
+        trie.shrink_to_fit();
+
+        // ignores btree node slack which only makes the map look better //
+        let map_size = map.len() * (size_of::<u128>() + size_of::<Corp>()) + heap;
+        println!("prefixes:{} trie:{}k map:>{}k", map.len(), trie.mem_size() / 1024, map_size / 1024);
+    }
+}
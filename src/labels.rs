@@ -0,0 +1,116 @@
+use std::fs::read_to_string;
+use std::net::IpAddr;
+use std::path::Path;
+
+use crate::etc::log;
+use crate::ipdata::trie::IpTrie;
+
+// user supplied names for networks ipdata knows nothing (useful) about.
+// one per line:
+//   # cidr, corp, cc, tag (optional)
+//   10.20.0.0/16, ACME-DC1, GB, prod-db
+//   192.0.2.0/24, PARTNERCO, US
+pub struct Labels {
+    labels: IpTrie<Label>
+}
+
+pub struct Label {
+    pub corp: String,
+    pub cc: String,
+    pub tag: Option<String>
+}
+
+impl Labels {
+    pub fn new() -> Self {
+        Labels { labels: IpTrie::new() }
+    }
+
+    pub fn load(path:&Path) -> Result<Self, String> {
+        match read_to_string(path) {
+            Ok(txt) => {
+                let (labels, errs) = Labels::parse(&txt);
+                for err in errs {
+                    eprintln!("{}: {} - dropping line", path.display(), err);
+                }
+                log(format!("labels: loaded {} from {}", labels.labels.len(), path.display()));
+                Ok(labels)
+            }
+            Err(err) => Err(format!("{}: {}", path.display(), err))
+        }
+    }
+
+    fn parse(txt:&str) -> (Self, Vec<String>) {
+        let mut labels = Labels::new();
+        let mut errs = Vec::new();
+
+        for (n, line) in txt.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let parts:Vec<&str> = line.split(',').map(|s| s.trim()).collect();
+            if parts.len() < 3 || parts.len() > 4 || parts[1].is_empty() {
+                errs.push(format!("line {}: expected cidr,corp,cc[,tag] [{}]", n + 1, line));
+                continue;
+            }
+
+            let label = Label {
+                corp: parts[1].to_string(),
+                cc: parts[2].to_string(),
+                tag: parts.get(3).filter(|tag| !tag.is_empty()).map(|tag| tag.to_string())
+            };
+
+            if let Err(msg) = labels.labels.insert_subnet(parts[0], label) {
+                errs.push(format!("line {}: {}", n + 1, msg));
+            }
+        }
+
+        (labels, errs)
+    }
+
+    pub fn lookup(&self, addr:&IpAddr) -> Option<&Label> {
+        self.labels.lookup(addr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::labels::Labels;
+    use crate::subnets::addr;
+
+    #[test]
+    fn test_parse() {
+        let (labels, errs) = Labels::parse("
+            # our stuff
+            10.20.0.0/16, ACME-DC1, GB, prod-db
+            10.20.5.0/24, ACME-DC1, GB, prod-web
+            192.0.2.0/24,PARTNERCO,US
+            2001:db8::/32, ACME-V6, , lab
+        ");
+        assert!(errs.is_empty(), "{:?}", errs);
+
+        let label = labels.lookup(&addr("10.20.1.1")).unwrap();
+        assert_eq!("ACME-DC1", label.corp);
+        assert_eq!("GB", label.cc);
+        assert_eq!(Some("prod-db".to_string()), label.tag);
+
+        assert_eq!(Some("prod-web".to_string()), labels.lookup(&addr("10.20.5.9")).unwrap().tag);
+        assert_eq!(None, labels.lookup(&addr("192.0.2.1")).unwrap().tag);
+        assert_eq!("", labels.lookup(&addr("2001:db8::1")).unwrap().cc);
+        assert!(labels.lookup(&addr("10.21.0.0")).is_none());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let (labels, errs) = Labels::parse("
+            10.0.0.0/8
+            10.0.0.0/8,,GB
+            10.0.0.0/99,X,GB
+            1.2.3.0/24,X,GB,a,b
+            1.2.4.0/24,OK,GB
+        ");
+        assert_eq!(4, errs.len(), "{:?}", errs);
+        assert_eq!("OK", labels.lookup(&addr("1.2.4.4")).unwrap().corp);
+    }
+}
@@ -1,7 +1,12 @@
-use std::collections::HashSet;
-use std::{env, io};
+use std::io;
+use chrono::{Local, TimeZone};
+use clap::Parser;
 use regex::Regex;
 
+use cli::{Cli, Command};
+use config::Config;
+use resolver::Resolver;
+
 mod pacmon;
 mod etc;
 mod ui;
@@ -11,38 +16,107 @@ mod pacdat;
 mod pacstream;
 mod pcap;
 mod ipdata;
+mod labels;
+mod cloud;
+mod blocklist;
+mod filter;
+mod history;
+mod asn;
+mod tls;
+mod config;
+mod cli;
+mod privs;
+mod sinks;
+mod collector;
+mod store;
+mod summary;
 
 fn main() {
-    check_user();
+    let cli = Cli::parse();
+    let config = cli.config();
 
-    let args: HashSet<String> = env::args().collect();
-    if args.contains("-h") {
-        help(); 
-    }
-    else if args.contains("-x") {
-        special_processing()
-    }
-    else {
-        pacmon::run(args);
+    match &cli.command {
+        Some(Command::Convert) => special_processing(),
+        Some(Command::Lookup { addrs }) => lookup(&config, addrs),
+        Some(Command::Query { by, from, to, top }) => query(&config, by, from.as_deref(), to.as_deref(), *top),
+        None => {
+            // collecting needs no more than a udp port //
+            if config.get_str("collect").is_none() {
+                match privs::check() {
+                    Ok(warnings) => warnings.iter().for_each(|msg| eprintln!("{}", msg)),
+                    Err(msg) => {
+                        eprintln!("{}", msg);
+                        std::process::exit(-99);
+                    }
+                }
+            }
+            pacmon::run(config);
+        }
     }
 }
 
-fn check_user() {
-    match env::var("USER") {
-        Ok(username) => if !username.eq("root") {
-            eprintln!("Pacmon does not cater to the underprivileged");
-            std::process::exit(-99);
+// everything the resolver would put on a stream for the far end //
+fn lookup(config: &Config, addrs: &[String]) {
+    let mut resolver = Resolver::new(config);
+    let mut status = 0;
+    for txt in addrs {
+        let addr = match txt.parse() {
+            Ok(addr) => addr,
+            Err(_) => {
+                eprintln!("{}: not an address", txt);
+                status -= 1;
+                continue;
+            }
+        };
+
+        let mut info = vec![txt.to_string(), resolver.resolve_host(addr)];
+        match resolver.classify(&addr) {
+            Some(special) => info.push(special.label().to_string()),
+            None => {
+                info.push(format!("cc:{}", resolver.resolve_cc(&addr)));
+                let fields = [
+                    ("city", resolver.resolve_city(&addr)),
+                    ("corp", resolver.resolve_company(&addr)),
+                    ("asn", resolver.resolve_asn(&addr).map(|asn| format!("AS{}", asn))),
+                    ("tag", resolver.resolve_tag(&addr)),
+                    ("cloud", resolver.resolve_cloud(&addr)),
+                    ("list", resolver.resolve_flag(&addr))
+                ];
+                for (name, value) in fields {
+                    if let Some(value) = value {
+                        info.push(format!("{}:{}", name, value));
+                    }
+                }
+            }
         }
-        Err(_) => {}
+        println!("{}", info.join("  "));
     }
+    std::process::exit(status);
 }
 
-fn help() {
-    println!("options:");
-    println!("   -l     create ./pacmon.log");
-    println!("   -x     invoke addr_to_int on stdin. see code for details");
-    println!("   -h     this");
-    std::process::exit(-98);
+fn query(config: &Config, by: &str, from: Option<&str>, to: Option<&str>, top: usize) {
+    let fail = |msg: String| -> ! {
+        eprintln!("{}", msg);
+        std::process::exit(-1);
+    };
+
+    let path = config.get_path("history").unwrap_or_else(|| fail("no history to query, see --history".to_string()));
+    if !path.exists() {
+        fail(format!("{}: no such file", path.display()));
+    }
+
+    let now = Local::now();
+    let from = store::parse_time(from.unwrap_or("-24h"), now).unwrap_or_else(|msg| fail(msg));
+    let to = store::parse_time(to.unwrap_or("now"), now).unwrap_or_else(|msg| fail(msg));
+    let rows = store::top(&path, by, from, to, top).unwrap_or_else(|msg| fail(msg));
+
+    let fmt = |ts: i64| Local.timestamp_opt(ts, 0).unwrap().format("%Y-%m-%d %H:%M").to_string();
+    println!("{} - {}", fmt(from), fmt(to));
+    let width = rows.iter().map(|(name, _, _)| name.chars().count()).chain([by.len()]).max().unwrap();
+    println!("{:<width$}  {:>6}  {:>6}  {:>6}", by.to_uppercase(), "IN", "OUT", "TOTAL", width = width);
+    for (name, recv, sent) in rows {
+        println!("{:<width$}  {:>6}  {:>6}  {:>6}", name, etc::mag_fmt(recv), etc::mag_fmt(sent), etc::mag_fmt(recv + sent), width = width);
+    }
 }
 
 fn special_processing() {
@@ -13,7 +13,8 @@ pub enum Dir {
 
 pub struct PacDat {
     pub ts: DateTime<Utc>,
-    pub len: Option<u32>,
+    pub len: Option<u64>,
+    pub packets: u64,               // more than one for a flow record
     pub ip_number: Option<IpNumber>,
     pub src_addr: Option<IpAddr>,
     pub dst_addr: Option<IpAddr>,
@@ -21,10 +22,11 @@ pub struct PacDat {
     pub dst_port: Option<u16>,
     pub dir: Option<Dir>,
     pub foreign: Option<bool>,
-    pub local_traffic: Option<bool>
+    pub local_traffic: Option<bool>,
+    pub sni: Option<String>         // from a tls client hello
 }
 
-#[derive(Ord, PartialOrd, Eq, PartialEq)]
+#[derive(Ord, PartialOrd, Eq, PartialEq, Clone, Debug)]
 pub struct StreamKey {
     ip_number: IpNumber,
     addr1: IpAddr,
@@ -33,26 +35,35 @@ pub struct StreamKey {
     port2: u16
 }
 
-impl PacDat {
-    pub fn key(&self) -> StreamKey {
-        if self.src_addr.unwrap().gt(&self.dst_addr.unwrap()) {
+impl StreamKey {
+    // same key whichever way the packet is going //
+    pub fn new(ip_number:IpNumber, src_addr:IpAddr, src_port:u16, dst_addr:IpAddr, dst_port:u16) -> Self {
+        if src_addr.gt(&dst_addr) {
             StreamKey {
-                ip_number: self.ip_number.unwrap(),
-                addr1: self.src_addr.unwrap(),
-                port1: self.src_port.unwrap(),
-                addr2: self.dst_addr.unwrap(),
-                port2: self.dst_port.unwrap()
+                ip_number,
+                addr1: src_addr,
+                port1: src_port,
+                addr2: dst_addr,
+                port2: dst_port
             }
         } else {
             StreamKey {
-                ip_number: self.ip_number.unwrap(),
-                addr1: self.dst_addr.unwrap(),
-                port1: self.dst_port.unwrap(),
-                addr2: self.src_addr.unwrap(),
-                port2: self.src_port.unwrap(),
+                ip_number,
+                addr1: dst_addr,
+                port1: dst_port,
+                addr2: src_addr,
+                port2: src_port,
             }
         }
     }
+}
+
+impl PacDat {
+    pub fn key(&self) -> StreamKey {
+        StreamKey::new(self.ip_number.unwrap(),
+                       self.src_addr.unwrap(), self.src_port.unwrap(),
+                       self.dst_addr.unwrap(), self.dst_port.unwrap())
+    }
 
     pub fn remote_addr(&self) -> IpAddr {
         match self.dir {
@@ -1,5 +1,5 @@
 use std::cmp::max;
-use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::collections::{BTreeMap, BTreeSet};
 use std::io;
 use std::io::Write;
 use std::net::IpAddr;
@@ -9,46 +9,93 @@ use pcap::Device;
 
 use etc::init_logging;
 
+use crate::collector;
+use crate::config::Config;
 use crate::etc;
 use crate::etc::log;
 use crate::pacdat::{PacDat, StreamKey};
+use crate::history::History;
 use crate::pacstream::PacStream;
-use crate::pcap::Pcap;
+use crate::pcap::{Pcap, Source};
+use crate::privs;
 use crate::resolver::Resolver;
+use crate::sinks;
+use crate::sinks::{Sinks, Tick};
+use crate::summary;
+use crate::ui::batch::Batch;
+use crate::ui;
 use crate::ui::UI;
 
 pub struct Streams {
     pub by_stream: BTreeMap<StreamKey, PacStream>,
-    pub by_corp: BTreeMap<String, PacStream>
+    pub by_corp: BTreeMap<String, PacStream>,
+    pub by_cloud: BTreeMap<String, PacStream>,
+    pub history: History
 }
 
 impl Streams {
-    fn new() -> Self {
+    pub fn new() -> Self {
         Streams{
             by_stream: BTreeMap::new(),
-            by_corp: BTreeMap::new()
+            by_corp: BTreeMap::new(),
+            by_cloud: BTreeMap::new(),
+            history: History::new()
         }
     }
+
+    // every grouping rolls over together so switching modes doesn't show a
+    // backlog of bytes or gaps in the history. call before reset_stats //
+    pub fn push_history(&mut self, interval: u64) {
+        if interval == 0 {
+            return;
+        }
+
+        self.history.record(etc::millitime(), interval, &self.by_stream, &self.by_corp);
+
+        self.by_stream.values_mut().for_each(|s| s.push_history(interval));
+        self.by_corp.values_mut().for_each(|s| s.push_history(interval));
+        self.by_cloud.values_mut().for_each(|s| s.push_history(interval));
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.by_stream.values_mut().for_each(|s| s.reset_stats());
+        self.by_corp.values_mut().for_each(|s| s.reset_stats());
+        self.by_cloud.values_mut().for_each(|s| s.reset_stats());
+    }
 }
 
-pub fn run(args: HashSet<String>) {
-    if args.contains("-l") {
+pub fn run(config: Config) {
+    if config.get_bool("log") == Some(true) {
         init_logging();
     }
 
     let mut interfaces = BTreeSet::new();
-    let dev = Device::lookup().unwrap().unwrap();
-    for addr in &dev.addresses {
-        if addr.addr.is_ipv4() {
-            log(format!("snooping {:?} / {:?} (IPv4 only)", addr.addr, addr.netmask.unwrap()));
-            interfaces.insert((addr.addr, addr.netmask.unwrap()));
+    let collect = config.get_str("collect");
+    let dev = match &collect {
+        Some(_) => None,
+        None => Some(device(&config))
+    };
+    match &dev {
+        Some(dev) => for addr in &dev.addresses {
+            if addr.addr.is_ipv4() {
+                log(format!("snooping {:?} / {:?} (IPv4 only)", addr.addr, addr.netmask.unwrap()));
+                interfaces.insert((addr.addr, addr.netmask.unwrap()));
+            }
+        },
+        // which end of a flow is ours //
+        None => match collector::networks(&config) {
+            Ok(networks) => interfaces.extend(networks),
+            Err(msg) => {
+                eprintln!("config: networks: {}", msg);
+                std::process::exit(-97);
+            }
         }
     }
 
-    print!("+ipdata..");
-    io::stdout().flush().unwrap();
-    let mut resolver = Resolver::new();
-    println!("done.\n~pcap..");
+    eprint!("+ipdata..");
+    io::stderr().flush().unwrap();
+    let mut resolver = Resolver::new(&config);
+    eprintln!("done.\n~pcap..");
 
     let mut streams = Streams::new();
     let mut packets = 0u64;
@@ -56,38 +103,96 @@ pub fn run(args: HashSet<String>) {
     let mut q_max = 0u64;
     let mut running = false;
 
-    let mut ui = UI::init();
+    let source = match (dev, &collect) {
+        (Some(dev), _) => Pcap::open(dev).map(Source::Capture),
+        (None, Some(addr)) => collector::bind(addr).map(Source::Collector),
+        (None, None) => unreachable!()
+    };
+    let source = match source {
+        Ok(source) => source,
+        Err(msg) => {
+            eprintln!("{}", msg);
+            std::process::exit(-1);
+        }
+    };
+
+    // opened while we can, for files only root may write to and ports below 1024 //
+    let mut sinks = match sinks::from_config(&config) {
+        Ok(sinks) => sinks,
+        Err(msg) => {
+            eprintln!("{}", msg);
+            std::process::exit(-1);
+        }
+    };
+
+    // nothing needs more than this from here on //
+    let user = config.get_str("user")
+        .or_else(|| std::env::var("SUDO_USER").ok())
+        .unwrap_or("nobody".to_string());
+    if let Err(msg) = privs::drop_to(&user) {
+        eprintln!("couldn't drop privileges: {}", msg);
+        std::process::exit(-1);
+    }
+    if let Err(msg) = sinks.open_history(&config) {
+        eprintln!("{}", msg);
+        std::process::exit(-1);
+    }
+
+    if sinks.summary.is_some() {
+        summary::catch_signals();
+    }
+    let mut sink_q_max = 0u64;
+    let mut sink_dropped = 0u64;
+    let mut last_redraw = Duration::ZERO;
+
+    let mut batch = Batch::new(&config);
+    let mut ui = UI::init(config);
+    if let Some(batch) = &batch {
+        batch.start(&mut ui);
+    }
 
     let pcap = Pcap::new();
-    pcap.start(dev);
+    pcap.start(source);
 
     loop {
         match pcap.rx().recv_timeout(Duration::from_millis(10)) {
             Ok(mut pac_dat) => {
                 // only start curses once we get a packet
-                if !running {
+                if !running && batch.is_none() {
                     ui.show();
                     running = true;
                 }
 
                 tally(&mut pac_dat, &mut streams, &mut resolver, &interfaces);
                 packets += 1 ;
-                q_max = max(q_max, pcap.decrement_and_get_q_depth());
+                let q_depth = pcap.decrement_and_get_q_depth();
+                q_max = max(q_max, q_depth);
+                sink_q_max = max(sink_q_max, q_depth);
             }
             Err(_recv_timeout_non_error) => {
             }
         }
 
-        ui.check_key();
+        let redraw = match &batch {
+            Some(batch) => batch.due(&ui),
+            None => {
+                ui.check_key();
+                ui.should_redraw()
+            }
+        };
 
-        if ui.should_redraw() {
+        if redraw && !ui.quitting {
             let start = Instant::now();
             let dropped = pcap.packets_dropped();
             let dropped_curr = dropped - last_dropped;
 
-            ui.draw(&mut streams, q_max, dropped_curr);
+            match &mut batch {
+                Some(batch) => batch.print(&mut ui, &mut streams, q_max, dropped_curr),
+                None => ui.draw(&mut streams, q_max, dropped_curr)
+            }
 
-            log(format!("redraw[q:{} packets:{}] took {:?}", q_max, packets, start.elapsed()));
+            last_redraw = start.elapsed();
+            log(format!("redraw[q:{} packets:{}] took {:?}", q_max, packets, last_redraw));
 
             if dropped_curr > 0 {
                 log(format!("err: dropped {} packets", dropped_curr));
@@ -97,6 +202,54 @@ pub fn run(args: HashSet<String>) {
             q_max = 0;
             last_dropped = dropped;
         }
+
+        // the sinks get what's left of the interval on the way out //
+        let quit = ui.quitting || summary::stopped() || batch.as_ref().is_some_and(|batch| batch.done());
+        let now = etc::millitime();
+        if sinks.due(now) || quit {
+            let dropped = pcap.packets_dropped();
+            let tick = Tick {
+                ts: now, interval: (now - sinks.last()) as u64, q_depth: sink_q_max, dropped: dropped - sink_dropped,
+                redraw: last_redraw, caches: resolver.cache_sizes()
+            };
+            sinks.write(&tick, &streams, quit);
+            sink_q_max = 0;
+            sink_dropped = dropped;
+        }
+
+        if quit {
+            finish(&mut sinks, batch.is_none());
+        }
+    }
+}
+
+// the way out once we're going. the summary goes after curses so it isn't
+// drawn over //
+fn finish(sinks: &mut Sinks, curses: bool) -> ! {
+    if curses {
+        ui::end();
+    }
+    if let Some(summary) = &mut sinks.summary {
+        if let Err(msg) = summary.report(etc::millitime()) {
+            eprintln!("summary: {}", msg);
+        }
+    }
+    if curses {
+        eprintln!("bye");
+    }
+    std::process::exit(0);
+}
+
+fn device(config: &Config) -> Device {
+    match config.get_str("interface") {
+        Some(name) => match Device::list().unwrap().into_iter().find(|dev| dev.name == name) {
+            Some(dev) => dev,
+            None => {
+                eprintln!("config: no such interface [{}]", name);
+                std::process::exit(-97);
+            }
+        },
+        None => Device::lookup().unwrap().unwrap()
     }
 }
 
@@ -125,11 +278,27 @@ fn tally(pac_dat: &mut PacDat, streams: &mut Streams, resolver:&mut Resolver, in
     }
 
     {   // tally by corp //
-        let key = match resolver.resolve_company(&pac_dat.remote_addr()) {
-            Some(corp) => corp,
-            None => resolver.resolve_host(pac_dat.remote_addr())
+        let remote_addr = pac_dat.remote_addr();
+        // hosts on our own subnet keep a row each //
+        let special = match pac_dat.local_traffic {
+            Some(true) => None,
+            _ => resolver.classify(&remote_addr)
+        };
+        let key = match special {
+            Some(special) => special.label().to_string(),
+            None => match resolver.resolve_company(&remote_addr) {
+                Some(corp) => corp,
+                None => resolver.resolve_host(remote_addr)
+            }
+        };
+        stream_for(key.to_string(), pac_dat, &mut streams.by_corp, resolver).tally(&pac_dat);
+
+        // tally by cloud - falling back to corp for everything else //
+        let key = match special {
+            Some(_) => key,
+            None => resolver.resolve_cloud(&remote_addr).unwrap_or(key)
         };
-        stream_for(key, pac_dat, &mut streams.by_corp, resolver).tally(&pac_dat);
+        stream_for(key, pac_dat, &mut streams.by_cloud, resolver).tally(pac_dat);
     }
 }
 
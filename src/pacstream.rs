@@ -1,3 +1,4 @@
+use std::collections::VecDeque;
 use std::net::IpAddr;
 use std::time::{Duration, UNIX_EPOCH};
 
@@ -5,14 +6,21 @@ use chrono::{DateTime, Utc};
 use etherparse::IpNumber;
 
 use crate::etc;
-use crate::pacdat::{Dir, PacDat};
+use crate::etc::log;
+use crate::pacdat::{Dir, PacDat, StreamKey};
 use crate::resolver::Resolver;
 
+// intervals of rate history kept per stream //
+pub const HISTORY_LEN: usize = 60;
+
 #[derive(Clone)]
 #[derive(Debug)]
 pub struct PacStream {
     pub proc: String,
     pub pid: Option<u32>,
+    pub uid: Option<u32>,
+    pub user: Option<String>,
+    pub cmdline: Option<String>,
     pub bytes_sent: u64,
     pub bytes_sent_last: u64,
     pub bytes_recv: u64,
@@ -26,13 +34,23 @@ pub struct PacStream {
     pub remote_host: String,
     pub remote_service: String,
     pub cc: String,
+    pub city: Option<String>,
     pub corp: String,
+    pub asn: Option<u32>,
+    pub sni: Option<String>,
+    pub tag: Option<String>,
+    pub cloud: Option<String>,
+    pub flag: Option<String>,
+    pub ts_first: DateTime<Utc>,
     pub ts_last: DateTime<Utc>,
+    pub history: VecDeque<(u64, u64)>,   // (recv, sent) bytes/s per interval, newest first
     pub foreign: bool,              // foreign = from another local host
     pub local_traffic: bool,        // is the traffic just on our subnet
     pub ip_number: IpNumber,
     pub packets_in: u64,
-    pub packets_out: u64
+    pub packets_in_last: u64,
+    pub packets_out: u64,
+    pub packets_out_last: u64
 }
 
 impl PacStream {
@@ -46,6 +64,9 @@ impl PacStream {
         PacStream {
             proc: "tbd".to_string(),
             pid: None,
+            uid: None,
+            user: None,
+            cmdline: None,
             bytes_sent: 0,
             bytes_sent_last: 0,
             bytes_recv: 0,
@@ -59,42 +80,71 @@ impl PacStream {
             remote_host: "tbd".to_string(),
             remote_service: "tbd".to_string(),
             cc: "?".to_string(),
+            city: None,
             corp: "?".to_string(),
+            asn: None,
+            sni: pac_dat.sni.clone(),
+            tag: None,
+            cloud: None,
+            flag: None,
+            ts_first: pac_dat.ts,
             ts_last: pac_dat.ts,
+            history: VecDeque::with_capacity(HISTORY_LEN),
             foreign: pac_dat.foreign.unwrap(),
             local_traffic: pac_dat.local_traffic.unwrap(),
             ip_number: pac_dat.ip_number.unwrap(),
             packets_in: 0,
-            packets_out:0
+            packets_in_last: 0,
+            packets_out: 0,
+            packets_out_last: 0
         }
     }
 
+    // saturating, as a flow record's counts are whatever an exporter says //
     pub fn tally(&mut self, pac_dat:&PacDat) {
-        let len = pac_dat.len.unwrap() as u64;
-        if pac_dat.dir == Some(Dir::Out) {
-            self.bytes_sent += len;
-            self.bytes_sent_last += len;
-            self.packets_out += 1;
+        let len = pac_dat.len.unwrap();
+        let (bytes, bytes_last, packets, packets_last) = match pac_dat.dir {
+            Some(Dir::Out) => (&mut self.bytes_sent, &mut self.bytes_sent_last, &mut self.packets_out, &mut self.packets_out_last),
+            _ => (&mut self.bytes_recv, &mut self.bytes_recv_last, &mut self.packets_in, &mut self.packets_in_last)
+        };
+        *bytes = bytes.saturating_add(len);
+        *bytes_last = bytes_last.saturating_add(len);
+        *packets = packets.saturating_add(pac_dat.packets);
+        *packets_last = packets_last.saturating_add(pac_dat.packets);
+        self.ts_last = pac_dat.ts;
+        if self.sni.is_none() && pac_dat.sni.is_some() {
+            self.sni = pac_dat.sni.clone();
         }
-        else {
-            self.bytes_recv += len;
-            self.bytes_recv_last += len;
-            self.packets_in += 1;
+    }
+
+    // call before reset_stats //
+    pub fn push_history(&mut self, interval: u64) {
+        if interval == 0 {
+            return;
         }
-        self.ts_last = pac_dat.ts;
+        if self.history.len() == HISTORY_LEN {
+            self.history.pop_back();
+        }
+        self.history.push_front((self.bytes_recv_last.saturating_mul(1000) / interval, self.bytes_sent_last.saturating_mul(1000) / interval));
     }
 
     pub fn reset_stats(&mut self) {
         self.bytes_sent_last = 0;
         self.bytes_recv_last = 0;
+        self.packets_in_last = 0;
+        self.packets_out_last = 0;
+    }
+
+    pub fn key(&self) -> StreamKey {
+        StreamKey::new(self.ip_number, self.local_addr, self.local_port, self.remote_addr, self.remote_port)
     }
 
     pub fn bytes(&self) -> u64 {
-        self.bytes_sent + self.bytes_recv
+        self.bytes_sent.saturating_add(self.bytes_recv)
     }
 
     pub fn bytes_last(&self) -> u64 {
-        self.bytes_sent_last + self.bytes_recv_last
+        self.bytes_sent_last.saturating_add(self.bytes_recv_last)
     }
 
     pub fn age(&self) -> String {
@@ -123,21 +173,42 @@ impl PacStream {
                 },
                 None => "-".to_string()
             };
+            if let Some(pid) = self.pid {
+                self.uid = resolver.resolve_uid(pid);
+                self.user = self.uid.and_then(|uid| resolver.resolve_user(uid));
+                self.cmdline = resolver.resolve_cmdline(pid);
+            }
         };
+        let special = resolver.classify(&self.remote_addr);
         self.local_host = resolver.resolve_host(self.local_addr).to_string();
-        self.remote_host = resolver.resolve_host(self.remote_addr).to_string();
+        self.remote_host = match special {
+            Some(special) if !special.resolvable() => self.remote_addr.to_string(),
+            _ => resolver.resolve_host(self.remote_addr).to_string()
+        };
         self.local_service = resolver.resolve_service(self.local_port);
         self.remote_service = resolver.resolve_service(self.remote_port);
         if self.local_traffic {
             self.cc = "-".to_string();
             self.corp = "-".to_string();
         }
+        else if let Some(special) = special {
+            self.cc = "-".to_string();
+            self.corp = special.label().to_string();
+        }
         else {
             self.cc = resolver.resolve_cc(&self.remote_addr);
+            self.city = resolver.resolve_city(&self.remote_addr);
+            self.asn = resolver.resolve_asn(&self.remote_addr);
             self.corp = match resolver.resolve_company(&self.remote_addr) {
                 Some(corp) => corp,
                 None => "?".to_string()
             };
+            self.tag = resolver.resolve_tag(&self.remote_addr);
+            self.cloud = resolver.resolve_cloud(&self.remote_addr);
+        }
+        self.flag = resolver.resolve_flag(&self.remote_addr);
+        if let Some(list) = &self.flag {
+            log(format!("flagged: {} on {} <{}>", self.remote_addr, list, self.proc));
         }
         self.to_owned()
     }
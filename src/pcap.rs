@@ -1,6 +1,6 @@
 use IpAddr::{V4, V6};
 use std::collections::BTreeSet;
-use std::net::IpAddr;
+use std::net::{IpAddr, UdpSocket};
 use std::sync::{Arc, mpsc};
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::mpsc::{Receiver, Sender};
@@ -11,11 +11,19 @@ use etherparse::InternetSlice::Ipv6;
 use etherparse::NetSlice::Ipv4;
 use etherparse::SlicedPacket;
 use etherparse::TransportSlice::{Tcp, Udp};
-use pcap::{Capture, Device, Packet};
+use pcap::{Active, Capture, Device, Packet};
 
+use crate::collector;
 use crate::etc::log;
 use crate::pacdat::{Dir, PacDat};
 use crate::subnets::same_subnet;
+use crate::tls;
+
+// packets from here, or flow records from elsewhere //
+pub enum Source {
+    Capture(Capture<Active>),
+    Collector(UdpSocket)
+}
 
 pub struct Pcap {
     q_depth: Arc<AtomicU64>,
@@ -35,21 +43,31 @@ impl Pcap {
         }
     }
 
-    pub fn start(&self, dev:Device) {
+    // separate from start so privileges can be dropped in between //
+    pub fn open(dev:Device) -> Result<Capture<Active>, String> {
+        let name = dev.name.to_string();
+        // note that we have immediate_mode=true in addition to non-zero buffer.
+        // this seems to not konk out when we are eg making a fast transfer.
+        Capture::from_device(dev)
+            .and_then(|cap| cap.promisc(true).immediate_mode(true).buffer_size(1000*1000*1000).open())
+            .map_err(|err| format!("{}: {}", name, err))
+    }
+
+    pub fn start(&self, source:Source) {
         let dropped_ref = self.packets_dropped.clone();
         let q_depth_ref = self.q_depth.clone();
         let tx_ref = self.tx.clone();
-        let _ = thread::Builder::new()
-            .name("pacmon:pcap".to_string())
-            .spawn(move || Pcap::start_pcap(tx_ref, dev, q_depth_ref, dropped_ref));
+        let _ = match source {
+            Source::Capture(cap) => thread::Builder::new()
+                .name("pacmon:pcap".to_string())
+                .spawn(move || Pcap::start_pcap(tx_ref, cap, q_depth_ref, dropped_ref)),
+            Source::Collector(sock) => thread::Builder::new()
+                .name("pacmon:collector".to_string())
+                .spawn(move || collector::run(sock, tx_ref, q_depth_ref))
+        };
     }
 
-    fn start_pcap(tx:Sender<PacDat>, dev:Device, q_depth:Arc<AtomicU64>, dropped:Arc<AtomicU64>) {
-        // note that we have immediate_mode=true in addition to non-zero buffer.
-        // this seems to not konk out when we are eg making a fast transfer.
-        let mut cap = Capture::from_device(dev).unwrap()
-            .promisc(true).immediate_mode(true).buffer_size(1000*1000*1000).open().unwrap();
-
+    fn start_pcap(tx:Sender<PacDat>, mut cap:Capture<Active>, q_depth:Arc<AtomicU64>, dropped:Arc<AtomicU64>) {
         loop {
             match cap.next_packet() {
                 Ok(packet) => {
@@ -95,10 +113,10 @@ impl Pcap {
         let dt = DateTime::from_timestamp(ts.tv_sec, (ts.tv_usec * 1000) as u32).unwrap();
 
         let mut pac_dat = PacDat {
-            ts: dt, len: None, ip_number: None,
+            ts: dt, len: None, packets: 1, ip_number: None,
             src_addr: None, dst_addr: None,
             src_port: None, dst_port: None,
-            dir: None, foreign: None, local_traffic: None
+            dir: None, foreign: None, local_traffic: None, sni: None
         };
 
         match SlicedPacket::from_ethernet(&packet) {
@@ -121,12 +139,13 @@ impl Pcap {
                     Some(Tcp(tcp_slice)) => {
                         pac_dat.src_port = Some(tcp_slice.source_port());
                         pac_dat.dst_port = Some(tcp_slice.destination_port());
-                        pac_dat.len = Some(tcp_slice.payload().len() as u32);
+                        pac_dat.len = Some(tcp_slice.payload().len() as u64);
+                        pac_dat.sni = tls::sni(tcp_slice.payload());
                     }
                     Some(Udp(udp_slice)) => {
                         pac_dat.src_port = Some(udp_slice.source_port());
                         pac_dat.dst_port = Some(udp_slice.destination_port());
-                        pac_dat.len = Some(udp_slice.payload().len() as u32)
+                        pac_dat.len = Some(udp_slice.payload().len() as u64)
                     }
                     _ => return None
                 }
@@ -0,0 +1,154 @@
+use std::ffi::CString;
+use std::fs::read_to_string;
+use std::io;
+
+use crate::etc::log;
+
+// what we need rather than who we are. capture needs raw sockets (and admin
+// for promiscuous mode); matching sockets to processes means reading other
+// users' /proc/<pid>/fd, which is mode 500 and ptrace-checked. run as root
+// or after: setcap cap_net_raw,cap_net_admin,cap_sys_ptrace,cap_dac_read_search+ep pacmon
+const CAP_DAC_READ_SEARCH: u32 = 2;
+const CAP_NET_ADMIN: u32 = 12;
+const CAP_NET_RAW: u32 = 13;
+const CAP_SYS_PTRACE: u32 = 19;
+
+struct Cap {
+    bit: u32,
+    name: &'static str,
+    why: &'static str
+}
+
+const CAPTURE: [Cap; 2] = [
+    Cap { bit: CAP_NET_RAW, name: "cap_net_raw", why: "to capture packets" },
+    Cap { bit: CAP_NET_ADMIN, name: "cap_net_admin", why: "for promiscuous mode" }
+];
+
+// all we keep once the capture is open //
+const RESOLVER: [Cap; 2] = [
+    Cap { bit: CAP_SYS_PTRACE, name: "cap_sys_ptrace", why: "to match other users' sockets to processes" },
+    Cap { bit: CAP_DAC_READ_SEARCH, name: "cap_dac_read_search", why: "to read other users' /proc/<pid>/fd" }
+];
+
+// Err if capture can't work, otherwise whatever will be missing from the display //
+pub fn check() -> Result<Vec<String>, String> {
+    let have = match read_to_string("/proc/self/status").ok().and_then(|txt| parse_caps(&txt, "CapEff")) {
+        Some(have) => have,
+        None if unsafe { libc::geteuid() } == 0 => u64::MAX,
+        None => return Err("can't read /proc/self/status and not root - pacmon needs to capture packets".to_string())
+    };
+
+    let lacking = missing(have, &CAPTURE);
+    if !lacking.is_empty() {
+        return Err(format!("pacmon is missing {} - run it as root or give it the capabilities with \
+                            setcap cap_net_raw,cap_net_admin,cap_sys_ptrace,cap_dac_read_search+ep", lacking.join(", ")));
+    }
+
+    Ok(missing(have, &RESOLVER).into_iter()
+        .map(|msg| format!("warn: missing {} - processes of other users will show as '-'", msg))
+        .collect())
+}
+
+fn missing(have: u64, caps: &[Cap]) -> Vec<String> {
+    caps.iter()
+        .filter(|cap| have & (1 << cap.bit) == 0)
+        .map(|cap| format!("{} ({})", cap.name, cap.why))
+        .collect()
+}
+
+// "CapEff:\t000001ffffffffff" //
+fn parse_caps(status: &str, field: &str) -> Option<u64> {
+    status.lines()
+        .find_map(|line| line.strip_prefix(field)?.strip_prefix(':'))
+        .and_then(|hex| u64::from_str_radix(hex.trim(), 16).ok())
+}
+
+// call once the capture is open and before any threads are started - caps
+// are per thread. as root, becomes `user` (unless that's root); either way
+// ends up with no more than the resolver caps //
+pub fn drop_to(user: &str) -> Result<(), String> {
+    if unsafe { libc::geteuid() } == 0 && user != "root" {
+        let (uid, gid) = lookup_user(user)?;
+        unsafe {
+            // keep the permitted set across setuid so we can pick from it below //
+            errno(libc::prctl(libc::PR_SET_KEEPCAPS, 1, 0, 0, 0), "prctl")?;
+            errno(libc::setgroups(1, &gid), "setgroups")?;
+            errno(libc::setgid(gid), "setgid")?;
+            errno(libc::setuid(uid), "setuid")?;
+        }
+    }
+
+    let keep = RESOLVER.iter().fold(0u64, |caps, cap| caps | 1 << cap.bit);
+    let permitted = read_to_string("/proc/self/status").ok()
+        .and_then(|txt| parse_caps(&txt, "CapPrm"))
+        .unwrap_or(0);
+    capset(keep & permitted)?;
+
+    log(format!("privs: now uid:{} gid:{} caps:{:x}", unsafe { libc::getuid() }, unsafe { libc::getgid() }, keep & permitted));
+    Ok(())
+}
+
+fn lookup_user(user: &str) -> Result<(libc::uid_t, libc::gid_t), String> {
+    let name = CString::new(user).map_err(|e| e.to_string())?;
+    let pw = unsafe { libc::getpwnam(name.as_ptr()) };
+    if pw.is_null() {
+        return Err(format!("no such user [{}]", user));
+    }
+    let pw = unsafe { &*pw };
+    Ok((pw.pw_uid, pw.pw_gid))
+}
+
+fn errno(ret: i32, what: &str) -> Result<(), String> {
+    match ret {
+        0 => Ok(()),
+        _ => Err(format!("{}: {}", what, io::Error::last_os_error()))
+    }
+}
+
+#[repr(C)]
+struct CapHeader {
+    version: u32,
+    pid: i32
+}
+
+#[repr(C)]
+struct CapData {
+    effective: u32,
+    permitted: u32,
+    inheritable: u32
+}
+
+const LINUX_CAPABILITY_VERSION_3: u32 = 0x20080522;
+
+// effective and permitted both, nothing inheritable. there's no wrapper in libc //
+fn capset(caps: u64) -> Result<(), String> {
+    let header = CapHeader { version: LINUX_CAPABILITY_VERSION_3, pid: 0 };
+    let data = [
+        CapData { effective: caps as u32, permitted: caps as u32, inheritable: 0 },
+        CapData { effective: (caps >> 32) as u32, permitted: (caps >> 32) as u32, inheritable: 0 }
+    ];
+    let ret = unsafe { libc::syscall(libc::SYS_capset, &header, data.as_ptr()) };
+    errno(ret as i32, "capset")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::privs::{CAPTURE, missing, parse_caps, RESOLVER};
+
+    #[test]
+    fn test_parse_caps() {
+        let status = "Name:\tpacmon\nCapInh:\t0000000000000000\nCapPrm:\t000001ffffffffff\nCapEff:\t0000000000003000\n";
+        assert_eq!(Some(0x3000), parse_caps(status, "CapEff"));
+        assert_eq!(Some(0x1ffffffffff), parse_caps(status, "CapPrm"));
+        assert_eq!(None, parse_caps(status, "CapBnd"));
+        assert_eq!(None, parse_caps("CapEff:\tnope", "CapEff"));
+    }
+
+    #[test]
+    fn test_missing() {
+        assert!(missing(0x3000, &CAPTURE).is_empty());
+        assert_eq!(vec!["cap_net_admin (for promiscuous mode)".to_string()], missing(0x2000, &CAPTURE));
+        assert_eq!(2, missing(0x3000, &RESOLVER).len());
+        assert!(missing(u64::MAX, &RESOLVER).is_empty());
+    }
+}
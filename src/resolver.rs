@@ -3,16 +3,24 @@ use std::fs;
 use std::fs::{File, read_to_string};
 use std::io::{BufRead, BufReader, ErrorKind, Read};
 use std::net::IpAddr;
+use std::os::unix::fs::MetadataExt;
 use std::time::Instant;
 
 use etherparse::IpNumber;
 use glob::glob;
 use once_cell::sync::Lazy;
 use regex::Regex;
+use crate::asn::Asns;
+use crate::blocklist::Blocklists;
+use crate::cloud::CloudRanges;
+use crate::config::Config;
 use crate::etc;
 
 use crate::etc::log;
 use crate::ipdata::IpData;
+use crate::labels::Labels;
+use crate::subnets;
+use crate::subnets::Special;
 
 // $ cat /proc/net/tcp
 //   sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
@@ -35,20 +43,56 @@ pub struct Resolver {
     pid_cache: BTreeMap<(IpNumber, IpAddr, u16), Option<u32>>,
     proc_cache: BTreeMap<u32, Option<String>>,
     services: BTreeMap<u16, String>,
-    ipdata: IpData
+    users: BTreeMap<u32, String>,
+    ipdata: IpData,
+    asns: Asns,
+    labels: Labels,
+    cloud: CloudRanges,
+    blocklists: Blocklists
 }
 
 impl Resolver {
-    pub fn new() -> Self {
+    pub fn new(config: &Config) -> Self {
         let mut services:BTreeMap<u16, String> = BTreeMap::new();
         read_services(&mut services);
 
+        let mut users:BTreeMap<u32, String> = BTreeMap::new();
+        read_users(&mut users);
+
+        let labels = match config.path("labels") {
+            Some(path) => Labels::load(&path).unwrap_or_else(|msg| {
+                eprintln!("{}", msg);
+                Labels::new()
+            }),
+            None => Labels::new()
+        };
+
+        let cloud = match config.path("cloud") {
+            Some(dir) => CloudRanges::load(&dir),
+            None => CloudRanges::new()
+        };
+
+        let asns = match config.path("asn") {
+            Some(path) => Asns::load(&path),
+            None => Asns::new()
+        };
+
+        let blocklists = match config.path("blocklists") {
+            Some(dir) => Blocklists::load(&dir),
+            None => Blocklists::new()
+        };
+
         Resolver {
             dns_cache: BTreeMap::new(),
             pid_cache: BTreeMap::new(),
             proc_cache: BTreeMap::new(),
             services,
-            ipdata: IpData::new()
+            users,
+            ipdata: IpData::new(),
+            asns,
+            labels,
+            cloud,
+            blocklists
         }
     }
 
@@ -69,6 +113,25 @@ impl Resolver {
         self.proc_cache.entry(pid).or_insert_with(|| proc_for_pid(pid)).clone()
     }
 
+    pub fn resolve_uid(&self, pid: u32) -> Option<u32> {
+        fs::metadata(format!("/proc/{}", pid)).ok().map(|meta| meta.uid())
+    }
+
+    pub fn resolve_user(&self, uid: u32) -> Option<String> {
+        self.users.get(&uid).cloned()
+    }
+
+    // the full command line, args separated by spaces //
+    pub fn resolve_cmdline(&self, pid: u32) -> Option<String> {
+        match fs::read(format!("/proc/{}/cmdline", pid)) {
+            Ok(bytes) => {
+                let cmd = String::from_utf8_lossy(&bytes).replace('\0', " ");
+                Some(cmd.trim_end().to_string())
+            }
+            Err(_) => None
+        }
+    }
+
     pub fn resolve_host(&mut self, addr: IpAddr) -> String {
         match self.dns_cache.get(&addr) {
             Some(host) => return host.to_string(),
@@ -89,6 +152,10 @@ impl Resolver {
         host
     }
 
+    pub fn cache_sizes(&self) -> Vec<(&'static str, usize)> {
+        vec![("dns", self.dns_cache.len()), ("pid", self.pid_cache.len()), ("proc", self.proc_cache.len())]
+    }
+
     pub fn resolve_service(&self, port:u16) -> String {
         match self.services.get(&port) {
             Some(service) => service.to_string(),
@@ -97,11 +164,50 @@ impl Resolver {
     }
 
     pub fn resolve_cc(&self, addr:&IpAddr) -> String {
-        self.ipdata.cc(addr)
+        match self.labels.lookup(addr) {
+            Some(label) if !label.cc.is_empty() => label.cc.to_string(),
+            _ => self.ipdata.cc(addr)
+        }
+    }
+
+    // labelled addresses are ours to name - don't guess a city //
+    pub fn resolve_city(&self, addr:&IpAddr) -> Option<String> {
+        match self.labels.lookup(addr) {
+            Some(_) => None,
+            None => self.ipdata.city(addr)
+        }
+    }
+
+    pub fn resolve_asn(&self, addr:&IpAddr) -> Option<u32> {
+        self.asns.lookup(addr)
     }
 
     pub fn resolve_company(&self, addr:&IpAddr) -> Option<String> {
-        self.ipdata.company(addr)
+        match self.labels.lookup(addr) {
+            Some(label) => Some(label.corp.to_string()),
+            None => self.ipdata.company(addr)
+        }
+    }
+
+    pub fn resolve_cloud(&self, addr:&IpAddr) -> Option<String> {
+        self.cloud.lookup(addr).map(|cloud| cloud.label())
+    }
+
+    // name of the blocklist the address is on, if any //
+    pub fn resolve_flag(&self, addr:&IpAddr) -> Option<String> {
+        self.blocklists.lookup(addr).map(|list| list.to_string())
+    }
+
+    // our own labels trump the special purpose registry //
+    pub fn classify(&self, addr:&IpAddr) -> Option<Special> {
+        match self.labels.lookup(addr) {
+            Some(_) => None,
+            None => subnets::classify(addr)
+        }
+    }
+
+    pub fn resolve_tag(&self, addr:&IpAddr) -> Option<String> {
+        self.labels.lookup(addr).and_then(|label| label.tag.clone())
     }
 }
 
@@ -123,6 +229,24 @@ fn read_services(services:&mut BTreeMap<u16, String>) {
     }
 }
 
+// root:x:0:0:root:/root:/bin/bash //
+fn read_users(users:&mut BTreeMap<u32, String>) {
+    let txt = match read_to_string("/etc/passwd") {
+        Ok(txt) => txt,
+        Err(err) => {
+            log(format!("/etc/passwd: {}", err));
+            return;
+        }
+    };
+
+    for line in txt.lines() {
+        let fields: Vec<&str> = line.split(':').collect();
+        if let (Some(name), Some(Ok(uid))) = (fields.first(), fields.get(2).map(|uid| uid.parse::<u32>())) {
+            users.entry(uid).or_insert(name.to_string());
+        }
+    }
+}
+
 fn proc_for_pid(pid:u32) -> Option<String> {
     let start = Instant::now();
     let path = format!("/proc/{}/cmdline", pid);
@@ -244,13 +368,14 @@ mod tests {
 
     use etherparse::IpNumber;
 
+    use crate::config::Config;
     use crate::etc::log;
     use crate::resolver::{create_key, extract_hex_ip_port_inode, pid_for_socket_inode, proc_for_pid, resolve_socket_inode, Resolver, to_hex_nbo};
 
     fn _resolve_proc_old(sock_type: &IpNumber, addr: &IpAddr, port: u16) -> String {
-        match Resolver::new().resolve_pid(sock_type, addr, port) {
+        match Resolver::new(&Config::new()).resolve_pid(sock_type, addr, port) {
             None => "?".to_string(),
-            Some(pid) => match Resolver::new().resolve_proc(pid) {
+            Some(pid) => match Resolver::new(&Config::new()).resolve_proc(pid) {
                 Some(proc) => proc,
                 None => "?".to_string()
             }
@@ -375,6 +500,22 @@ mod tests {
         assert_eq!(pid.unwrap(), std::process::id());
     }
 
+    #[test]
+    fn test_resolve_cmdline() {
+        let resolver = Resolver::new(&Config::new());
+        let cmdline = resolver.resolve_cmdline(std::process::id()).unwrap();
+        assert!(cmdline.contains("pacmon-"), "{}", cmdline);
+        assert!(!cmdline.contains('\0'));
+        assert_eq!(None, resolver.resolve_cmdline(u32::MAX));
+    }
+
+    #[test]
+    fn test_resolve_user() {
+        let resolver = Resolver::new(&Config::new());
+        assert_eq!(Some("root".to_string()), resolver.resolve_user(0));
+        assert_eq!(None, resolver.resolve_user(u32::MAX));
+    }
+
     #[test]
     fn test_proc_for_pid() {
         let pid = std::process::id();
@@ -388,13 +529,13 @@ mod tests {
     fn test_resolve_host() {
         // todo: determine ip dynamically //
         let addr = V4(Ipv4Addr::from_str("192.168.1.109").unwrap());
-        let host = Resolver::new().resolve_host(addr);
+        let host = Resolver::new(&Config::new()).resolve_host(addr);
         assert_eq!("DEV", host);
     }
 
     #[test]
     fn test_resolve_service() {
-        assert_eq!("http", Resolver::new().resolve_service(80));
-        assert_eq!("9934", Resolver::new().resolve_service(9934));
+        assert_eq!("http", Resolver::new(&Config::new()).resolve_service(80));
+        assert_eq!("9934", Resolver::new(&Config::new()).resolve_service(9934));
     }
 }
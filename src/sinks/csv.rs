@@ -0,0 +1,167 @@
+use std::fs;
+use std::io::Write;
+
+use chrono::DateTime;
+
+use crate::etc;
+use crate::pacmon::Streams;
+use crate::pacstream::PacStream;
+use crate::sinks::{fmt_ts, Sink, Tick};
+
+// raw numbers rather than what's on screen, for spreadsheets //
+const INTERVAL_HEADER: &str = "ts,interval_ms,proto,local_addr,local_port,remote_addr,remote_port,proc,pid,user,\
+local_host,remote_host,local_service,remote_service,cc,corp,cloud,list,bytes_in,bytes_out,packets_in,packets_out,\
+total_bytes_in,total_bytes_out,total_packets_in,total_packets_out,first,last";
+
+const STREAM_HEADER: &str = "proto,local_addr,local_port,remote_addr,remote_port,proc,pid,user,\
+local_host,remote_host,local_service,remote_service,cc,corp,cloud,list,in_rate,out_rate,\
+total_bytes_in,total_bytes_out,total_packets_in,total_packets_out,first,last";
+
+const CORP_HEADER: &str = "name,cc,in_rate,out_rate,total_bytes_in,total_bytes_out,total_packets_in,total_packets_out,first,last";
+
+// a row per stream that saw traffic, per interval //
+pub struct Csv {
+    out: Box<dyn Write>,
+    header: bool
+}
+
+impl Csv {
+    // no header when appending to something that already has one //
+    pub fn new(out: Box<dyn Write>, header: bool) -> Self {
+        Csv { out, header }
+    }
+}
+
+impl Sink for Csv {
+    fn write(&mut self, tick: &Tick, streams: &Streams) -> Result<(), String> {
+        if self.header {
+            writeln!(self.out, "{}", INTERVAL_HEADER).map_err(|e| e.to_string())?;
+            self.header = false;
+        }
+
+        let ts = fmt_ts(DateTime::from_timestamp_millis(tick.ts).unwrap_or_default());
+        for stream in streams.by_stream.values().filter(|s| s.packets_in_last + s.packets_out_last > 0) {
+            let mut row = vec![ts.to_string(), tick.interval.to_string()];
+            row.extend(stream_fields(stream));
+            row.extend([stream.bytes_recv_last, stream.bytes_sent_last, stream.packets_in_last, stream.packets_out_last].map(|n| n.to_string()));
+            row.extend(totals(stream));
+            writeln!(self.out, "{}", join(&row)).map_err(|e| e.to_string())?;
+        }
+
+        self.out.flush().map_err(|e| e.to_string())
+    }
+}
+
+// the rows as shown - streams, or corps (and clouds) when `corp` - rates
+// being those of the last interval //
+pub fn snapshot(path: &str, rows: &[PacStream], corp: bool, cloud: bool) -> Result<(), String> {
+    let mut txt = String::new();
+    txt.push_str(if corp { CORP_HEADER } else { STREAM_HEADER });
+    txt.push('\n');
+
+    for stream in rows {
+        let mut row = match corp {
+            true => vec![name(stream, cloud), stream.cc.to_string()],
+            false => stream_fields(stream)
+        };
+        let (recv, sent) = stream.history.front().cloned().unwrap_or((0, 0));
+        row.extend([recv.to_string(), sent.to_string()]);
+        row.extend(totals(stream));
+        txt.push_str(&join(&row));
+        txt.push('\n');
+    }
+
+    fs::write(path, txt).map_err(|e| format!("{}: {}", path, e))
+}
+
+// as corp mode shows it //
+fn name(stream: &PacStream, cloud: bool) -> String {
+    match (&stream.cloud, stream.corp.len() < 2) {
+        (Some(name), _) if cloud => name.to_string(),
+        (_, true) => stream.remote_host.to_string(),
+        (_, false) => stream.corp.to_string()
+    }
+}
+
+fn stream_fields(stream: &PacStream) -> Vec<String> {
+    vec![
+        etc::str(stream.ip_number),
+        stream.local_addr.to_string(),
+        stream.local_port.to_string(),
+        stream.remote_addr.to_string(),
+        stream.remote_port.to_string(),
+        match stream.foreign {
+            true => "".to_string(),
+            false => stream.proc.to_string()
+        },
+        stream.pid.map(|pid| pid.to_string()).unwrap_or_default(),
+        stream.user.clone().unwrap_or_default(),
+        stream.local_host.to_string(),
+        stream.remote_host.to_string(),
+        stream.local_service.to_string(),
+        stream.remote_service.to_string(),
+        stream.cc.to_string(),
+        stream.corp.to_string(),
+        stream.cloud.clone().unwrap_or_default(),
+        stream.flag.clone().unwrap_or_default()
+    ]
+}
+
+fn totals(stream: &PacStream) -> Vec<String> {
+    let mut ret: Vec<String> = [stream.bytes_recv, stream.bytes_sent, stream.packets_in, stream.packets_out]
+        .map(|n| n.to_string()).to_vec();
+    ret.extend([fmt_ts(stream.ts_first), fmt_ts(stream.ts_last)]);
+    ret
+}
+
+fn join(fields: &[String]) -> String {
+    fields.iter().map(|field| quote(field)).collect::<Vec<String>>().join(",")
+}
+
+// rfc 4180 //
+fn quote(field: &str) -> String {
+    match field.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::read_to_string;
+
+    use crate::sinks::csv::{CORP_HEADER, quote, snapshot, STREAM_HEADER};
+    use crate::sinks::tests::stream;
+
+    #[test]
+    fn test_quote() {
+        assert_eq!("plain", quote("plain"));
+        assert_eq!("\"Amazon, Inc\"", quote("Amazon, Inc"));
+        assert_eq!("\"say \"\"hi\"\"\"", quote("say \"hi\""));
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let mut stream = stream();
+        stream.corp = "Cloudflare, Inc".to_string();
+        stream.history.push_front((750, 50));
+
+        let path = std::env::temp_dir().join(format!("pacmon-test-{}.csv", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        snapshot(path, &[stream.clone()], false, false).unwrap();
+        let txt = read_to_string(path).unwrap();
+        let lines: Vec<&str> = txt.lines().collect();
+        assert_eq!(STREAM_HEADER, lines[0]);
+        assert!(lines[1].starts_with("TCP,192.168.1.2,50000,1.1.1.1,443,"), "{}", lines[1]);
+        assert!(lines[1].contains(",\"Cloudflare, Inc\",,,750,50,1500,100,1,1,"), "{}", lines[1]);
+        assert_eq!(STREAM_HEADER.split(',').count(), lines[1].replace("\"Cloudflare, Inc\"", "x").split(',').count());
+
+        snapshot(path, &[stream], true, false).unwrap();
+        let txt = read_to_string(path).unwrap();
+        assert!(txt.starts_with(CORP_HEADER));
+        assert!(txt.contains("\n\"Cloudflare, Inc\",?,750,50,1500,100,"), "{}", txt);
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
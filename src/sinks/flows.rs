@@ -0,0 +1,428 @@
+use std::collections::BTreeMap;
+use std::net::{IpAddr, UdpSocket};
+
+use crate::etc::log;
+use crate::pacdat::StreamKey;
+use crate::pacmon::Streams;
+use crate::pacstream::PacStream;
+use crate::sinks::{Sink, Tick};
+
+// a software flow probe: a record per direction of each stream to a netflow
+// v9 or ipfix collector, once it's been quiet for `idle` or every `active`
+// while it isn't. on top of the usual fields each record says which process
+// it was - something a probe on a router can't know //
+pub struct FlowExport {
+    sock: UdpSocket,
+    format: Format,
+    active: i64,    // ms
+    idle: i64,
+    flows: BTreeMap<StreamKey, Exported>,
+    started: i64,
+    messages: u64,
+    sequence: u32
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Format {
+    V9,
+    Ipfix
+}
+
+// what's been sent of a stream so far //
+struct Exported {
+    bytes_in: u64,
+    bytes_out: u64,
+    packets_in: u64,
+    packets_out: u64,
+    from: i64,      // ms, what's not been sent came after this
+    at: i64         // when it last was
+}
+
+struct Record {
+    src: IpAddr,
+    src_port: u16,
+    dst: IpAddr,
+    dst_port: u16,
+    proto: u8,
+    egress: bool,
+    bytes: u64,
+    packets: u64,
+    start: i64,
+    end: i64,
+    proc: String,
+    pid: u32
+}
+
+// RFC 5612's documentation number, until there's one of our own //
+const PEN: u32 = 32473;
+const PROC_LEN: u16 = 32;
+
+const TEMPLATE_V4: u16 = 256;
+const TEMPLATE_V6: u16 = 257;
+
+// (ipfix element, length), the high bit for ours. v9 has nowhere to put an
+// enterprise number so they go as plain types from the top half //
+const FIELDS_V4: [(u16, u16); 12] = [
+    (1, 8), (2, 8), (4, 1), (7, 2), (8, 4), (11, 2), (12, 4), (61, 1), (152, 8), (153, 8), (0x8001, PROC_LEN), (0x8002, 4)
+];
+const FIELDS_V6: [(u16, u16); 12] = [
+    (1, 8), (2, 8), (4, 1), (7, 2), (27, 16), (11, 2), (28, 16), (61, 1), (152, 8), (153, 8), (0x8001, PROC_LEN), (0x8002, 4)
+];
+
+// records per message, keeping under the mtu //
+const BATCH: usize = 10;
+// templates go with the first message and every so many after //
+const TEMPLATE_EVERY: u64 = 20;
+
+impl FlowExport {
+    pub fn new(addr: &str, format: Format, active_secs: i64, idle_secs: i64, now: i64) -> Result<Self, String> {
+        let err = |e: std::io::Error| format!("flows: {}: {}", addr, e);
+        let sock = UdpSocket::bind(if addr.starts_with('[') { "[::]:0" } else { "0.0.0.0:0" }).map_err(err)?;
+        sock.connect(addr).map_err(err)?;
+        log(format!("flows: {:?} to {}, active:{}s idle:{}s", format, addr, active_secs, idle_secs));
+
+        Ok(FlowExport {
+            sock,
+            format,
+            active: active_secs * 1000,
+            idle: idle_secs * 1000,
+            flows: BTreeMap::new(),
+            started: now,
+            messages: 0,
+            sequence: 0
+        })
+    }
+
+    // everything that's due, or everything not yet sent when `all` //
+    fn expired(&mut self, now: i64, streams: &Streams, all: bool) -> Vec<Record> {
+        // gone from the streams, gone from here //
+        self.flows.retain(|key, _| streams.by_stream.contains_key(key));
+
+        let mut ret = Vec::new();
+        for (key, stream) in &streams.by_stream {
+            let first = stream.ts_first.timestamp_millis();
+            let last = stream.ts_last.timestamp_millis();
+            let sent = self.flows.entry(key.clone()).or_insert(Exported {
+                bytes_in: 0, bytes_out: 0, packets_in: 0, packets_out: 0, from: first, at: first
+            });
+
+            let (packets_in, packets_out) = (stream.packets_in - sent.packets_in, stream.packets_out - sent.packets_out);
+            if packets_in + packets_out == 0 {
+                // so whatever comes next starts after now //
+                sent.from = now;
+                continue;
+            }
+            if !all && now - last < self.idle && now - sent.at < self.active {
+                continue;
+            }
+
+            let start = sent.from.min(last);
+            if packets_in > 0 {
+                ret.push(record(stream, false, stream.bytes_recv - sent.bytes_in, packets_in, start, last));
+            }
+            if packets_out > 0 {
+                ret.push(record(stream, true, stream.bytes_sent - sent.bytes_out, packets_out, start, last));
+            }
+
+            *sent = Exported {
+                bytes_in: stream.bytes_recv, bytes_out: stream.bytes_sent,
+                packets_in: stream.packets_in, packets_out: stream.packets_out,
+                from: now, at: now
+            };
+        }
+        ret
+    }
+
+    fn message(&mut self, now: i64, template: u16, records: &[Record]) -> Vec<u8> {
+        let mut sets = Vec::new();
+        let mut count = records.len() as u16;
+
+        if self.messages.is_multiple_of(TEMPLATE_EVERY) {
+            let mut set = Vec::new();
+            for (id, fields) in [(TEMPLATE_V4, &FIELDS_V4), (TEMPLATE_V6, &FIELDS_V6)] {
+                put16(&mut set, id);
+                put16(&mut set, fields.len() as u16);
+                for (kind, len) in fields {
+                    put16(&mut set, *kind);
+                    put16(&mut set, *len);
+                    if self.format == Format::Ipfix && kind & 0x8000 != 0 {
+                        put32(&mut set, PEN);
+                    }
+                }
+            }
+            sets.extend(set_header(if self.format == Format::V9 { 0 } else { 2 }, set.len()));
+            sets.extend(set);
+            count += 2;
+        }
+
+        let mut set = Vec::new();
+        for rec in records {
+            encode(&mut set, rec);
+        }
+        while set.len() % 4 != 0 {
+            set.push(0);
+        }
+        sets.extend(set_header(template, set.len()));
+        sets.extend(set);
+
+        let mut ret = Vec::new();
+        match self.format {
+            Format::V9 => {
+                put16(&mut ret, 9);
+                put16(&mut ret, count);
+                put32(&mut ret, (now - self.started) as u32);
+                put32(&mut ret, (now / 1000) as u32);
+                put32(&mut ret, self.messages as u32);
+                put32(&mut ret, 0);
+            }
+            Format::Ipfix => {
+                put16(&mut ret, 10);
+                put16(&mut ret, (16 + sets.len()) as u16);
+                put32(&mut ret, (now / 1000) as u32);
+                put32(&mut ret, self.sequence);
+                put32(&mut ret, 0);
+            }
+        }
+        ret.extend(sets);
+
+        self.messages += 1;
+        self.sequence = self.sequence.wrapping_add(records.len() as u32);
+        ret
+    }
+}
+
+impl Sink for FlowExport {
+    fn write(&mut self, tick: &Tick, streams: &Streams) -> Result<(), String> {
+        self.export(tick, streams, false)
+    }
+
+    // unexpired flows would otherwise never be heard of //
+    fn finish(&mut self, tick: &Tick, streams: &Streams) -> Result<(), String> {
+        self.export(tick, streams, true)
+    }
+}
+
+impl FlowExport {
+    fn export(&mut self, tick: &Tick, streams: &Streams, all: bool) -> Result<(), String> {
+        let (v4, v6): (Vec<Record>, Vec<Record>) = self.expired(tick.ts, streams, all).into_iter()
+            .partition(|rec| rec.src.is_ipv4());
+
+        for (template, records) in [(TEMPLATE_V4, v4), (TEMPLATE_V6, v6)] {
+            for batch in records.chunks(BATCH) {
+                let msg = self.message(tick.ts, template, batch);
+                self.sock.send(&msg).map_err(|e| e.to_string())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn record(stream: &PacStream, egress: bool, bytes: u64, packets: u64, start: i64, end: i64) -> Record {
+    let (src, src_port, dst, dst_port) = match egress {
+        true => (stream.local_addr, stream.local_port, stream.remote_addr, stream.remote_port),
+        false => (stream.remote_addr, stream.remote_port, stream.local_addr, stream.local_port)
+    };
+    Record {
+        src, src_port, dst, dst_port,
+        proto: stream.ip_number.0,
+        egress,
+        bytes,
+        packets,
+        start,
+        end,
+        proc: match stream.foreign || stream.pid.is_none() {
+            true => String::new(),
+            false => stream.proc.to_string()
+        },
+        pid: stream.pid.unwrap_or(0)
+    }
+}
+
+// in the order of FIELDS_V4/V6 //
+fn encode(buf: &mut Vec<u8>, rec: &Record) {
+    buf.extend(rec.bytes.to_be_bytes());
+    buf.extend(rec.packets.to_be_bytes());
+    buf.push(rec.proto);
+    put16(buf, rec.src_port);
+    put_addr(buf, rec.src);
+    put16(buf, rec.dst_port);
+    put_addr(buf, rec.dst);
+    buf.push(rec.egress as u8);
+    buf.extend((rec.start as u64).to_be_bytes());
+    buf.extend((rec.end as u64).to_be_bytes());
+
+    let mut proc = rec.proc.as_str();
+    while proc.len() > PROC_LEN as usize {
+        proc = &proc[..proc.char_indices().last().map(|(i, _)| i).unwrap_or(0)];
+    }
+    buf.extend(proc.as_bytes());
+    buf.extend(vec![0u8; PROC_LEN as usize - proc.len()]);
+    put32(buf, rec.pid);
+}
+
+fn set_header(id: u16, len: usize) -> Vec<u8> {
+    let mut ret = Vec::new();
+    put16(&mut ret, id);
+    put16(&mut ret, (4 + len) as u16);
+    ret
+}
+
+fn put16(buf: &mut Vec<u8>, n: u16) {
+    buf.extend(n.to_be_bytes());
+}
+
+fn put32(buf: &mut Vec<u8>, n: u32) {
+    buf.extend(n.to_be_bytes());
+}
+
+fn put_addr(buf: &mut Vec<u8>, addr: IpAddr) {
+    match addr {
+        IpAddr::V4(addr) => buf.extend(addr.octets()),
+        IpAddr::V6(addr) => buf.extend(addr.octets())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::UdpSocket;
+    use std::time::Duration;
+
+    use crate::pacdat::Dir;
+    use crate::pacmon::Streams;
+    use crate::sinks::{Sink, Tick};
+    use crate::sinks::flows::{FIELDS_V4, FlowExport, Format, PEN};
+    use crate::sinks::tests::{pac_dat, stream};
+
+    const T0: i64 = 1_700_000_000_000;
+
+    fn tick(ts: i64) -> Tick {
+        Tick { ts, interval: 1000, q_depth: 0, dropped: 0, redraw: Duration::ZERO, caches: vec![] }
+    }
+
+    fn u16_at(buf: &[u8], at: usize) -> u16 {
+        u16::from_be_bytes(buf[at..at + 2].try_into().unwrap())
+    }
+
+    fn u32_at(buf: &[u8], at: usize) -> u32 {
+        u32::from_be_bytes(buf[at..at + 4].try_into().unwrap())
+    }
+
+    fn u64_at(buf: &[u8], at: usize) -> u64 {
+        u64::from_be_bytes(buf[at..at + 8].try_into().unwrap())
+    }
+
+    fn recv(listener: &UdpSocket) -> Option<Vec<u8>> {
+        let mut buf = [0u8; 2048];
+        listener.recv(&mut buf).ok().map(|len| buf[..len].to_vec())
+    }
+
+    #[test]
+    fn test_ipfix() {
+        let listener = UdpSocket::bind("127.0.0.1:0").unwrap();
+        listener.set_read_timeout(Some(Duration::from_millis(500))).unwrap();
+        let mut flows = FlowExport::new(&listener.local_addr().unwrap().to_string(), Format::Ipfix, 60, 15, T0).unwrap();
+
+        let mut streams = Streams::new();
+        let mut stream = stream();
+        stream.proc = "curl".to_string();
+        stream.pid = Some(4242);
+        streams.by_stream.insert(stream.key(), stream);
+
+        // neither idle nor active yet //
+        flows.write(&tick(T0 + 5_000), &streams).unwrap();
+        assert!(recv(&listener).is_none());
+
+        // idle //
+        flows.write(&tick(T0 + 20_000), &streams).unwrap();
+        let msg = recv(&listener).unwrap();
+        assert_eq!(10, u16_at(&msg, 0));
+        assert_eq!(msg.len(), u16_at(&msg, 2) as usize);
+        assert_eq!(0, u32_at(&msg, 8));
+
+        // two templates, with the pen after each of ours //
+        assert_eq!(2, u16_at(&msg, 16));
+        let template_len = 2 * (4 + FIELDS_V4.len() * 4 + 2 * 4);
+        assert_eq!(4 + template_len, u16_at(&msg, 18) as usize);
+        assert_eq!(PEN, u32_at(&msg, 20 + 4 + 10 * 4 + 4));
+
+        let data = 16 + 4 + template_len;
+        assert_eq!(256, u16_at(&msg, data));
+        let rec = data + 4;
+        assert_eq!(1500, u64_at(&msg, rec));
+        assert_eq!(1, u64_at(&msg, rec + 8));
+        assert_eq!(6, msg[rec + 16]);
+        assert_eq!(443, u16_at(&msg, rec + 17));
+        assert_eq!([1, 1, 1, 1], msg[rec + 19..rec + 23]);
+        assert_eq!(0, msg[rec + 29]);
+        assert_eq!(T0 as u64, u64_at(&msg, rec + 30));
+        assert_eq!(b"curl\0", &msg[rec + 46..rec + 51]);
+        assert_eq!(4242, u32_at(&msg, rec + 78));
+
+        let rec = rec + 82;
+        assert_eq!(100, u64_at(&msg, rec));
+        assert_eq!(1, msg[rec + 29]);
+
+        // nothing new, nothing sent //
+        flows.write(&tick(T0 + 40_000), &streams).unwrap();
+        assert!(recv(&listener).is_none());
+    }
+
+    #[test]
+    fn test_v9_active() {
+        let listener = UdpSocket::bind("127.0.0.1:0").unwrap();
+        listener.set_read_timeout(Some(Duration::from_millis(500))).unwrap();
+        let mut flows = FlowExport::new(&listener.local_addr().unwrap().to_string(), Format::V9, 60, 15, T0).unwrap();
+
+        // still going at the active timeout, so only the deltas each time //
+        let mut streams = Streams::new();
+        let mut stream = stream();
+        let mut dat = pac_dat(Dir::In, 1000);
+        dat.ts = chrono::DateTime::from_timestamp_millis(T0 + 59_000).unwrap();
+        stream.tally(&dat);
+        streams.by_stream.insert(stream.key(), stream.clone());
+
+        flows.write(&tick(T0 + 60_000), &streams).unwrap();
+        let msg = recv(&listener).unwrap();
+        assert_eq!(9, u16_at(&msg, 0));
+        assert_eq!(4, u16_at(&msg, 2));
+        assert_eq!(60_000, u32_at(&msg, 4));
+
+        dat.ts = chrono::DateTime::from_timestamp_millis(T0 + 119_000).unwrap();
+        stream.tally(&dat);
+        streams.by_stream.insert(stream.key(), stream);
+
+        flows.write(&tick(T0 + 120_000), &streams).unwrap();
+        let msg = recv(&listener).unwrap();
+        assert_eq!(1, u16_at(&msg, 2));
+        assert_eq!(1, u32_at(&msg, 12));
+        assert_eq!(256, u16_at(&msg, 20));
+        assert_eq!(1000, u64_at(&msg, 24));
+        assert_eq!((T0 + 60_000) as u64, u64_at(&msg, 24 + 30));
+    }
+
+    #[test]
+    fn test_finish() {
+        let listener = UdpSocket::bind("127.0.0.1:0").unwrap();
+        listener.set_read_timeout(Some(Duration::from_millis(500))).unwrap();
+        let mut flows = FlowExport::new(&listener.local_addr().unwrap().to_string(), Format::Ipfix, 60, 15, T0).unwrap();
+
+        let mut streams = Streams::new();
+        let stream = stream();
+        streams.by_stream.insert(stream.key(), stream);
+
+        flows.write(&tick(T0 + 5_000), &streams).unwrap();
+        assert!(recv(&listener).is_none());
+
+        // whatever's left goes on the way out //
+        flows.finish(&tick(T0 + 6_000), &streams).unwrap();
+        let msg = recv(&listener).unwrap();
+        let data = 16 + 4 + 2 * (4 + FIELDS_V4.len() * 4 + 2 * 4);
+        assert_eq!(4 + 2 * 82, u16_at(&msg, data + 2) as usize);
+        flows.finish(&tick(T0 + 7_000), &streams).unwrap();
+        assert!(recv(&listener).is_none());
+
+        // and what's gone is forgotten //
+        flows.write(&tick(T0 + 8_000), &Streams::new()).unwrap();
+        assert!(flows.flows.is_empty());
+    }
+}
@@ -0,0 +1,136 @@
+use std::io::Write;
+
+use chrono::DateTime;
+use serde_json::{json, Value};
+
+use crate::etc;
+use crate::pacmon::Streams;
+use crate::pacstream::PacStream;
+use crate::sinks::{fmt_ts, Sink, Tick};
+
+// one line per stream that saw traffic in the interval, then one for the
+// interval itself:
+//   {"type":"stream","ts":"..","interval_ms":2000,"proto":"TCP","local_addr":..,"bytes_in":..,..}
+//   {"type":"interval","ts":"..","interval_ms":2000,"streams":40,"active":3,..,"q_depth":0,"dropped":0}
+pub struct JsonLines {
+    out: Box<dyn Write>
+}
+
+impl JsonLines {
+    pub fn new(out: Box<dyn Write>) -> Self {
+        JsonLines { out }
+    }
+}
+
+impl Sink for JsonLines {
+    fn write(&mut self, tick: &Tick, streams: &Streams) -> Result<(), String> {
+        let ts = fmt_ts(DateTime::from_timestamp_millis(tick.ts).unwrap_or_default());
+        let active: Vec<&PacStream> = streams.by_stream.values()
+            .filter(|s| s.packets_in_last + s.packets_out_last > 0)
+            .collect();
+
+        for stream in &active {
+            writeln!(self.out, "{}", stream_record(&ts, tick.interval, stream)).map_err(|e| e.to_string())?;
+        }
+
+        let summary = json!({
+            "type": "interval",
+            "ts": ts,
+            "interval_ms": tick.interval,
+            "streams": streams.by_stream.len(),
+            "active": active.len(),
+            "bytes_in": active.iter().map(|s| s.bytes_recv_last).fold(0, u64::saturating_add),
+            "bytes_out": active.iter().map(|s| s.bytes_sent_last).fold(0, u64::saturating_add),
+            "q_depth": tick.q_depth,
+            "dropped": tick.dropped
+        });
+        writeln!(self.out, "{}", summary).map_err(|e| e.to_string())?;
+
+        self.out.flush().map_err(|e| e.to_string())
+    }
+}
+
+fn stream_record(ts: &str, interval: u64, stream: &PacStream) -> Value {
+    json!({
+        "type": "stream",
+        "ts": ts,
+        "interval_ms": interval,
+        "proto": etc::str(stream.ip_number),
+        "local_addr": stream.local_addr.to_string(),
+        "local_port": stream.local_port,
+        "remote_addr": stream.remote_addr.to_string(),
+        "remote_port": stream.remote_port,
+        "foreign": stream.foreign,
+        "proc": match stream.foreign {
+            true => None,
+            false => Some(&stream.proc)
+        },
+        "pid": stream.pid,
+        "user": stream.user,
+        "local_host": stream.local_host,
+        "remote_host": stream.remote_host,
+        "local_service": stream.local_service,
+        "remote_service": stream.remote_service,
+        "cc": stream.cc,
+        "corp": stream.corp,
+        "asn": stream.asn,
+        "sni": stream.sni,
+        "bytes_in": stream.bytes_recv_last,
+        "bytes_out": stream.bytes_sent_last,
+        "packets_in": stream.packets_in_last,
+        "packets_out": stream.packets_out_last,
+        "total_bytes_in": stream.bytes_recv,
+        "total_bytes_out": stream.bytes_sent,
+        "total_packets_in": stream.packets_in,
+        "total_packets_out": stream.packets_out,
+        "first": fmt_ts(stream.ts_first),
+        "last": fmt_ts(stream.ts_last)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::Value;
+
+    use crate::pacmon::Streams;
+    use crate::sinks::{Sink, Tick};
+    use crate::sinks::json::JsonLines;
+    use crate::sinks::tests::{Shared, stream};
+
+    #[test]
+    fn test_json_lines() {
+        let mut streams = Streams::new();
+        let active = stream();
+        streams.by_stream.insert(active.key(), active);
+
+        let mut idle = stream();
+        idle.local_port = 50001;
+        idle.reset_stats();
+        streams.by_stream.insert(idle.key(), idle);
+
+        let shared = Shared::default();
+        let mut sink = JsonLines::new(Box::new(shared.clone()));
+        sink.write(&Tick { ts: 1_700_000_002_000, interval: 2000, q_depth: 3, dropped: 1, redraw: Duration::ZERO, caches: vec![] }, &streams).unwrap();
+
+        let txt = shared.txt();
+        let lines: Vec<Value> = txt.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(2, lines.len(), "{}", txt);
+
+        assert_eq!("stream", lines[0]["type"]);
+        assert_eq!("2023-11-14T22:13:22.000Z", lines[0]["ts"]);
+        assert_eq!("TCP", lines[0]["proto"]);
+        assert_eq!("1.1.1.1", lines[0]["remote_addr"]);
+        assert_eq!(443, lines[0]["remote_port"]);
+        assert_eq!(1500, lines[0]["bytes_in"]);
+        assert_eq!(100, lines[0]["bytes_out"]);
+        assert_eq!(1, lines[0]["packets_in"]);
+
+        assert_eq!("interval", lines[1]["type"]);
+        assert_eq!(2, lines[1]["streams"]);
+        assert_eq!(1, lines[1]["active"]);
+        assert_eq!(3, lines[1]["q_depth"]);
+        assert_eq!(1, lines[1]["dropped"]);
+    }
+}
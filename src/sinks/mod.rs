@@ -0,0 +1,340 @@
+pub mod csv;
+mod flows;
+mod json;
+mod prometheus;
+mod push;
+
+use std::collections::BTreeMap;
+use std::fs::OpenOptions;
+use std::fs;
+use std::io;
+use std::io::{BufWriter, ErrorKind, stdout, Write};
+use std::time::Duration;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+
+use crate::config::Config;
+use crate::etc;
+use crate::etc::log;
+use crate::history::History;
+use crate::pacdat::StreamKey;
+use crate::pacmon::Streams;
+use crate::pacstream::PacStream;
+use crate::store::Store;
+use crate::summary::Summary;
+
+// somewhere other than the screen for each interval's numbers. sinks are
+// handed the streams just before they roll over, so the *_last fields
+// hold what happened during the interval //
+pub trait Sink {
+    fn write(&mut self, tick: &Tick, streams: &Streams) -> Result<(), String>;
+
+    // the last interval, on the way out //
+    fn finish(&mut self, tick: &Tick, streams: &Streams) -> Result<(), String> {
+        self.write(tick, streams)
+    }
+}
+
+pub struct Tick {
+    pub ts: i64,            // end of the interval, ms
+    pub interval: u64,
+    pub q_depth: u64,
+    pub dropped: u64,
+    pub redraw: Duration,    // how long the previous redraw took
+    pub caches: Vec<(&'static str, usize)>
+}
+
+// the sinks on a clock of their own. the ui's redraws stop while paused,
+// hurry through the first few seconds and come early on a key, none of
+// which belongs in a log file, so each tick works the *_last fields out
+// afresh from the running totals as they were at the one before //
+pub struct Sinks {
+    sinks: Vec<Box<dyn Sink>>,
+    pub summary: Option<Summary>,
+    interval: i64,
+    last: i64,
+    seen: (Totals<StreamKey>, Totals<String>, Totals<String>)
+}
+
+// bytes in, bytes out, packets in, packets out //
+pub type Totals<K> = BTreeMap<K, [u64; 4]>;
+
+impl Sinks {
+    pub fn is_empty(&self) -> bool {
+        self.sinks.is_empty() && self.summary.is_none()
+    }
+
+    pub fn due(&self, now: i64) -> bool {
+        !self.is_empty() && now - self.last >= self.interval
+    }
+
+    // when the interval being ticked started //
+    pub fn last(&self) -> i64 {
+        self.last
+    }
+
+    // not with the rest, before privileges are dropped: sqlite keeps its
+    // journal beside the file, so both had better be --user's //
+    pub fn open_history(&mut self, config: &Config) -> Result<(), String> {
+        if let Some(path) = config.get_path("history") {
+            let retention = config.get_int("retention").filter(|days| *days > 0).unwrap_or(30);
+            let downsample = config.get_int("downsample").filter(|hours| *hours > 0).unwrap_or(24);
+            self.sinks.push(Box::new(Store::open(&path, retention, downsample)?));
+        }
+        Ok(())
+    }
+
+    pub fn write(&mut self, tick: &Tick, streams: &Streams, last: bool) {
+        self.last = tick.ts;
+        if self.is_empty() {
+            return;
+        }
+
+        let streams = Streams {
+            by_stream: since(&streams.by_stream, &mut self.seen.0),
+            by_corp: since(&streams.by_corp, &mut self.seen.1),
+            by_cloud: since(&streams.by_cloud, &mut self.seen.2),
+            history: History::new()
+        };
+        let summary = self.summary.as_mut().map(|summary| summary as &mut dyn Sink);
+        for sink in self.sinks.iter_mut().map(|sink| sink.as_mut()).chain(summary) {
+            let ret = match last {
+                true => sink.finish(tick, &streams),
+                false => sink.write(tick, &streams)
+            };
+            if let Err(msg) = ret {
+                log(format!("err: sink: {}", msg));
+            }
+        }
+    }
+}
+
+fn since<K: Ord + Clone>(streams: &BTreeMap<K, PacStream>, seen: &mut Totals<K>) -> BTreeMap<K, PacStream> {
+    streams.iter()
+        .map(|(key, stream)| {
+            let now = [stream.bytes_recv, stream.bytes_sent, stream.packets_in, stream.packets_out];
+            let then = seen.insert(key.clone(), now).unwrap_or_default();
+            let mut stream = stream.clone();
+            [stream.bytes_recv_last, stream.bytes_sent_last, stream.packets_in_last, stream.packets_out_last] =
+                [0, 1, 2, 3].map(|i| now[i].saturating_sub(then[i]));
+            (key.clone(), stream)
+        })
+        .collect()
+}
+
+// an interval's bytes in, bytes out, packets in and packets out by each of
+// store::KINDS: proc, corp as corp mode has them, cc and remote host.
+// traffic we only see in passing has no process, so it's "-" as in proc mode //
+pub fn by_kind(streams: &Streams) -> [Totals<String>; 4] {
+    let mut ret: [Totals<String>; 4] = Default::default();
+    let mut add = |kind: usize, name: &str, stream: &PacStream| {
+        let entry = ret[kind].entry(name.to_string()).or_default();
+        for (total, n) in entry.iter_mut().zip([stream.bytes_recv_last, stream.bytes_sent_last, stream.packets_in_last, stream.packets_out_last]) {
+            *total = total.saturating_add(n);
+        }
+    };
+    let active = |stream: &&PacStream| stream.packets_in_last + stream.packets_out_last > 0;
+
+    for stream in streams.by_stream.values().filter(active) {
+        add(0, if stream.foreign { "-" } else { &stream.proc }, stream);
+        add(2, &stream.cc, stream);
+        add(3, &stream.remote_host, stream);
+    }
+    for (corp, stream) in streams.by_corp.iter().filter(|(_, stream)| active(stream)) {
+        add(1, corp, stream);
+    }
+    ret
+}
+
+pub fn from_config(config: &Config) -> Result<Sinks, String> {
+    let mut sinks: Vec<Box<dyn Sink>> = Vec::new();
+
+    let stdout = [config.get_bool("batch") == Some(true), config.get_str("json").as_deref() == Some("-"), config.get_str("csv").as_deref() == Some("-")];
+    if stdout.iter().filter(|on| **on).count() > 1 {
+        return Err("only one of batch tables, json and csv can go to stdout".to_string());
+    }
+
+    if let Some(target) = config.get_str("json") {
+        sinks.push(Box::new(json::JsonLines::new(open(&target)?)));
+    }
+
+    if let Some(target) = config.get_str("csv") {
+        let header = target == "-" || fs::metadata(&target).map(|meta| meta.len() == 0).unwrap_or(true);
+        sinks.push(Box::new(csv::Csv::new(open(&target)?, header)));
+    }
+
+    let top = config.get_int("top").filter(|n| *n > 0).unwrap_or(20) as usize;
+    if let Some(addr) = config.get_str("prometheus") {
+        sinks.push(Box::new(prometheus::Prometheus::new(&addr, top)?));
+    }
+
+    let prefix = config.get_str("prefix").unwrap_or("pacmon".to_string());
+    for (key, format) in [("statsd", push::Format::Statsd), ("graphite", push::Format::Graphite)] {
+        if let Some(addr) = config.get_str(key) {
+            sinks.push(Box::new(push::Push::new(&addr, format, &prefix, top)?));
+        }
+    }
+
+    if let Some(addr) = config.get_str("flows") {
+        let format = match config.get_str("flow_format").as_deref() {
+            None | Some("ipfix") => flows::Format::Ipfix,
+            Some("v9") => flows::Format::V9,
+            Some(other) => return Err(format!("flow_format: {}? ipfix or v9", other))
+        };
+        let active = config.get_int("active_timeout").filter(|secs| *secs > 0).unwrap_or(60);
+        let idle = config.get_int("idle_timeout").filter(|secs| *secs > 0).unwrap_or(15);
+        sinks.push(Box::new(flows::FlowExport::new(&addr, format, active, idle, etc::millitime())?));
+    }
+
+    Ok(Sinks {
+        sinks,
+        summary: Summary::from_config(config)?,
+        interval: config.get_int("interval").unwrap_or(4000).max(200),
+        last: etc::millitime(),
+        seen: Default::default()
+    })
+}
+
+// "-" for stdout, otherwise appended to //
+fn open(target: &str) -> Result<Box<dyn Write>, String> {
+    match target {
+        "-" => Ok(Box::new(Stdout)),
+        path => OpenOptions::new().create(true).append(true).open(path)
+            .map(|file| Box::new(BufWriter::new(file)) as Box<dyn Write>)
+            .map_err(|e| format!("{}: {}", path, e))
+    }
+}
+
+pub(crate) fn fmt_ts(ts: DateTime<Utc>) -> String {
+    ts.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+// stdout going away (| head) is as good as being told to stop //
+struct Stdout;
+
+impl Write for Stdout {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        exit_on_epipe(stdout().write(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        exit_on_epipe(stdout().flush())
+    }
+}
+
+fn exit_on_epipe<T>(ret: io::Result<T>) -> io::Result<T> {
+    match ret {
+        Err(err) if err.kind() == ErrorKind::BrokenPipe => std::process::exit(0),
+        ret => ret
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::cell::RefCell;
+    use std::io::Write;
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    use chrono::DateTime;
+    use etherparse::IpNumber;
+    use serde_json::Value;
+
+    use crate::pacdat::{Dir, PacDat};
+    use crate::pacmon::Streams;
+    use crate::pacstream::PacStream;
+    use crate::sinks::{by_kind, json, Sinks, Tick};
+    use crate::subnets::addr;
+
+    // a Write we can look into afterwards //
+    #[derive(Clone, Default)]
+    pub struct Shared(Rc<RefCell<Vec<u8>>>);
+
+    impl Shared {
+        pub fn txt(&self) -> String {
+            String::from_utf8(self.0.borrow().clone()).unwrap()
+        }
+    }
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    pub fn pac_dat(dir: Dir, len: u32) -> PacDat {
+        PacDat {
+            ts: DateTime::from_timestamp(1_700_000_000, 0).unwrap(), len: Some(len as u64), packets: 1, ip_number: Some(IpNumber::TCP),
+            src_addr: Some(addr("192.168.1.2")), dst_addr: Some(addr("1.1.1.1")),
+            src_port: Some(50000), dst_port: Some(443),
+            dir: Some(dir), foreign: Some(false), local_traffic: Some(false), sni: None
+        }
+    }
+
+    // 100 bytes out and 1500 in, unresolved //
+    pub fn stream() -> PacStream {
+        let out = pac_dat(Dir::Out, 100);
+        let mut stream = PacStream::new(&out);
+        stream.tally(&out);
+        stream.tally(&pac_dat(Dir::In, 1500));
+        stream
+    }
+
+    #[test]
+    fn test_sinks() {
+        let shared = Shared::default();
+        let mut sinks = Sinks {
+            sinks: vec![Box::new(json::JsonLines::new(Box::new(shared.clone())))],
+            summary: None,
+            interval: 2000,
+            last: 0,
+            seen: Default::default()
+        };
+        assert!(!sinks.due(1999));
+        assert!(sinks.due(2000));
+
+        let mut streams = Streams::new();
+        let stream = stream();
+        let key = stream.key();
+        streams.by_stream.insert(key.clone(), stream);
+        let tick = |ts| Tick { ts, interval: 2000, q_depth: 0, dropped: 0, redraw: Duration::ZERO, caches: vec![] };
+        sinks.write(&tick(2000), &streams, false);
+
+        // redraws in between take nothing from the sinks //
+        streams.reset_stats();
+        streams.by_stream.get_mut(&key).unwrap().tally(&pac_dat(Dir::In, 500));
+        streams.reset_stats();
+        sinks.write(&tick(4000), &streams, false);
+        sinks.write(&tick(6000), &streams, false);
+
+        let txt = shared.txt();
+        let intervals: Vec<Value> = txt.lines()
+            .map(|l| serde_json::from_str::<Value>(l).unwrap())
+            .filter(|v| v["type"] == "interval")
+            .collect();
+        assert_eq!([1500, 500, 0], [0, 1, 2].map(|i| intervals[i]["bytes_in"].as_u64().unwrap()), "{}", txt);
+        assert_eq!(6000, sinks.last());
+    }
+
+    #[test]
+    fn test_by_kind() {
+        let mut streams = Streams::new();
+        let mut mine = stream();
+        mine.proc = "firefox".to_string();
+        streams.by_stream.insert(mine.key(), mine);
+        let mut passing = PacStream::new(&PacDat { src_port: Some(50001), foreign: Some(true), ..pac_dat(Dir::Out, 100) });
+        passing.tally(&pac_dat(Dir::In, 200));
+        passing.local_host = "192.168.1.2".to_string();
+        streams.by_stream.insert(passing.key(), passing);
+
+        let totals = by_kind(&streams);
+        assert_eq!(Some(&[1500, 100, 1, 1]), totals[0].get("firefox"));
+        assert_eq!(Some(&[200, 0, 1, 0]), totals[0].get("-"));
+        assert!(!totals[0].contains_key("192.168.1.2"));
+        assert_eq!(Some(&[1700, 100, 2, 1]), totals[3].values().next());
+    }
+}
@@ -0,0 +1,324 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::etc::log;
+use crate::pacmon::Streams;
+use crate::pacstream::PacStream;
+use crate::sinks::{Sink, Tick};
+
+// counters for prometheus to scrape from http://<addr>/metrics. each label
+// takes the first `top` distinct values it sees and anything after that is
+// counted as "other", so a port scan can't make a series per port. once in,
+// always in: counters mustn't move between series or they'd go backwards,
+// so it isn't a ranking and a later heavy talker may only ever be "other".
+// only values turning up in the same interval go busiest first. the labels
+// together could still make (top+1)^4 series, so past `max_series` new
+// combinations all go to the one that's "other" throughout //
+pub struct Prometheus {
+    top: usize,
+    max_series: usize,
+    seen: [BTreeSet<String>; 4],
+    counters: BTreeMap<Series, (u64, u64)>,    // bytes, packets
+    dropped: u64,
+    page: Arc<Mutex<String>>,
+    listener: Option<TcpListener>      // until the first write
+}
+
+// proc, corp, cc, service, dir //
+type Series = [String; 5];
+
+const LABELS: [&str; 5] = ["proc", "corp", "cc", "service", "dir"];
+const OTHER: &str = "other";
+const MAX_SERIES: usize = 2000;
+
+// one scraper at a time, so a connection gets this long all told //
+const DEADLINE: Duration = Duration::from_secs(5);
+const MAX_REQUEST: usize = 8192;
+
+impl Prometheus {
+    pub fn new(addr: &str, top: usize) -> Result<Self, String> {
+        let addr = match addr.contains(':') {
+            true => addr.to_string(),
+            false => format!("127.0.0.1:{}", addr)
+        };
+        let listener = TcpListener::bind(&addr).map_err(|e| format!("prometheus: {}: {}", addr, e))?;
+        log(format!("prometheus: listening on {}", addr));
+
+        let mut prometheus = Prometheus::unbound(top);
+        prometheus.listener = Some(listener);
+        Ok(prometheus)
+    }
+
+    // bound while we may still be root, served once we aren't: the
+    // capabilities we let go of are only let go of by our own thread //
+    fn serve(&mut self) -> Result<(), String> {
+        if let Some(listener) = self.listener.take() {
+            let page = self.page.clone();
+            thread::Builder::new()
+                .name("pacmon:metrics".to_string())
+                .spawn(move || serve(listener, page))
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    fn unbound(top: usize) -> Self {
+        Prometheus {
+            top,
+            max_series: MAX_SERIES,
+            seen: Default::default(),
+            counters: BTreeMap::new(),
+            dropped: 0,
+            page: Arc::new(Mutex::new(String::new())),
+            listener: None
+        }
+    }
+
+    fn tally(&mut self, streams: &Streams) {
+        let mut active: Vec<&PacStream> = streams.by_stream.values()
+            .filter(|s| s.packets_in_last + s.packets_out_last > 0)
+            .collect();
+        active.sort_by_key(|s| u64::MAX - s.bytes_last());
+
+        for stream in active {
+            let proc = match stream.foreign {
+                true => "-",
+                false => &stream.proc
+            };
+            let labels = [proc, &stream.corp, &stream.cc, &stream.remote_service];
+            let [proc, corp, cc, service] = [0, 1, 2, 3].map(|i| self.admit(i, labels[i]));
+
+            for (dir, bytes, packets) in [("in", stream.bytes_recv_last, stream.packets_in_last), ("out", stream.bytes_sent_last, stream.packets_out_last)] {
+                if packets > 0 {
+                    let mut series = [proc.clone(), corp.clone(), cc.clone(), service.clone(), dir.to_string()];
+                    if !self.counters.contains_key(&series) && self.counters.len() >= self.max_series {
+                        series = [OTHER, OTHER, OTHER, OTHER, dir].map(|value| value.to_string());
+                    }
+                    let counter = self.counters.entry(series).or_default();
+                    counter.0 = counter.0.saturating_add(bytes);
+                    counter.1 = counter.1.saturating_add(packets);
+                }
+            }
+        }
+    }
+
+    fn admit(&mut self, label: usize, value: &str) -> String {
+        let seen = &mut self.seen[label];
+        if seen.contains(value) || seen.len() < self.top {
+            seen.insert(value.to_string());
+            value.to_string()
+        } else {
+            OTHER.to_string()
+        }
+    }
+
+    fn render(&self, tick: &Tick, streams: &Streams) -> String {
+        let mut ret = String::new();
+
+        for (name, help, pick) in [
+            ("pacmon_bytes_total", "Bytes seen, by process, corp, country, remote service and direction.", 0),
+            ("pacmon_packets_total", "Packets seen, by process, corp, country, remote service and direction.", 1)
+        ] {
+            ret.push_str(&format!("# HELP {} {}\n# TYPE {} counter\n", name, help, name));
+            for (series, counter) in &self.counters {
+                let labels: Vec<String> = LABELS.iter().zip(series)
+                    .map(|(label, value)| format!("{}=\"{}\"", label, escape(value)))
+                    .collect();
+                ret.push_str(&format!("{}{{{}}} {}\n", name, labels.join(","), if pick == 0 { counter.0 } else { counter.1 }));
+            }
+        }
+
+        let gauge = |ret: &mut String, name: &str, kind: &str, help: &str, value: String| {
+            ret.push_str(&format!("# HELP {} {}\n# TYPE {} {}\n{} {}\n", name, help, name, kind, name, value));
+        };
+        gauge(&mut ret, "pacmon_queue_depth", "gauge", "Most packets waiting on the capture queue during the last interval.", tick.q_depth.to_string());
+        gauge(&mut ret, "pacmon_dropped_packets_total", "counter", "Packets the capture dropped.", self.dropped.to_string());
+        gauge(&mut ret, "pacmon_redraw_seconds", "gauge", "How long the last redraw took.", tick.redraw.as_secs_f64().to_string());
+        gauge(&mut ret, "pacmon_streams", "gauge", "Streams being tracked.", streams.by_stream.len().to_string());
+
+        ret.push_str("# HELP pacmon_resolver_cache_entries Entries in each of the resolver's caches.\n# TYPE pacmon_resolver_cache_entries gauge\n");
+        for (cache, size) in &tick.caches {
+            ret.push_str(&format!("pacmon_resolver_cache_entries{{cache=\"{}\"}} {}\n", cache, size));
+        }
+
+        ret
+    }
+}
+
+impl Sink for Prometheus {
+    fn write(&mut self, tick: &Tick, streams: &Streams) -> Result<(), String> {
+        self.tally(streams);
+        self.dropped += tick.dropped;
+        let page = self.render(tick, streams);
+        *self.page.lock().map_err(|e| e.to_string())? = page;
+        self.serve()
+    }
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+// just enough http for a scraper //
+fn serve(listener: TcpListener, page: Arc<Mutex<String>>) {
+    for conn in listener.incoming() {
+        match conn {
+            Ok(conn) => if let Err(err) = respond(conn, &page) {
+                log(format!("prometheus: {}", err));
+            },
+            Err(err) => log(format!("prometheus: {}", err))
+        }
+    }
+}
+
+fn respond(mut conn: TcpStream, page: &Arc<Mutex<String>>) -> std::io::Result<()> {
+    let deadline = Instant::now() + DEADLINE;
+    let request = read_request(&mut conn, deadline)?;
+    let request = String::from_utf8_lossy(&request);
+
+    let (status, body) = match request.split_whitespace().take(2).collect::<Vec<&str>>()[..] {
+        ["GET", "/metrics"] => ("200 OK", page.lock().map(|page| page.to_string()).unwrap_or_default()),
+        ["GET", _] => ("404 Not Found", "try /metrics\n".to_string()),
+        _ => ("405 Method Not Allowed", String::new())
+    };
+
+    let response = format!("HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                           status, body.len(), body);
+    let mut buf = response.as_bytes();
+    while !buf.is_empty() {
+        conn.set_write_timeout(Some(left(deadline)?))?;
+        match conn.write(buf)? {
+            0 => return Err(ErrorKind::WriteZero.into()),
+            n => buf = &buf[n..]
+        }
+    }
+    Ok(())
+}
+
+// the request line, however slowly it comes, as long as it's in time //
+fn read_request(conn: &mut TcpStream, deadline: Instant) -> std::io::Result<Vec<u8>> {
+    let mut ret = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !ret.contains(&b'\n') {
+        if ret.len() >= MAX_REQUEST {
+            return Err(std::io::Error::new(ErrorKind::InvalidData, "request too long"));
+        }
+        conn.set_read_timeout(Some(left(deadline)?))?;
+        let want = buf.len().min(MAX_REQUEST - ret.len());
+        match conn.read(&mut buf[..want])? {
+            0 => break,
+            n => ret.extend(&buf[..n])
+        }
+    }
+    Ok(ret)
+}
+
+fn left(deadline: Instant) -> std::io::Result<Duration> {
+    match deadline.checked_duration_since(Instant::now()) {
+        Some(left) if !left.is_zero() => Ok(left),
+        _ => Err(ErrorKind::TimedOut.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+    use std::time::Duration;
+
+    use crate::pacdat::Dir;
+    use crate::pacmon::Streams;
+    use crate::sinks::{Sink, Tick};
+    use crate::sinks::prometheus::{MAX_REQUEST, Prometheus, serve};
+    use crate::sinks::tests::{pac_dat, stream};
+
+    fn tick() -> Tick {
+        Tick { ts: 0, interval: 1000, q_depth: 2, dropped: 5, redraw: Duration::from_millis(3), caches: vec![("dns", 7)] }
+    }
+
+    #[test]
+    fn test_counters() {
+        let mut streams = Streams::new();
+        for port in [443, 8443, 22] {
+            let mut stream = stream();
+            stream.remote_port = port;
+            stream.remote_service = port.to_string();
+            stream.proc = "curl".to_string();
+            if port == 22 {
+                stream.tally(&pac_dat(Dir::In, 500));
+            }
+            streams.by_stream.insert(stream.key(), stream);
+        }
+
+        let mut prometheus = Prometheus::unbound(2);
+        prometheus.write(&tick(), &streams).unwrap();
+        prometheus.write(&tick(), &streams).unwrap();
+        let page = prometheus.page.lock().unwrap().to_string();
+
+        // the same interval: the busiest gets in, then one of the other two //
+        assert!(page.contains("pacmon_bytes_total{proc=\"curl\",corp=\"?\",cc=\"?\",service=\"22\",dir=\"in\"} 4000\n"), "{}", page);
+        assert!(page.contains("pacmon_packets_total{proc=\"curl\",corp=\"?\",cc=\"?\",service=\"22\",dir=\"out\"} 2\n"), "{}", page);
+        assert!(page.contains("service=\"other\",dir=\"in\"} 3000\n"), "{}", page);
+        assert_eq!(6, page.lines().filter(|l| l.starts_with("pacmon_bytes_total")).count());
+
+        assert!(page.contains("pacmon_dropped_packets_total 10\n"));
+        assert!(page.contains("pacmon_queue_depth 2\n"));
+        assert!(page.contains("pacmon_resolver_cache_entries{cache=\"dns\"} 7\n"));
+    }
+
+    #[test]
+    fn test_serve() {
+        let mut prometheus = Prometheus::unbound(10);
+        prometheus.write(&tick(), &Streams::new()).unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let page = prometheus.page.clone();
+        thread::spawn(move || serve(listener, page));
+
+        let get = |path: &str| {
+            let mut conn = TcpStream::connect(addr).unwrap();
+            write!(conn, "GET {} HTTP/1.1\r\nHost: x\r\n\r\n", path).unwrap();
+            let mut txt = String::new();
+            conn.read_to_string(&mut txt).unwrap();
+            txt
+        };
+
+        let txt = get("/metrics");
+        assert!(txt.starts_with("HTTP/1.1 200 OK\r\n"), "{}", txt);
+        assert!(txt.contains("pacmon_streams 0\n"));
+        assert!(get("/").starts_with("HTTP/1.1 404"));
+
+        // a request line that never ends doesn't hold up the next scrape //
+        let mut endless = TcpStream::connect(addr).unwrap();
+        endless.write_all(&[b'x'; MAX_REQUEST + 1]).unwrap();
+        let mut txt = String::new();
+        let _ = endless.read_to_string(&mut txt);
+        assert!(txt.is_empty());
+        assert!(get("/metrics").starts_with("HTTP/1.1 200 OK\r\n"));
+    }
+
+    #[test]
+    fn test_max_series() {
+        let mut streams = Streams::new();
+        for port in 1..=10 {
+            let mut stream = stream();
+            stream.remote_port = port;
+            stream.remote_service = port.to_string();
+            streams.by_stream.insert(stream.key(), stream);
+        }
+
+        let mut prometheus = Prometheus::unbound(20);
+        prometheus.max_series = 4;
+        prometheus.write(&tick(), &streams).unwrap();
+        let page = prometheus.page.lock().unwrap().to_string();
+
+        assert_eq!(6, page.lines().filter(|l| l.starts_with("pacmon_bytes_total")).count(), "{}", page);
+        assert!(page.contains("pacmon_bytes_total{proc=\"other\",corp=\"other\",cc=\"other\",service=\"other\",dir=\"in\"} 12000\n"), "{}", page);
+    }
+}
@@ -0,0 +1,214 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::net::UdpSocket;
+
+use crate::pacmon::Streams;
+use crate::sinks::{Sink, Tick};
+
+// in and out bytes/sec per proc and per corp, sent over udp each interval
+// for hosts that can only push:
+//   statsd:    pacmon.proc.firefox.in:5120|g
+//   graphite:  pacmon.proc.firefox.in 5120 1700000002
+// the busiest `top` of each plus an "other" for the rest. a gauge holds
+// its last value, so anything that's gone quiet gets a 0, once //
+pub struct Push {
+    sock: UdpSocket,
+    format: Format,
+    prefix: String,
+    top: usize,
+    reported: BTreeSet<(&'static str, String)>    // kind, name
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Format {
+    Statsd,
+    Graphite
+}
+
+// comfortably under the mtu //
+const DATAGRAM: usize = 1400;
+
+impl Push {
+    pub fn new(addr: &str, format: Format, prefix: &str, top: usize) -> Result<Self, String> {
+        let err = |e: std::io::Error| format!("{:?}: {}: {}", format, addr, e);
+        let sock = UdpSocket::bind(if addr.starts_with('[') { "[::]:0" } else { "0.0.0.0:0" }).map_err(err)?;
+        sock.connect(addr).map_err(err)?;
+        Ok(Push { sock, format, prefix: prefix.to_string(), top, reported: BTreeSet::new() })
+    }
+
+    fn lines(&mut self, tick: &Tick, streams: &Streams) -> Vec<String> {
+        let mut by_proc: BTreeMap<&str, (u64, u64)> = BTreeMap::new();
+        for stream in streams.by_stream.values().filter(|s| !s.foreign) {
+            let entry = by_proc.entry(&stream.proc).or_default();
+            entry.0 = entry.0.saturating_add(stream.bytes_recv_last);
+            entry.1 = entry.1.saturating_add(stream.bytes_sent_last);
+        }
+
+        let by_corp: BTreeMap<&str, (u64, u64)> = streams.by_corp.iter()
+            .map(|(corp, stream)| (corp.as_str(), (stream.bytes_recv_last, stream.bytes_sent_last)))
+            .collect();
+
+        let secs = tick.interval.max(1) as f64 / 1000.;
+        let mut ret = Vec::new();
+        let mut reported = BTreeSet::new();
+        for (kind, totals) in [("proc", by_proc), ("corp", by_corp)] {
+            let mut rows = self.top(totals);
+            reported.extend(rows.iter().map(|(name, _)| (kind, name.to_string())));
+            for (_, name) in self.reported.iter().filter(|(was, name)| *was == kind && !reported.contains(&(kind, name.to_string()))) {
+                rows.push((name.to_string(), (0, 0)));
+            }
+
+            for (name, (recv, sent)) in rows {
+                for (dir, bytes) in [("in", recv), ("out", sent)] {
+                    let metric = format!("{}.{}.{}.{}", self.prefix, kind, clean(&name), dir);
+                    let rate = (bytes as f64 / secs).round() as u64;
+                    ret.push(match self.format {
+                        Format::Statsd => format!("{}:{}|g", metric, rate),
+                        Format::Graphite => format!("{} {} {}", metric, rate, tick.ts / 1000)
+                    });
+                }
+            }
+        }
+        self.reported = reported;
+        ret
+    }
+
+    // the busiest, then the rest rolled into one. quiet ones are left out //
+    fn top(&self, totals: BTreeMap<&str, (u64, u64)>) -> Vec<(String, (u64, u64))> {
+        let mut totals: Vec<(&str, (u64, u64))> = totals.into_iter()
+            .filter(|(_, (recv, sent))| recv + sent > 0)
+            .collect();
+        totals.sort_by_key(|(_, (recv, sent))| u64::MAX - (recv + sent));
+
+        let mut ret: Vec<(String, (u64, u64))> = totals.iter().take(self.top)
+            .map(|(name, bytes)| (name.to_string(), *bytes))
+            .collect();
+        if totals.len() > self.top {
+            let rest = totals[self.top..].iter().fold((0, 0), |acc, (_, (recv, sent))| (acc.0 + recv, acc.1 + sent));
+            ret.push(("other".to_string(), rest));
+        }
+        ret
+    }
+}
+
+impl Sink for Push {
+    fn write(&mut self, tick: &Tick, streams: &Streams) -> Result<(), String> {
+        let mut datagram = String::new();
+        for line in self.lines(tick, streams) {
+            if !datagram.is_empty() && datagram.len() + line.len() + 1 > DATAGRAM {
+                self.sock.send(datagram.as_bytes()).map_err(|e| e.to_string())?;
+                datagram.clear();
+            }
+            datagram.push_str(&line);
+            datagram.push('\n');
+        }
+
+        if !datagram.is_empty() {
+            self.sock.send(datagram.as_bytes()).map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+}
+
+// dots would make more levels and both formats split on spaces //
+fn clean(name: &str) -> String {
+    let ret: String = name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    match ret.is_empty() {
+        true => "_".to_string(),
+        false => ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::UdpSocket;
+    use std::time::Duration;
+
+    use crate::pacmon::Streams;
+    use crate::sinks::{Sink, Tick};
+    use crate::sinks::push::{clean, Format, Push};
+    use crate::sinks::tests::stream;
+
+    fn tick() -> Tick {
+        Tick { ts: 1_700_000_002_000, interval: 2000, q_depth: 0, dropped: 0, redraw: Duration::ZERO, caches: vec![] }
+    }
+
+    fn streams() -> Streams {
+        let mut streams = Streams::new();
+        for (port, proc) in [(443, "firefox"), (8443, "curl"), (22, "ssh")] {
+            let mut stream = stream();
+            stream.remote_port = port;
+            stream.proc = proc.to_string();
+            streams.by_stream.insert(stream.key(), stream);
+        }
+        let mut corp = stream();
+        corp.corp = "Cloudflare, Inc.".to_string();
+        streams.by_corp.insert(corp.corp.to_string(), corp);
+        streams
+    }
+
+    fn recv(listener: &UdpSocket) -> String {
+        let mut buf = [0u8; 2048];
+        let len = listener.recv(&mut buf).unwrap();
+        String::from_utf8_lossy(&buf[..len]).to_string()
+    }
+
+    #[test]
+    fn test_statsd() {
+        let listener = UdpSocket::bind("127.0.0.1:0").unwrap();
+        listener.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+        let mut push = Push::new(&listener.local_addr().unwrap().to_string(), Format::Statsd, "pacmon", 2).unwrap();
+        push.write(&tick(), &streams()).unwrap();
+
+        let txt = recv(&listener);
+        let lines: Vec<&str> = txt.lines().collect();
+        assert_eq!(8, lines.len(), "{}", txt);
+        assert!(lines.contains(&"pacmon.proc.other.in:750|g"), "{}", txt);
+        assert!(lines.contains(&"pacmon.proc.other.out:50|g"), "{}", txt);
+        assert!(lines.contains(&"pacmon.corp.Cloudflare__Inc_.in:750|g"), "{}", txt);
+        assert_eq!(3, lines.iter().filter(|l| l.starts_with("pacmon.proc.") && l.ends_with(".in:750|g")).count());
+    }
+
+    #[test]
+    fn test_graphite() {
+        let listener = UdpSocket::bind("127.0.0.1:0").unwrap();
+        listener.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+        let mut push = Push::new(&listener.local_addr().unwrap().to_string(), Format::Graphite, "box1.pacmon", 20).unwrap();
+        push.write(&tick(), &streams()).unwrap();
+
+        let txt = recv(&listener);
+        assert!(txt.contains("box1.pacmon.proc.ssh.out 50 1700000002\n"), "{}", txt);
+        assert!(!txt.contains("other"));
+    }
+
+    #[test]
+    fn test_quiet() {
+        let listener = UdpSocket::bind("127.0.0.1:0").unwrap();
+        listener.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+        let mut push = Push::new(&listener.local_addr().unwrap().to_string(), Format::Statsd, "pacmon", 20).unwrap();
+        let mut streams = streams();
+        push.write(&tick(), &streams).unwrap();
+        recv(&listener);
+
+        streams.by_stream.values_mut().filter(|s| s.proc == "ssh").for_each(|s| s.reset_stats());
+        push.write(&tick(), &streams).unwrap();
+        let txt = recv(&listener);
+        assert!(txt.contains("pacmon.proc.ssh.in:0|g\npacmon.proc.ssh.out:0|g\n"), "{}", txt);
+
+        push.write(&tick(), &streams).unwrap();
+        let txt = recv(&listener);
+        assert!(!txt.contains("ssh"), "{}", txt);
+    }
+
+    #[test]
+    fn test_clean() {
+        assert_eq!("firefox", clean("firefox"));
+        assert_eq!("Web_Content", clean("Web Content"));
+        assert_eq!("1_1_1_1", clean("1.1.1.1"));
+        assert_eq!("_", clean(""));
+    }
+}
@@ -0,0 +1,239 @@
+use std::fs;
+use std::path::Path;
+
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, TimeZone, Timelike};
+use rusqlite::{Connection, OpenFlags, params};
+
+use crate::etc::log;
+use crate::pacmon::Streams;
+use crate::sinks::{by_kind, Sink, Tick};
+
+// each interval's totals by proc, corp, cc and remote host in a sqlite file
+// so there's something to look back on after 'q'. rows older than
+// `downsample` are rolled up into hours and anything past `retention` goes //
+pub struct Store {
+    conn: Connection,
+    retention: i64,     // secs
+    downsample: i64,
+    tidied: i64
+}
+
+pub const KINDS: [&str; 4] = ["proc", "corp", "cc", "host"];
+
+const HOUR: i64 = 3600;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS traffic (
+    ts INTEGER NOT NULL,        -- start, unix secs
+    span INTEGER NOT NULL,      -- secs
+    kind TEXT NOT NULL,         -- proc corp cc host
+    name TEXT NOT NULL,
+    bytes_in INTEGER NOT NULL,
+    bytes_out INTEGER NOT NULL,
+    packets_in INTEGER NOT NULL,
+    packets_out INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS traffic_kind_ts ON traffic (kind, ts);";
+
+impl Store {
+    pub fn open(path: &Path, retention_days: i64, downsample_hours: i64) -> Result<Self, String> {
+        let err = |e: rusqlite::Error| format!("{}: {}", path.display(), e);
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        }
+        let conn = Connection::open(path).map_err(err)?;
+        conn.execute_batch(SCHEMA).map_err(err)?;
+        log(format!("history: {} retention:{}d downsample:{}h", path.display(), retention_days, downsample_hours));
+
+        Ok(Store { conn, retention: retention_days * 24 * HOUR, downsample: downsample_hours * HOUR, tidied: 0 })
+    }
+
+    fn record(&mut self, tick: &Tick, streams: &Streams) -> rusqlite::Result<()> {
+        let span = ((tick.interval + 500) / 1000).max(1) as i64;
+        let ts = tick.ts / 1000 - span;
+
+        let totals = by_kind(streams);
+
+        let tx = self.conn.transaction()?;
+        {
+            let mut insert = tx.prepare_cached("INSERT INTO traffic VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)")?;
+            for (kind, totals) in KINDS.iter().zip(&totals) {
+                for (name, [bytes_in, bytes_out, packets_in, packets_out]) in totals {
+                    insert.execute(params![ts, span, kind, name, *bytes_in as i64, *bytes_out as i64, *packets_in as i64, *packets_out as i64])?;
+                }
+            }
+        }
+        tx.commit()
+    }
+
+    // at most hourly. whole hours only, so an hour is never half rolled up //
+    fn tidy(&mut self, now: i64) -> rusqlite::Result<()> {
+        if now - self.tidied < HOUR {
+            return Ok(());
+        }
+        self.tidied = now;
+
+        let cutoff = (now - self.downsample) / HOUR * HOUR;
+        let tx = self.conn.transaction()?;
+        let rolled = tx.execute("INSERT INTO traffic
+            SELECT ts - ts % 3600, 3600, kind, name, SUM(bytes_in), SUM(bytes_out), SUM(packets_in), SUM(packets_out)
+            FROM traffic WHERE ts < ?1 AND span < 3600 GROUP BY ts - ts % 3600, kind, name", [cutoff])?;
+        tx.execute("DELETE FROM traffic WHERE ts < ?1 AND span < 3600", [cutoff])?;
+        let expired = tx.execute("DELETE FROM traffic WHERE ts < ?1", [now - self.retention])?;
+        tx.commit()?;
+
+        log(format!("history: rolled up into {} rows, expired {}", rolled, expired));
+        Ok(())
+    }
+}
+
+impl Sink for Store {
+    fn write(&mut self, tick: &Tick, streams: &Streams) -> Result<(), String> {
+        self.record(tick, streams)
+            .and_then(|_| self.tidy(tick.ts / 1000))
+            .map_err(|e| format!("history: {}", e))
+    }
+}
+
+// (name, bytes in, bytes out) for the busiest of a kind in [from, to), by
+// when each interval (or hour, once rolled up) started //
+pub fn top(path: &Path, kind: &str, from: i64, to: i64, limit: usize) -> Result<Vec<(String, u64, u64)>, String> {
+    let err = |e: rusqlite::Error| format!("{}: {}", path.display(), e);
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY).map_err(err)?;
+    let mut select = conn.prepare("SELECT name, SUM(bytes_in), SUM(bytes_out) FROM traffic
+        WHERE kind = ?1 AND ts >= ?2 AND ts < ?3
+        GROUP BY name ORDER BY SUM(bytes_in) + SUM(bytes_out) DESC LIMIT ?4").map_err(err)?;
+    let rows = select.query_map(params![kind, from, to, limit as i64], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)? as u64, row.get::<_, i64>(2)? as u64))
+    }).map_err(err)?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(err)
+}
+
+// unix secs for "now", "-90m", "-2h", "-3d", "14:00", "2pm", "yesterday 14:00",
+// "today", "2024-05-01" or "2024-05-01 14:30", in local time //
+pub fn parse_time(txt: &str, now: DateTime<Local>) -> Result<i64, String> {
+    let err = || format!("{}? eg now, -2h, 14:00, 2pm, yesterday 14:00 or 2024-05-01 14:00", txt);
+
+    if txt == "now" {
+        return Ok(now.timestamp());
+    }
+
+    if let Some(ago) = txt.strip_prefix('-') {
+        let secs = match ago.chars().last() {
+            Some('m') => 60,
+            Some('h') => HOUR,
+            Some('d') => 24 * HOUR,
+            _ => return Err(err())
+        };
+        let n: i64 = ago[..ago.len() - 1].parse().map_err(|_| err())?;
+        return n.checked_mul(secs).and_then(|secs| now.timestamp().checked_sub(secs)).ok_or_else(err);
+    }
+
+    let words: Vec<&str> = txt.split_whitespace().collect();
+    let (date, time) = match words[..] {
+        ["today"] => (now.date_naive(), None),
+        ["yesterday"] => (now.date_naive().pred_opt().ok_or_else(err)?, None),
+        ["today", time] => (now.date_naive(), Some(time)),
+        ["yesterday", time] => (now.date_naive().pred_opt().ok_or_else(err)?, Some(time)),
+        [date] if date.contains('-') => (NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| err())?, None),
+        [date, time] => (NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| err())?, Some(time)),
+        [time] => (now.date_naive(), Some(time)),
+        _ => return Err(err())
+    };
+
+    let time = match time {
+        Some(time) => parse_clock(time).ok_or_else(err)?,
+        None => NaiveTime::MIN
+    };
+    Local.from_local_datetime(&date.and_time(time)).earliest().map(|dt| dt.timestamp()).ok_or_else(err)
+}
+
+// 14:30, 14, 2pm, 2:30pm //
+fn parse_clock(txt: &str) -> Option<NaiveTime> {
+    let (txt, pm) = match (txt.strip_suffix("pm"), txt.strip_suffix("am")) {
+        (Some(txt), _) => (txt, Some(true)),
+        (_, Some(txt)) => (txt, Some(false)),
+        _ => (txt, None)
+    };
+    let time = match txt.contains(':') {
+        true => NaiveTime::parse_from_str(txt, "%H:%M").ok()?,
+        false => NaiveTime::from_hms_opt(txt.parse().ok()?, 0, 0)?
+    };
+    match pm {
+        Some(_) if time.hour() == 0 || time.hour() > 12 => None,
+        Some(pm) => time.with_hour(time.hour() % 12 + if pm { 12 } else { 0 }),
+        None => Some(time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::{Local, TimeZone};
+
+    use crate::pacmon::Streams;
+    use crate::sinks::{Sink, Tick};
+    use crate::sinks::tests::stream;
+    use crate::store::{parse_time, Store, top};
+
+    #[test]
+    fn test_parse_time() {
+        let now = Local.with_ymd_and_hms(2024, 5, 2, 10, 30, 0).unwrap();
+        let at = |y, m, d, h, min| Local.with_ymd_and_hms(y, m, d, h, min, 0).unwrap().timestamp();
+
+        assert_eq!(Ok(now.timestamp()), parse_time("now", now));
+        assert_eq!(Ok(now.timestamp() - 2 * 3600), parse_time("-2h", now));
+        assert_eq!(Ok(now.timestamp() - 90 * 60), parse_time("-90m", now));
+        assert_eq!(Ok(at(2024, 5, 2, 14, 0)), parse_time("14:00", now));
+        assert_eq!(Ok(at(2024, 5, 1, 14, 0)), parse_time("yesterday 2pm", now));
+        assert_eq!(Ok(at(2024, 5, 1, 15, 0)), parse_time("yesterday 15", now));
+        assert_eq!(Ok(at(2024, 5, 1, 0, 0)), parse_time("yesterday", now));
+        assert_eq!(Ok(at(2024, 4, 30, 9, 15)), parse_time("2024-04-30 9:15", now));
+        assert_eq!(Ok(at(2024, 5, 2, 0, 30)), parse_time("12:30am", now));
+        assert!(parse_time("-2w", now).is_err());
+        assert!(parse_time("13pm", now).is_err());
+        assert!(parse_time("last tuesday", now).is_err());
+        assert!(parse_time("-9999999999999999d", now).is_err());
+    }
+
+    #[test]
+    fn test_store() {
+        // and a directory for it, as on first use //
+        let dir = std::env::temp_dir().join(format!("pacmon-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("history.db");
+
+        let mut streams = Streams::new();
+        let mut active = stream();
+        active.proc = "curl".to_string();
+        active.cc = "AU".to_string();
+        active.remote_host = "one.one.one.one".to_string();
+        streams.by_corp.insert("Cloudflare".to_string(), active.clone());
+        streams.by_stream.insert(active.key(), active);
+
+        // a couple of days ago, then just now //
+        let now = 1_700_000_000;
+        let mut store = Store::open(&path, 30, 24).unwrap();
+        for ts in [now - 2 * 86400 + 10, now - 2 * 86400 + 20, now - 10] {
+            store.tidied = 0;
+            store.write(&Tick { ts: ts * 1000, interval: 10_000, q_depth: 0, dropped: 0, redraw: Duration::ZERO, caches: vec![] }, &streams).unwrap();
+        }
+
+        let count = |sql: &str| store.conn.query_row(sql, [], |row| row.get::<_, i64>(0)).unwrap();
+        assert_eq!(4, count("SELECT COUNT(*) FROM traffic WHERE span = 3600"));
+        assert_eq!(4, count("SELECT COUNT(*) FROM traffic WHERE span = 10"));
+
+        assert_eq!(vec![("Cloudflare".to_string(), 4500, 300)], top(&path, "corp", 0, now, 10).unwrap());
+        assert_eq!(vec![("curl".to_string(), 1500, 100)], top(&path, "proc", now - 3600, now, 10).unwrap());
+        assert_eq!(vec![("AU".to_string(), 3000, 200)], top(&path, "cc", 0, now - 86400, 10).unwrap());
+        assert!(top(&path, "host", 0, now - 3 * 86400, 10).unwrap().is_empty());
+
+        // retention //
+        let mut store = Store::open(&path, 1, 24).unwrap();
+        store.tidy(now).unwrap();
+        assert!(top(&path, "corp", 0, now - 86400, 10).unwrap().is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
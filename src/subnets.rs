@@ -1,6 +1,118 @@
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use std::net::IpAddr::{V4, V6};
 
+use once_cell::sync::Lazy;
+
+use crate::ipdata::trie::IpTrie;
+
+// https://www.iana.org/assignments/iana-ipv4-special-registry
+// https://www.iana.org/assignments/iana-ipv6-special-registry
+// (plus multicast which has registries of its own). the more specific
+// entries inside a block win //
+static SPECIAL_PURPOSE: &[(&str, Special)] = &[
+    ("0.0.0.0/8", Special::Unspecified),
+    ("10.0.0.0/8", Special::Private),
+    ("100.64.0.0/10", Special::Cgnat),
+    ("127.0.0.0/8", Special::Loopback),
+    ("169.254.0.0/16", Special::LinkLocal),
+    ("172.16.0.0/12", Special::Private),
+    ("192.0.0.0/24", Special::Reserved),
+    ("192.0.2.0/24", Special::Documentation),
+    ("192.31.196.0/24", Special::Anycast),
+    ("192.52.193.0/24", Special::Anycast),
+    ("192.88.99.0/24", Special::Reserved),
+    ("192.168.0.0/16", Special::Private),
+    ("192.175.48.0/24", Special::Anycast),
+    ("198.18.0.0/15", Special::Benchmarking),
+    ("198.51.100.0/24", Special::Documentation),
+    ("203.0.113.0/24", Special::Documentation),
+    ("224.0.0.0/4", Special::Multicast),
+    ("240.0.0.0/4", Special::Reserved),
+    ("255.255.255.255/32", Special::Broadcast),
+    ("::/128", Special::Unspecified),
+    ("::1/128", Special::Loopback),
+    ("64:ff9b::/96", Special::Translation),
+    ("64:ff9b:1::/48", Special::Translation),
+    ("100::/64", Special::Reserved),
+    ("100:0:0:1::/64", Special::Reserved),
+    ("2001::/23", Special::Reserved),
+    ("2001::/32", Special::Tunnel),
+    ("2001:2::/48", Special::Benchmarking),
+    ("2001:3::/32", Special::Anycast),
+    ("2001:4:112::/48", Special::Anycast),
+    ("2001:db8::/32", Special::Documentation),
+    ("2002::/16", Special::Tunnel),
+    ("2620:4f:8000::/48", Special::Anycast),
+    ("3fff::/20", Special::Documentation),
+    ("5f00::/16", Special::Reserved),
+    ("fc00::/7", Special::Ula),
+    ("fe80::/10", Special::LinkLocal),
+    ("ff00::/8", Special::Multicast),
+];
+
+static SPECIAL_TRIE: Lazy<IpTrie<Special>> = Lazy::new(|| {
+    let mut trie = IpTrie::new();
+    for (subnet, special) in SPECIAL_PURPOSE {
+        trie.insert_subnet(subnet, *special).unwrap();
+    }
+    trie
+});
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Special {
+    Unspecified,
+    Loopback,
+    Private,
+    Cgnat,
+    LinkLocal,
+    Ula,
+    Multicast,
+    Broadcast,
+    Documentation,
+    Benchmarking,
+    Anycast,        // AS112 and AMT, the same everywhere
+    Translation,    // NAT64, a v4 address inside
+    Tunnel,         // 6to4 and Teredo
+    Reserved
+}
+
+impl Special {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Special::Unspecified => "unspecified",
+            Special::Loopback => "loopback",
+            Special::Private => "private",
+            Special::Cgnat => "CGNAT",
+            Special::LinkLocal => "link-local",
+            Special::Ula => "ULA",
+            Special::Multicast => "multicast",
+            Special::Broadcast => "broadcast",
+            Special::Documentation => "documentation",
+            Special::Benchmarking => "benchmarking",
+            Special::Anycast => "anycast",
+            Special::Translation => "NAT64",
+            Special::Tunnel => "tunnel",
+            Special::Reserved => "reserved"
+        }
+    }
+
+    // no point asking dns about addresses that aren't hosts //
+    pub fn resolvable(&self) -> bool {
+        matches!(self, Special::Loopback | Special::Private | Special::Cgnat | Special::LinkLocal | Special::Ula)
+    }
+}
+
+// None for ordinary internet addresses //
+pub fn classify(addr:&IpAddr) -> Option<Special> {
+    match addr {
+        V6(v6addr) => match v6addr.to_ipv4_mapped() {
+            Some(v4addr) => SPECIAL_TRIE.lookup(&V4(v4addr)),
+            None => SPECIAL_TRIE.lookup(addr)
+        },
+        V4(_) => SPECIAL_TRIE.lookup(addr)
+    }.copied()
+}
+
 pub fn subnet(addr:&IpAddr, mask:&IpAddr) -> Option<IpAddr> {
     match addr {
         V4(v4addr) => {
@@ -71,13 +183,10 @@ fn octets_to_int(oo:&[u8]) -> u128 {
 }
 
 pub fn parse_subnet_to_int(txt:&str) -> Result<u128,String> {
+    // ::/0 is a whole 128 bit shift, which << won't do //
     fn to_mask(mask_bits:u8, mask_len:u8) -> u128 {
-        let mut ret = 0u128;
-        for _ in 0..mask_bits {
-            ret <<= 1;
-            ret += 1;
-        }
-        ret << (mask_len - mask_bits)
+        let ones = u128::MAX.checked_shr(128 - mask_bits as u32).unwrap_or(0);
+        ones.checked_shl((mask_len - mask_bits) as u32).unwrap_or(0)
     }
 
     let parts:Vec<_> = txt.split("/").collect();
@@ -92,11 +201,13 @@ pub fn parse_subnet_to_int(txt:&str) -> Result<u128,String> {
     }; 
 
     match addr_str.parse::<Ipv4Addr>() {
+        Ok(_) if mask_bits > 32 => return Err(format!("Failed to parse [{}]", txt)),
         Ok(addr) => return Ok(octets_to_int(&addr.octets()) & to_mask(mask_bits, 32)),
         Err(_) => {}
     };
 
     match addr_str.parse::<Ipv6Addr>() {
+        Ok(_) if mask_bits > 128 => return Err(format!("Failed to parse [{}]", txt)),
         Ok(addr) => return Ok(octets_to_int(&addr.octets()) & to_mask(mask_bits, 128)),
         Err(_) => {}
     };
@@ -104,6 +215,22 @@ pub fn parse_subnet_to_int(txt:&str) -> Result<u128,String> {
     Err(format!("Failed to parse [{}]", addr_str))
 }
 
+// "10.0.0.0/8" to (10.0.0.0, 255.0.0.0) as same_subnet wants them //
+pub fn parse_cidr(txt:&str) -> Result<(IpAddr, IpAddr), String> {
+    let err = || format!("Failed to parse [{}]", txt);
+    let (addr_str, bits_str) = txt.split_once('/').ok_or_else(err)?;
+    let addr = addr_str.parse::<IpAddr>().map_err(|_| err())?;
+    let width = if addr.is_ipv4() { 32 } else { 128 };
+    let bits = bits_str.parse::<u32>().ok().filter(|bits| *bits <= width).ok_or_else(err)?;
+
+    let mask = u128::MAX.checked_shl(128 - bits).unwrap_or(0);
+    let mask = match addr {
+        V4(_) => V4(Ipv4Addr::from((mask >> 96) as u32)),
+        V6(_) => V6(Ipv6Addr::from(mask))
+    };
+    Ok((subnet(&addr, &mask).unwrap(), mask))
+}
+
 #[allow(dead_code)]
 pub fn addr(txt:&str) -> IpAddr {
     match txt.parse::<Ipv4Addr>() {
@@ -116,7 +243,7 @@ pub fn addr(txt:&str) -> IpAddr {
 mod tests {
     use std::net::{IpAddr, Ipv4Addr};
     use IpAddr::V4;
-    use crate::subnets::{addr, addr_to_int, parse_subnet_to_int, subnet};
+    use crate::subnets::{addr, addr_to_int, classify, parse_cidr, parse_subnet_to_int, same_subnet, Special, subnet};
 
     #[test]
     fn test_subnet() {
@@ -135,6 +262,22 @@ mod tests {
         assert_eq!(42540766452641154071740215577757643572, parse_subnet_to_int("2001:0db8:85a3:0000:0000:8a2e:0370:7334/128").unwrap());
         assert_eq!(42540766452641154071740063647526813696, parse_subnet_to_int("2001:0db8:85a3:0000:0000:8a2e:0370:7334/64").unwrap());
         assert_eq!(42535295865117307932921825928971026432, parse_subnet_to_int("2001:0db8:85a3:0000:0000:8a2e:0370:7334/8").unwrap());
+        assert_eq!(0, parse_subnet_to_int("::/0").unwrap());
+        assert_eq!(0, parse_subnet_to_int("8.8.8.8/0").unwrap());
+        assert!(parse_subnet_to_int("8.8.8.8/33").is_err());
+    }
+
+    #[test]
+    fn test_parse_cidr() {
+        assert_eq!(Ok((addr("10.0.0.0"), addr("255.0.0.0"))), parse_cidr("10.1.2.3/8"));
+        assert_eq!(Ok((addr("0.0.0.0"), addr("0.0.0.0"))), parse_cidr("0.0.0.0/0"));
+        assert_eq!(Ok((addr("fc00::"), addr("fe00::"))), parse_cidr("fc00::/7"));
+        assert!(parse_cidr("10.0.0.0/33").is_err());
+        assert!(parse_cidr("10.0.0.0").is_err());
+
+        let (net, mask) = parse_cidr("172.16.0.0/12").unwrap();
+        assert!(same_subnet(&net, &addr("172.31.1.1"), &mask));
+        assert!(!same_subnet(&net, &addr("172.32.1.1"), &mask));
     }
 
     #[test]
@@ -145,6 +288,58 @@ mod tests {
         assert_eq!(42540766452641154071740215577757643572, addr_to_int(&addr("2001:0db8:85a3:0000:0000:8a2e:0370:7334")));
     }
 
+    #[test]
+    fn test_classify_v4() {
+        assert_eq!(None, classify(&addr("8.8.8.8")));
+        assert_eq!(None, classify(&addr("100.128.0.1")));
+        assert_eq!(None, classify(&addr("172.32.0.1")));
+        assert_eq!(Some(Special::Private), classify(&addr("10.1.2.3")));
+        assert_eq!(Some(Special::Private), classify(&addr("172.31.255.255")));
+        assert_eq!(Some(Special::Private), classify(&addr("192.168.1.1")));
+        assert_eq!(Some(Special::Cgnat), classify(&addr("100.64.0.1")));
+        assert_eq!(Some(Special::Cgnat), classify(&addr("100.127.255.254")));
+        assert_eq!(Some(Special::Loopback), classify(&addr("127.0.0.53")));
+        assert_eq!(Some(Special::LinkLocal), classify(&addr("169.254.169.254")));
+        assert_eq!(Some(Special::Multicast), classify(&addr("224.0.0.251")));
+        assert_eq!(Some(Special::Multicast), classify(&addr("239.255.255.250")));
+        assert_eq!(Some(Special::Broadcast), classify(&addr("255.255.255.255")));
+        assert_eq!(Some(Special::Reserved), classify(&addr("255.255.255.254")));
+        assert_eq!(Some(Special::Documentation), classify(&addr("203.0.113.9")));
+        assert_eq!(Some(Special::Anycast), classify(&addr("192.31.196.1")));
+        assert_eq!(Some(Special::Anycast), classify(&addr("192.52.193.1")));
+        assert_eq!(Some(Special::Anycast), classify(&addr("192.175.48.6")));
+        assert_eq!(Some(Special::Reserved), classify(&addr("192.88.99.1")));
+        assert_eq!(None, classify(&addr("192.175.49.1")));
+    }
+
+    #[test]
+    fn test_classify_v6() {
+        assert_eq!(None, classify(&addr("2606:4700::1111")));
+        assert_eq!(Some(Special::Loopback), classify(&addr("::1")));
+        assert_eq!(Some(Special::Unspecified), classify(&addr("::")));
+        assert_eq!(Some(Special::LinkLocal), classify(&addr("fe80::2d56:de1f:eb7a:1140")));
+        assert_eq!(Some(Special::Ula), classify(&addr("fd12:3456::1")));
+        assert_eq!(Some(Special::Multicast), classify(&addr("ff02::fb")));
+        assert_eq!(Some(Special::Documentation), classify(&addr("2001:db8::1")));
+        assert_eq!(Some(Special::Documentation), classify(&addr("3fff:fff::1")));
+        assert_eq!(Some(Special::Translation), classify(&addr("64:ff9b::808:808")));
+        assert_eq!(Some(Special::Translation), classify(&addr("64:ff9b:1::1")));
+        assert_eq!(Some(Special::Tunnel), classify(&addr("2001:0:4136:e378::1")));
+        assert_eq!(Some(Special::Tunnel), classify(&addr("2002:c000:204::1")));
+        assert_eq!(Some(Special::Reserved), classify(&addr("2001:1::1")));
+        assert_eq!(Some(Special::Reserved), classify(&addr("2001:1ff::1")));
+        assert_eq!(Some(Special::Benchmarking), classify(&addr("2001:2::1")));
+        assert_eq!(Some(Special::Anycast), classify(&addr("2001:3::1")));
+        assert_eq!(Some(Special::Anycast), classify(&addr("2001:4:112::1")));
+        assert_eq!(Some(Special::Anycast), classify(&addr("2620:4f:8000::1")));
+        assert_eq!(Some(Special::Reserved), classify(&addr("100:0:0:1::1")));
+        assert_eq!(Some(Special::Reserved), classify(&addr("5f00::1")));
+        assert_eq!(None, classify(&addr("2001:200::1")));
+        assert_eq!(None, classify(&addr("2003::1")));
+        assert_eq!(Some(Special::Private), classify(&addr("::ffff:192.168.0.1")));
+        assert_eq!(None, classify(&addr("::ffff:8.8.8.8")));
+    }
+
 }
 
 
@@ -0,0 +1,342 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{stdout, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use chrono::{DateTime, Local, TimeZone};
+use serde_json::{json, Value};
+
+use crate::config::Config;
+use crate::etc;
+use crate::pacdat::StreamKey;
+use crate::pacmon::Streams;
+use crate::pacstream::PacStream;
+use crate::sinks::{by_kind, fmt_ts, Sink, Tick};
+
+// what the session added up to, written on the way out: how long, how much,
+// the busiest procs, streams, corps, countries and hosts, the peak rate and
+// when, how many connections and how many packets were dropped. fed each
+// interval like a sink, so streams that have since gone still count //
+pub struct Summary {
+    out: Box<dyn Write>,
+    format: Format,
+    top: usize,
+    start: i64,
+    recv: u64,
+    sent: u64,
+    peak: (u64, i64),           // bytes/sec, end of the interval
+    dropped: u64,
+    totals: [BTreeMap<String, (u64, u64)>; 4],
+    streams: BTreeMap<StreamKey, (String, u64, u64)>
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Format {
+    Text,
+    Json,
+    Html
+}
+
+// name, bytes in, bytes out //
+type Row = (String, u64, u64);
+
+const TABLES: [&str; 5] = ["procs", "streams", "corps", "countries", "hosts"];
+
+static STOPPED: AtomicBool = AtomicBool::new(false);
+
+impl Summary {
+    pub fn from_config(config: &Config) -> Result<Option<Self>, String> {
+        let Some(target) = config.get_str("summary") else {
+            return Ok(None);
+        };
+
+        // the file's extension will do if not told //
+        let format = match config.get_str("summary_format").as_deref() {
+            Some("text") => Format::Text,
+            Some("json") => Format::Json,
+            Some("html") => Format::Html,
+            Some(other) => return Err(format!("summary_format: {}? text, json or html", other)),
+            None if target.ends_with(".json") => Format::Json,
+            None if target.ends_with(".html") || target.ends_with(".htm") => Format::Html,
+            None => Format::Text
+        };
+        // opened now, while we may still be root //
+        let out: Box<dyn Write> = match config.get_path("summary") {
+            Some(path) if target != "-" => Box::new(File::create(&path).map_err(|e| format!("summary: {}: {}", path.display(), e))?),
+            _ => Box::new(stdout())
+        };
+        let top = config.get_int("summary_top").filter(|n| *n > 0).unwrap_or(10) as usize;
+
+        Ok(Some(Summary::new(out, format, top, etc::millitime())))
+    }
+
+    fn new(out: Box<dyn Write>, format: Format, top: usize, start: i64) -> Self {
+        Summary {
+            out,
+            format,
+            top,
+            start,
+            recv: 0,
+            sent: 0,
+            peak: (0, start),
+            dropped: 0,
+            totals: Default::default(),
+            streams: BTreeMap::new()
+        }
+    }
+
+    pub fn report(&mut self, end: i64) -> Result<(), String> {
+        let txt = match self.format {
+            Format::Text => self.text(end),
+            Format::Json => format!("{:#}\n", self.json(end)),
+            Format::Html => self.html(end)
+        };
+
+        self.out.write_all(txt.as_bytes()).and_then(|_| self.out.flush()).map_err(|e| e.to_string())
+    }
+
+    // (title, [(name, in, out)]) the busiest first //
+    fn tables(&self) -> Vec<(&'static str, Vec<Row>)> {
+        let streams = self.streams.values().map(|(name, recv, sent)| (name.as_str(), (*recv, *sent)));
+        let [procs, corps, countries, hosts] = self.totals.each_ref()
+            .map(|totals| totals.iter().map(|(name, (recv, sent))| (name.as_str(), (*recv, *sent))).collect::<Vec<_>>());
+
+        TABLES.iter().zip([procs, streams.collect(), corps, countries, hosts])
+            .map(|(title, mut rows)| {
+                rows.sort_by(|(name1, (recv1, sent1)), (name2, (recv2, sent2))|
+                    recv2.saturating_add(*sent2).cmp(&recv1.saturating_add(*sent1)).then(name1.cmp(name2)));
+                (*title, rows.into_iter().take(self.top).map(|(name, (recv, sent))| (name.to_string(), recv, sent)).collect())
+            })
+            .collect()
+    }
+
+    fn text(&self, end: i64) -> String {
+        let mut ret = format!("{} - {} ({})\n", local(self.start), local(end), span(end - self.start));
+        ret.push_str(&format!("in {}  out {}  peak {}/s at {}\n", etc::mag_fmt(self.recv), etc::mag_fmt(self.sent),
+                              etc::mag_fmt(self.peak.0), local(self.peak.1)));
+        ret.push_str(&format!("{} connections, {} dropped\n", self.streams.len(), self.dropped));
+
+        for (title, rows) in self.tables() {
+            let width = rows.iter().map(|(name, _, _)| name.chars().count()).chain([title.len()]).max().unwrap();
+            ret.push_str(&format!("\n{:<width$}  {:>6}  {:>6}  {:>6}\n", title.to_uppercase(), "IN", "OUT", "TOTAL", width = width));
+            for (name, recv, sent) in rows {
+                ret.push_str(&format!("{:<width$}  {:>6}  {:>6}  {:>6}\n", name, etc::mag_fmt(recv), etc::mag_fmt(sent),
+                                      etc::mag_fmt(recv.saturating_add(sent)), width = width));
+            }
+        }
+        ret
+    }
+
+    fn json(&self, end: i64) -> Value {
+        let mut ret = json!({
+            "start": fmt_ts(DateTime::from_timestamp_millis(self.start).unwrap_or_default()),
+            "end": fmt_ts(DateTime::from_timestamp_millis(end).unwrap_or_default()),
+            "duration_secs": (end - self.start) / 1000,
+            "bytes_in": self.recv,
+            "bytes_out": self.sent,
+            "peak": {
+                "bytes_per_sec": self.peak.0,
+                "ts": fmt_ts(DateTime::from_timestamp_millis(self.peak.1).unwrap_or_default())
+            },
+            "connections": self.streams.len(),
+            "dropped": self.dropped
+        });
+        for (title, rows) in self.tables() {
+            ret[title] = rows.into_iter()
+                .map(|(name, recv, sent)| json!({ "name": name, "bytes_in": recv, "bytes_out": sent }))
+                .collect();
+        }
+        ret
+    }
+
+    // one self contained page, fit for mailing //
+    fn html(&self, end: i64) -> String {
+        let mut ret = String::from("<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>pacmon</title>\n\
+            <style>body{font-family:monospace} table{border-collapse:collapse;margin-bottom:1em} \
+            th,td{padding:0 1em} td.n{text-align:right}</style></head><body>\n");
+        ret.push_str(&format!("<h1>pacmon {} - {}</h1>\n<table>\n", local(self.start), local(end)));
+        for (label, value) in [
+            ("duration", span(end - self.start)),
+            ("in", etc::mag_fmt(self.recv)),
+            ("out", etc::mag_fmt(self.sent)),
+            ("peak", format!("{}/s at {}", etc::mag_fmt(self.peak.0), local(self.peak.1))),
+            ("connections", self.streams.len().to_string()),
+            ("dropped", self.dropped.to_string())
+        ] {
+            ret.push_str(&format!("<tr><th>{}</th><td>{}</td></tr>\n", label, value));
+        }
+        ret.push_str("</table>\n");
+
+        for (title, rows) in self.tables() {
+            ret.push_str(&format!("<h2>{}</h2>\n<table>\n<tr><th>name</th><th>in</th><th>out</th><th>total</th></tr>\n", title));
+            for (name, recv, sent) in rows {
+                ret.push_str(&format!("<tr><td>{}</td><td class=\"n\">{}</td><td class=\"n\">{}</td><td class=\"n\">{}</td></tr>\n",
+                                      escape(&name), etc::mag_fmt(recv), etc::mag_fmt(sent), etc::mag_fmt(recv.saturating_add(sent))));
+            }
+            ret.push_str("</table>\n");
+        }
+        ret.push_str("</body></html>\n");
+        ret
+    }
+}
+
+impl Sink for Summary {
+    fn write(&mut self, tick: &Tick, streams: &Streams) -> Result<(), String> {
+        for (totals, interval) in self.totals.iter_mut().zip(by_kind(streams)) {
+            for (name, [recv, sent, _, _]) in interval {
+                let entry = totals.entry(name).or_default();
+                entry.0 = entry.0.saturating_add(recv);
+                entry.1 = entry.1.saturating_add(sent);
+            }
+        }
+
+        let (mut recv, mut sent) = (0u64, 0u64);
+        let active = |stream: &&PacStream| stream.packets_in_last > 0 || stream.packets_out_last > 0;
+        for stream in streams.by_stream.values().filter(active) {
+            // named as it is now, it may have resolved since. one we only
+            // see in passing goes by its local host //
+            let local = match stream.foreign {
+                true => format!("{}:{}", stream.local_host, stream.local_port),
+                false => format!("{} :{}", stream.proc, stream.local_port)
+            };
+            let entry = self.streams.entry(stream.key()).or_default();
+            entry.0 = format!("{} > {}:{}", local, stream.remote_host, stream.remote_service);
+            entry.1 = entry.1.saturating_add(stream.bytes_recv_last);
+            entry.2 = entry.2.saturating_add(stream.bytes_sent_last);
+            recv = recv.saturating_add(stream.bytes_recv_last);
+            sent = sent.saturating_add(stream.bytes_sent_last);
+        }
+        self.recv = self.recv.saturating_add(recv);
+        self.sent = self.sent.saturating_add(sent);
+
+        // not from the sliver of an interval before quitting //
+        let rate = recv.saturating_add(sent).saturating_mul(1000) / tick.interval.max(1);
+        if rate > self.peak.0 && tick.interval >= 200 {
+            self.peak = (rate, tick.ts);
+        }
+        self.dropped += tick.dropped;
+        Ok(())
+    }
+}
+
+// so ^C or a kill still gets a summary //
+pub fn catch_signals() {
+    extern "C" fn stop(_: libc::c_int) {
+        STOPPED.store(true, Ordering::Relaxed);
+    }
+
+    let handler = stop as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    unsafe {
+        libc::signal(libc::SIGINT, handler);
+        libc::signal(libc::SIGTERM, handler);
+    }
+}
+
+pub fn stopped() -> bool {
+    STOPPED.load(Ordering::Relaxed)
+}
+
+fn local(ts: i64) -> String {
+    Local.timestamp_millis_opt(ts).unwrap().format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+fn span(millis: i64) -> String {
+    let secs = millis.max(0) / 1000;
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m{:02}s", secs / 60, secs % 60),
+        _ => format!("{}h{:02}m{:02}s", secs / 3600, secs / 60 % 60, secs % 60)
+    }
+}
+
+fn escape(txt: &str) -> String {
+    txt.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::pacmon::Streams;
+    use crate::sinks::{Sink, Tick};
+    use crate::sinks::tests::{Shared, stream};
+    use crate::summary::{Format, span, Summary};
+
+    fn tick(ts: i64, interval: u64) -> Tick {
+        Tick { ts, interval, q_depth: 0, dropped: 3, redraw: Duration::ZERO, caches: vec![] }
+    }
+
+    #[test]
+    fn test_summary() {
+        let mut streams = Streams::new();
+        for (port, proc) in [(443, "firefox"), (8443, "firefox"), (22, "ssh")] {
+            let mut stream = stream();
+            stream.remote_port = port;
+            stream.remote_host = "example.com".to_string();
+            stream.remote_service = port.to_string();
+            stream.proc = proc.to_string();
+            stream.cc = "NL".to_string();
+            streams.by_stream.insert(stream.key(), stream);
+        }
+        let mut corp = stream();
+        corp.corp = "Cloudflare, Inc.".to_string();
+        streams.by_corp.insert(corp.corp.to_string(), corp);
+
+        let start = 1_700_000_000_000;
+        let mut summary = Summary::new(Box::new(Shared::default()), Format::Text, 1, start);
+        summary.write(&tick(start + 1000, 1000), &streams).unwrap();
+        summary.write(&tick(start + 3000, 2000), &streams).unwrap();
+
+        assert_eq!((9000, 600), (summary.recv, summary.sent));
+        assert_eq!((4800, start + 1000), summary.peak);
+        assert_eq!(6, summary.dropped);
+
+        let tables = summary.tables();
+        assert_eq!(("procs", vec![("firefox".to_string(), 6000, 400)]), tables[0]);
+        assert_eq!(("streams", vec![("firefox :50000 > example.com:443".to_string(), 3000, 200)]), tables[1]);
+        assert_eq!("Cloudflare, Inc.", tables[2].1[0].0);
+
+        let txt = summary.text(start + 3000);
+        assert!(txt.contains("(3s)\nin 9.0k  out 600b  peak 4.8k/s at "), "{}", txt);
+        assert!(txt.contains("\n3 connections, 6 dropped\n"), "{}", txt);
+        assert!(txt.contains("\nCOUNTRIES      IN     OUT   TOTAL\nNL           9.0k    600b     10k\n"), "{}", txt);
+
+        let json = summary.json(start + 3000);
+        assert_eq!(3, json["connections"]);
+        assert_eq!(4800, json["peak"]["bytes_per_sec"]);
+        assert_eq!("firefox", json["procs"][0]["name"]);
+        assert_eq!(1, json["hosts"].as_array().unwrap().len());
+
+        let html = summary.html(start + 3000);
+        assert!(html.contains("<h2>corps</h2>"));
+        assert!(html.contains("<td>firefox :50000 &gt; example.com:443</td>"), "{}", html);
+    }
+
+    // counts from a flow exporter can be anything //
+    #[test]
+    fn test_huge() {
+        let mut streams = Streams::new();
+        for port in [443, 8443] {
+            let mut stream = stream();
+            stream.remote_port = port;
+            stream.bytes_recv_last = u64::MAX;
+            stream.bytes_sent_last = u64::MAX;
+            streams.by_stream.insert(stream.key(), stream);
+        }
+        let mut summary = Summary::new(Box::new(Shared::default()), Format::Text, 1, 0);
+        summary.write(&tick(1000, 1000), &streams).unwrap();
+        summary.write(&tick(2000, 1000), &streams).unwrap();
+
+        assert_eq!((u64::MAX, u64::MAX), (summary.recv, summary.sent));
+        assert_eq!((u64::MAX / 1000, 1000), summary.peak);
+        assert_eq!(u64::MAX, summary.tables()[0].1[0].1);
+        assert!(summary.text(2000).contains("\nin "));
+    }
+
+    #[test]
+    fn test_span() {
+        assert_eq!("0s", span(999));
+        assert_eq!("2m05s", span(125_000));
+        assert_eq!("1h01m01s", span(3_661_000));
+    }
+}
@@ -0,0 +1,104 @@
+// longest a dns name can be //
+const MAX_NAME: usize = 253;
+
+// pulls the server name out of a tls client hello, if that's what the
+// payload starts with. only looks at the first segment - a hello split
+// over several (big post-quantum key shares) comes back None. it's whatever
+// the other end says, so anything but a plausible host name is too //
+//
+//   record:    type(1)=0x16 version(2) length(2)
+//   handshake: type(1)=0x01 length(3) version(2) random(32)
+//              session_id(1+n) cipher_suites(2+n) compression(1+n) extensions(2+n)
+//   extension: type(2) length(2) data - server_name is type 0:
+//              list_length(2) name_type(1)=0 name_length(2) name
+pub fn sni(payload: &[u8]) -> Option<String> {
+    if payload.len() < 6 || payload[0] != 0x16 || payload[1] != 0x03 || payload[5] != 0x01 {
+        return None;
+    }
+
+    let mut pos = 5 + 4 + 2 + 32;
+    pos += 1 + *payload.get(pos)? as usize;       // session id
+    pos += 2 + be16(payload, pos)?;               // cipher suites
+    pos += 1 + *payload.get(pos)? as usize;       // compression methods
+
+    let end = (pos + 2 + be16(payload, pos)?).min(payload.len());
+    pos += 2;
+
+    while pos + 4 <= end {
+        let ext_type = be16(payload, pos)?;
+        let ext_len = be16(payload, pos + 2)?;
+        pos += 4;
+        if ext_type == 0 {
+            // first entry of the list, which in practice is the only one //
+            let name_len = be16(payload, pos + 3)?;
+            let name = payload.get(pos + 5..pos + 5 + name_len)?;
+            let host = |c: &u8| c.is_ascii_alphanumeric() || b".-_".contains(c);
+            return match payload.get(pos + 2) {
+                Some(0) if !name.is_empty() && name.len() <= MAX_NAME && name.iter().all(host) => {
+                    Some(String::from_utf8_lossy(name).to_lowercase())
+                }
+                _ => None
+            };
+        }
+        pos += ext_len;
+    }
+
+    None
+}
+
+fn be16(buf: &[u8], pos: usize) -> Option<usize> {
+    Some(((*buf.get(pos)? as usize) << 8) | *buf.get(pos + 1)? as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tls::sni;
+
+    fn client_hello(name: &str) -> Vec<u8> {
+        let mut ext = vec![0x00, 0x0b, 0x00, 0x02, 0x01, 0x00];     // ec_point_formats first
+        let list_len = name.len() + 3;
+        ext.extend([0x00, 0x00]);
+        ext.extend((list_len as u16 + 2).to_be_bytes());
+        ext.extend((list_len as u16).to_be_bytes());
+        ext.push(0x00);
+        ext.extend((name.len() as u16).to_be_bytes());
+        ext.extend(name.as_bytes());
+
+        let mut body = vec![0x03, 0x03];
+        body.extend([0xab; 32]);
+        body.extend([0x20]);
+        body.extend([0xcd; 32]);
+        body.extend([0x00, 0x04, 0x13, 0x01, 0x13, 0x02]);
+        body.extend([0x01, 0x00]);
+        body.extend((ext.len() as u16).to_be_bytes());
+        body.extend(ext);
+
+        let mut hs = vec![0x01, 0x00];
+        hs.extend((body.len() as u16).to_be_bytes());
+        hs.extend(body);
+
+        let mut record = vec![0x16, 0x03, 0x01];
+        record.extend((hs.len() as u16).to_be_bytes());
+        record.extend(hs);
+        record
+    }
+
+    #[test]
+    fn test_sni() {
+        assert_eq!(Some("github.com".to_string()), sni(&client_hello("GitHub.com")));
+        assert_eq!(None, sni(&client_hello("")));
+        assert_eq!(None, sni(b"GET / HTTP/1.1\r\n"));
+        assert_eq!(None, sni(&[]));
+        assert_eq!(Some("a-b_c.example".to_string()), sni(&client_hello("a-b_c.example")));
+        for name in ["%s%n.example", "a\0b", "münchen.de", "a b", &"a".repeat(254)] {
+            assert_eq!(None, sni(&client_hello(name)), "{}", name);
+        }
+        assert!(sni(&client_hello(&"a".repeat(253))).is_some());
+
+        // cut short anywhere //
+        let hello = client_hello("example.org");
+        for len in 0..hello.len() {
+            assert_eq!(None, sni(&hello[..len]));
+        }
+    }
+}
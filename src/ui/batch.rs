@@ -0,0 +1,145 @@
+use std::io::{stdout, Write};
+
+use chrono::Local;
+
+use crate::config::Config;
+use crate::etc::millitime;
+use crate::pacmon::Streams;
+use crate::ui::{Cell, compute_widths, corp_mode, normal_mode, spark, UI};
+use crate::ui::Justify::{LHS, RHS};
+use crate::ui::spark::SPARK_LEN;
+
+// like top -b: the normal (or corp) mode table as plain text on stdout every
+// interval, for scripts, cron and dumb terminals. stops after `count`
+// tables or `duration` seconds, whichever comes first. also how we run with
+// json or csv going to stdout, or headless, just without the tables //
+pub struct Batch {
+    tables: bool,
+    count: Option<i64>,
+    until: Option<i64>,
+    width: Option<i32>,    // none for no limit
+    rows: Option<usize>,
+    printed: i64
+}
+
+impl Batch {
+    pub fn new(config: &Config) -> Option<Self> {
+        let tables = config.get_bool("batch") == Some(true);
+        let stdout = |key| config.get_str(key).as_deref() == Some("-");
+        let headless = config.get_bool("headless") == Some(true);
+        if !tables && !headless && !stdout("json") && !stdout("csv") {
+            return None;
+        }
+
+        Some(Batch {
+            tables: tables && !headless,
+            count: config.get_int("count").filter(|n| *n > 0),
+            until: config.get_int("duration").filter(|secs| *secs > 0).map(|secs| millitime() + secs * 1000),
+            width: config.get_int("width").filter(|cols| *cols > 0).map(|cols| cols as i32),
+            rows: config.get_int("rows").filter(|n| *n > 0).map(|n| n as usize),
+            printed: 0
+        })
+    }
+
+    // the first interval starts now rather than at the epoch //
+    pub fn start(&self, ui: &mut UI) {
+        ui.last_draw = millitime();
+    }
+
+    pub fn due(&self, ui: &UI) -> bool {
+        let now = millitime();
+        now - ui.last_draw >= ui.redraw_interval || self.until.is_some_and(|until| now >= until)
+    }
+
+    pub fn done(&self) -> bool {
+        self.count.is_some_and(|count| self.printed >= count) || self.until.is_some_and(|until| millitime() >= until)
+    }
+
+    pub fn print(&mut self, ui: &mut UI, streams: &mut Streams, q_depth: u64, dropped: u64) {
+        let (pac_vec, interval) = ui.prepare(streams);
+        ui.last_draw = millitime();
+        self.printed += 1;
+
+        if !self.tables {
+            return;
+        }
+
+        let history = streams.history.rates(SPARK_LEN);
+        let rows = 0..self.rows.unwrap_or(pac_vec.len()).min(pac_vec.len());
+
+        let (matrix, flex) = match ui.corp_mode {
+            true => (corp_mode::render(ui, &pac_vec, &history, interval, rows), vec![]),
+            false => normal_mode::render(ui, &pac_vec, &history, interval, rows, self.width.unwrap_or(i32::MAX))
+        };
+
+        let mut widths = compute_widths(&matrix, &vec![]);
+        if let Some(cols) = self.width {
+            normal_mode::hack_widths(&mut widths, &flex, cols);
+        }
+
+        let mut out = format!("--- {} interval:{}ms streams:{} q:{} drop'd:{}\n",
+                              Local::now().format("%Y-%m-%d %H:%M:%S"), interval, pac_vec.len(), q_depth, dropped);
+        out.push_str(&format_matrix(&matrix, &widths, self.width));
+        out.push('\n');
+
+        // stdout going away (| head) is as good as done //
+        let mut stdout = stdout().lock();
+        if stdout.write_all(out.as_bytes()).and_then(|_| stdout.flush()).is_err() {
+            std::process::exit(0);
+        }
+    }
+}
+
+// print_matrix without curses. cells too wide for their column lose their
+// left end as on screen, and lines stop at the width if there is one //
+fn format_matrix(matrix: &[Vec<Cell>], widths: &[i16], width: Option<i32>) -> String {
+    let mut ret = String::new();
+    for row in matrix {
+        let mut line = String::new();
+        for (cell, width) in row.iter().zip(widths) {
+            let width = (*width).max(0) as usize;
+            let txt: Vec<char> = match &cell.spark {
+                Some(levels) => spark::text(levels),
+                None => cell.txt.replace("%%", "%")
+            }.chars().collect();
+
+            let txt: String = match txt.len() > width {
+                true if width > 0 => "#".chars().chain(txt[txt.len() - width + 1..].iter().cloned()).collect(),
+                true => String::new(),
+                false => txt.iter().collect()
+            };
+
+            match cell.justify {
+                LHS => line.push_str(&format!("{:<width$}", txt, width = width)),
+                RHS => line.push_str(&format!("{:>width$}", txt, width = width))
+            }
+        }
+
+        let mut line: String = match width {
+            Some(cols) => line.chars().take(cols as usize).collect(),
+            None => line
+        };
+        line.truncate(line.trim_end().len());
+        ret.push_str(&line);
+        ret.push('\n');
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ui::batch::format_matrix;
+    use crate::ui::Cell;
+    use crate::ui::Justify::{LHS, RHS};
+
+    #[test]
+    fn test_format_matrix() {
+        let matrix = vec![
+            vec![Cell::new(LHS, "HOST"), Cell::new(RHS, " "), Cell::new(RHS, "IN%%")],
+            vec![Cell::new(LHS, "example.com"), Cell::new(RHS, " "), Cell::new(RHS, "5%%")]
+        ];
+        assert_eq!("HOST        IN%\nexample.com  5%\n", format_matrix(&matrix, &[11, 1, 3], None));
+        assert_eq!("HOST    IN%\n#le.com  5%\n", format_matrix(&matrix, &[7, 1, 3], None));
+        assert_eq!("HOST\n#le.co\n", format_matrix(&matrix, &[7, 1, 3], Some(6)));
+    }
+}
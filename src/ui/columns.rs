@@ -0,0 +1,257 @@
+use std::fs::read_to_string;
+use std::path::Path;
+
+use ncurses::*;
+
+use crate::etc::log;
+use crate::ui::pad;
+
+// the columns normal mode can show. pick them in ~/.config/pacmon/columns,
+// in the order wanted, eg "proc pid remote rate total age cc city corp sni",
+// or with 'C' on the fly //
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Column {
+    Local,      // <proc> or host for traffic we only see in passing, with the port
+    Proc,
+    Pid,
+    User,
+    Remote,
+    Proto,
+    In,         // pct, rate and total in
+    Out,
+    Pct,        // in and out side by side
+    Rate,
+    Total,
+    Packets,
+    History,
+    Age,
+    Cc,
+    City,
+    Corp,
+    Asn,
+    Sni,
+    Cloud,      // only shown if there's something to show
+    List
+}
+
+use Column::*;
+
+pub const ALL: [Column; 21] = [
+    Local, Proc, Pid, User, Remote, Proto, In, Out, Pct, Rate, Total, Packets,
+    History, Age, Cc, City, Corp, Asn, Sni, Cloud, List
+];
+
+pub const DEFAULT: [Column; 10] = [Local, Remote, In, Out, History, Age, Cc, Corp, Cloud, List];
+
+impl Column {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Local => "local",
+            Proc => "proc",
+            Pid => "pid",
+            User => "user",
+            Remote => "remote",
+            Proto => "proto",
+            In => "in",
+            Out => "out",
+            Pct => "pct",
+            Rate => "rate",
+            Total => "total",
+            Packets => "packets",
+            History => "history",
+            Age => "age",
+            Cc => "cc",
+            City => "city",
+            Corp => "corp",
+            Asn => "asn",
+            Sni => "sni",
+            Cloud => "cloud",
+            List => "list"
+        }
+    }
+
+    pub fn parse(txt: &str) -> Option<Column> {
+        ALL.iter().find(|col| col.name().eq_ignore_ascii_case(txt)).copied()
+    }
+
+    // host names soak up whatever width is left over //
+    pub fn flex(&self) -> Option<f32> {
+        match self {
+            Local => Some(0.45),
+            Remote => Some(0.55),
+            _ => None
+        }
+    }
+}
+
+// whitespace or comma separated, # for comments //
+pub fn parse_list(txt: &str) -> Result<Vec<Column>, String> {
+    let mut ret = Vec::new();
+    for word in txt.lines().flat_map(|l| l.split('#').next().unwrap().split([' ', '\t', ','])).filter(|w| !w.is_empty()) {
+        match Column::parse(word) {
+            Some(col) if ret.contains(&col) => return Err(format!("[{}] listed twice", word)),
+            Some(col) => ret.push(col),
+            None => return Err(format!("unknown column [{}]", word))
+        }
+    }
+    match ret.is_empty() {
+        true => Err("no columns".to_string()),
+        false => Ok(ret)
+    }
+}
+
+pub fn load(path: &Path) -> Vec<Column> {
+    match read_to_string(path).map_err(|e| e.to_string()).and_then(|txt| parse_list(&txt)) {
+        Ok(cols) => {
+            log(format!("columns: {:?} from {}", cols, path.display()));
+            cols
+        }
+        Err(msg) => {
+            eprintln!("{}: {} - using the defaults", path.display(), msg);
+            DEFAULT.to_vec()
+        }
+    }
+}
+
+// the shown columns first in order, then the rest //
+struct Picker {
+    items: Vec<(Column, bool)>,
+    pos: usize
+}
+
+impl Picker {
+    fn new(cols: &[Column]) -> Self {
+        let mut items: Vec<(Column, bool)> = cols.iter().map(|col| (*col, true)).collect();
+        items.extend(ALL.iter().filter(|col| !cols.contains(col)).map(|col| (*col, false)));
+        Picker { items, pos: 0 }
+    }
+
+    fn up(&mut self) {
+        self.pos = self.pos.saturating_sub(1);
+    }
+
+    fn down(&mut self) {
+        self.pos = (self.pos + 1).min(self.items.len() - 1);
+    }
+
+    // show/hide the item under the cursor //
+    fn toggle(&mut self) {
+        self.items[self.pos].1 = !self.items[self.pos].1;
+    }
+
+    // drags the item under the cursor along //
+    fn shift(&mut self, up: bool) {
+        let to = match up {
+            true => self.pos.checked_sub(1),
+            false => Some(self.pos + 1).filter(|to| *to < self.items.len())
+        };
+        if let Some(to) = to {
+            self.items.swap(self.pos, to);
+            self.pos = to;
+        }
+    }
+
+    fn columns(&self) -> Vec<Column> {
+        self.items.iter().filter(|(_, on)| *on).map(|(col, _)| *col).collect()
+    }
+}
+
+// modal, like the filter prompt. None if cancelled //
+pub fn pick(cols: &[Column]) -> Option<Vec<Column>> {
+    let mut picker = Picker::new(cols);
+    let ret;
+
+    nodelay(stdscr(), false);
+
+    loop {
+        clear();
+        attron(A_BOLD());
+        mvprintw(0, 2, "columns");
+        attroff(A_BOLD());
+        for (i, (col, on)) in picker.items.iter().enumerate() {
+            let y = i as i32 + 2;
+            if y >= LINES() - 1 {
+                break;
+            }
+            if i == picker.pos {
+                attron(A_REVERSE());
+            }
+            mvprintw(y, 2, &format!("[{}] {:<10}", if *on { 'x' } else { ' ' }, col.name()));
+            attroff(A_REVERSE());
+        }
+        let help = " up/down: move   space: show/hide   u/d: shift up/down   r: defaults   enter: done   esc: cancel";
+        attron(A_REVERSE());
+        mvprintw(LINES() - 1, 0, help);
+        pad(COLS() - help.len() as i32);
+        attroff(A_REVERSE());
+        refresh();
+
+        match getch() {
+            KEY_UP | 107 => picker.up(),        // k
+            KEY_DOWN | 106 => picker.down(),    // j
+            32 => picker.toggle(),
+            117 => picker.shift(true),          // u
+            100 => picker.shift(false),         // d
+            114 => picker = Picker::new(&DEFAULT),
+            KEY_ENTER | 10 | 13 => {
+                ret = Some(picker.columns()).filter(|cols| !cols.is_empty());
+                break;
+            }
+            27 => {
+                ret = None;
+                break;
+            }
+            _ => {}
+        }
+    }
+
+    nodelay(stdscr(), true);
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ui::columns::{ALL, Column, DEFAULT, parse_list, Picker};
+    use crate::ui::columns::Column::{Age, Cc, Pid, Proc, Remote};
+
+    #[test]
+    fn test_names() {
+        for col in ALL {
+            assert_eq!(Some(col), Column::parse(col.name()));
+        }
+        assert_eq!(ALL.to_vec(), parse_list(&ALL.map(|c| c.name()).join(" ")).unwrap());
+    }
+
+    #[test]
+    fn test_parse_list() {
+        assert_eq!(vec![Proc, Pid, Remote], parse_list("proc, PID\n# comment\nremote  # the far end\n").unwrap());
+        assert!(parse_list("proc nosuch").is_err());
+        assert!(parse_list("proc proc").is_err());
+        assert!(parse_list("# nothing").is_err());
+    }
+
+    #[test]
+    fn test_picker() {
+        let mut picker = Picker::new(&[Cc, Age]);
+        assert_eq!(ALL.len(), picker.items.len());
+        assert_eq!(vec![Cc, Age], picker.columns());
+
+        picker.shift(false);
+        assert_eq!(vec![Age, Cc], picker.columns());
+        assert_eq!(1, picker.pos);
+
+        picker.down();
+        picker.toggle();
+        picker.shift(true);
+        picker.shift(true);
+        assert_eq!(vec![ALL[0], Age, Cc], picker.columns());
+
+        picker.up();
+        picker.down();
+        picker.toggle();
+        assert_eq!(vec![ALL[0], Cc], picker.columns());
+
+        let picker = Picker::new(&DEFAULT);
+        assert_eq!(DEFAULT.to_vec(), picker.columns());
+    }
+}
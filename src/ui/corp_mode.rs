@@ -1,32 +1,58 @@
-use std::cmp::min;
-use ncurses::{clear, LINES, refresh};
+use std::collections::VecDeque;
+use std::ops::Range;
+
+use ncurses::{clear, refresh};
 use ui::{print_footer, print_matrix};
 use crate::pacstream::PacStream;
 use crate::ui;
-use crate::ui::{Cell, compute_widths, stats, UI};
+use crate::ui::{Cell, compute_widths, stats, Style, UI};
 use crate::ui::Justify::{LHS, RHS};
+use crate::ui::sort::SortKey::{Age, Cc, Corp, Host};
+
+pub(crate) fn print(ui: &mut UI, pac_vec: &Vec<PacStream>, history: &VecDeque<(u64, u64)>, q_depth: u64, dropped: u64, interval: u64) {
+    let mut matrix = render(ui, pac_vec, history, interval, ui.visible(pac_vec.len()));
+
+    let mut widths = compute_widths(&matrix, &ui.widths);
+
+    clear();
+
+    print_matrix(&mut matrix, &mut widths, &ui.theme);
+
+    print_footer(ui, q_depth, dropped, widths.iter().sum::<i16>() as i32);
+
+    refresh();
+
+    ui.store_widths(&widths);
+}
 
-pub(crate) fn print(ui: &mut UI, pac_vec: &Vec<PacStream>, q_depth: u64, dropped: u64, interval: u64) {
-    let nrows = min(pac_vec.len(), (LINES() - 2) as usize);
+pub(crate) fn render(ui: &UI, pac_vec: &[PacStream], history: &VecDeque<(u64, u64)>, interval: u64, rows: Range<usize>) -> Vec<Vec<Cell>> {
     let mut matrix: Vec<Vec<Cell>> = Vec::new();
 
-    let bytes_sent_last: u64 = pac_vec.iter().map(|s| s.bytes_sent_last).sum();
-    let bytes_recv_last: u64 = pac_vec.iter().map(|s| s.bytes_recv_last).sum();
+    let bytes_sent_last: u64 = pac_vec.iter().map(|s| s.bytes_sent_last).fold(0, u64::saturating_add);
+    let bytes_recv_last: u64 = pac_vec.iter().map(|s| s.bytes_recv_last).fold(0, u64::saturating_add);
 
     let mut header: Vec<Cell> = Vec::new();
-    header.push(Cell::new(LHS, "CORP"));
+    header.push(Cell::new(LHS, &format!("{}{}", match ui.cloud_mode {
+        true => "CLOUD|CORP",
+        false => "CORP"
+    }, ui.sort.mark(&[Corp, Host]))));
     header.push(Cell::new(RHS, " "));
-    header.push(Cell::new(RHS, "CC"));
+    header.push(Cell::new(RHS, &format!("CC{}", ui.sort.mark(&[Cc]))));
     header.push(Cell::new(RHS, " "));
-    stats::add_headers(&mut header, bytes_sent_last, bytes_recv_last, interval);
+    stats::add_headers(&mut header, bytes_sent_last, bytes_recv_last, interval, &ui.sort);
     header.push(Cell::new(RHS, " "));
-    header.push(Cell::new(RHS, "LAST"));
+    header.push(Cell::spark(history));
+    header.push(Cell::new(RHS, " "));
+    header.push(Cell::new(RHS, &format!("LAST{}", ui.sort.mark(&[Age]))));
     matrix.push(header);
 
-    for i in 0..nrows {
+    for i in rows {
         let mut row: Vec<Cell> = Vec::new();
         let pac = &pac_vec[i];
-        if pac.corp.len() < 2 {
+        if let (true, Some(cloud)) = (ui.cloud_mode, &pac.cloud) {
+            row.push(Cell::new(LHS, cloud));
+        }
+        else if pac.corp.len() < 2 {
             row.push(Cell::new(LHS, &pac.remote_host));
         }
         else {
@@ -37,20 +63,16 @@ pub(crate) fn print(ui: &mut UI, pac_vec: &Vec<PacStream>, q_depth: u64, dropped
         row.push(Cell::new(LHS, " "));
         stats::add(&mut row, pac, bytes_sent_last, bytes_recv_last, interval);
         row.push(Cell::new(RHS, " "));
+        row.push(Cell::spark(&pac.history));
+        row.push(Cell::new(RHS, " "));
         row.push(Cell::new(RHS, &pac.age()));
+        Cell::style_row(&mut row, Style::of(pac));
+        if ui.cursor == Some(i) {
+            Cell::style_row(&mut row, Style::Selected);
+        }
         matrix.push(row);
     }
 
-    let mut widths = compute_widths(&matrix, &ui.widths);
-
-    clear();
-
-    print_matrix(&mut matrix, &mut widths);
-
-    print_footer(ui, q_depth, dropped, widths.iter().sum::<i16>() as i32);
-
-    refresh();
-
-    ui.store_widths(&widths);
+    matrix
 }
 
@@ -0,0 +1,104 @@
+use ncurses::{A_BOLD, ACS_HLINE, ACS_LLCORNER, ACS_LRCORNER, ACS_ULCORNER, ACS_URCORNER, ACS_VLINE, attroff, attron, clear, COLS, LINES, mvaddch, mvhline, mvprintw, mvvline, refresh};
+
+use crate::etc::{fmt_millis, mag_fmt, str};
+use crate::pacstream::PacStream;
+use crate::ui::{opt_fmt, speed, UI};
+
+pub(crate) fn print(ui: &UI, stream: &PacStream) {
+    clear();
+
+    mvaddch(0, 0, ACS_ULCORNER());
+    mvhline(0, 1, ACS_HLINE(), COLS() - 2);
+    mvaddch(0, COLS() - 1, ACS_URCORNER());
+    mvvline(1, 0, ACS_VLINE(), LINES() - 2);
+    mvvline(1, COLS() - 1, ACS_VLINE(), LINES() - 2);
+    mvaddch(LINES() - 1, 0, ACS_LLCORNER());
+    mvhline(LINES() - 1, 1, ACS_HLINE(), COLS() - 2);
+    mvaddch(LINES() - 1, COLS() - 1, ACS_LRCORNER());
+
+    let width = ((COLS() - 17).max(1)) as usize;   // what's left after the labels
+    let mut tt: Vec<(&str, String)> = Vec::new();
+
+    if ui.corp_mode {
+        tt.push(("corp", stream.corp.to_string()));
+        tt.push(("cc", stream.cc.to_string()));
+        tt.push(("first host", format!("{} ({})", stream.remote_host, stream.remote_addr)));
+    } else {
+        tt.push(("remote", stream.remote_host.to_string()));
+        tt.push(("", format!("{}:{} ({}) {}", stream.remote_addr, stream.remote_port, stream.remote_service, str(stream.ip_number))));
+        tt.push(("local", stream.local_host.to_string()));
+        tt.push(("", format!("{}:{} ({})", stream.local_addr, stream.local_port, stream.local_service)));
+        tt.push(("", "".to_string()));
+        tt.push(("proc", stream.proc.to_string()));
+        tt.push(("pid", opt_fmt(stream.pid)));
+        tt.push(("uid", match &stream.user {
+            Some(user) => format!("{} ({})", opt_fmt(stream.uid), user),
+            None => opt_fmt(stream.uid)
+        }));
+        tt.push(("cmdline", stream.cmdline.clone().unwrap_or("-".to_string())));
+        tt.push(("", "".to_string()));
+        tt.push(("corp", stream.corp.to_string()));
+        tt.push(("cc", stream.cc.to_string()));
+    }
+
+    if let Some(city) = &stream.city {
+        tt.push(("city", city.to_string()));
+    }
+    if let Some(asn) = stream.asn {
+        tt.push(("asn", format!("AS{}", asn)));
+    }
+    if let Some(sni) = &stream.sni {
+        tt.push(("sni", sni.to_string()));
+    }
+
+    if let Some(tag) = &stream.tag {
+        tt.push(("tag", tag.to_string()));
+    }
+    if let Some(cloud) = &stream.cloud {
+        tt.push(("cloud", cloud.to_string()));
+    }
+    if let Some(list) = &stream.flag {
+        tt.push(("blocklist", list.to_string()));
+    }
+
+    tt.push(("", "".to_string()));
+    tt.push(("packets", format!("in:{} out:{}", stream.packets_in, stream.packets_out)));
+    tt.push(("bytes", format!("in:{} out:{}", mag_fmt(stream.bytes_recv), mag_fmt(stream.bytes_sent))));
+    tt.push(("rate", format!("in:{} out:{}",
+        speed(stream.history.front().map(|h| h.0).unwrap_or(0), 1000),
+        speed(stream.history.front().map(|h| h.1).unwrap_or(0), 1000))));
+    tt.push(("first seen", fmt_millis(stream.ts_first.timestamp_millis())));
+    tt.push(("last seen", format!("{} ({})", fmt_millis(stream.ts_last.timestamp_millis()), stream.age())));
+    tt.push(("", "".to_string()));
+    tt.push(("history", format!("in/out per second, {}ms intervals, newest first", ui.redraw_interval)));
+
+    let hist = stream.history.iter()
+        .map(|(recv, sent)| format!("{}/{}", mag_fmt(*recv), mag_fmt(*sent)))
+        .collect::<Vec<String>>();
+    for chunk in hist.chunks((width / 12).max(1)) {
+        tt.push(("", chunk.iter().map(|h| format!("{:<12}", h)).collect::<String>()));
+    }
+
+    let mut y = 1;
+    for (label, value) in tt {
+        // long values (eg cmdline) wrap rather than truncate //
+        let mut first = true;
+        let chars: Vec<char> = value.chars().collect();
+        for line in chars.chunks(width).map(|c| c.iter().collect::<String>()).chain(value.is_empty().then(String::new)) {
+            if y >= LINES() - 1 {
+                break;
+            }
+            attron(A_BOLD());
+            mvprintw(y, 2, &format!("{:>11}", if first { label } else { "" }));
+            attroff(A_BOLD());
+            mvprintw(y, 15, &line.replace('%', "%%"));
+            first = false;
+            y += 1;
+        }
+    }
+
+    mvprintw(LINES() - 1, 2, " enter/esc: back ");
+    mvprintw(LINES() - 1, COLS() - 13, &fmt_millis(ui.last_draw));
+
+    refresh();
+}
@@ -0,0 +1,117 @@
+use ncurses::{A_BOLD, addch, addstr, attr_t, attroff, attron, chtype, clear, COLS, LINES, mv, mvaddch, mvprintw, refresh};
+
+use crate::etc::millitime;
+use crate::history::{Graph, Group, History};
+use crate::ui::{print_footer, speed, Style, UI};
+
+// series fills, biggest first. the last one is "other" //
+const FILLS: [char; 6] = ['#', '@', '=', '+', '*', 'o'];
+const OTHER_FILL: char = '.';
+const AXIS_WIDTH: i32 = 9;
+
+// throughput over the last n minutes, in on top of out, the top processes
+// (or corps in corp mode) stacked with everything else lumped together //
+pub(crate) fn print(ui: &UI, history: &History, q_depth: u64, dropped: u64) {
+    clear();
+
+    let group = match ui.corp_mode {
+        true => Group::Corp,
+        false => Group::Proc
+    };
+
+    let cols = (COLS() - AXIS_WIDTH).max(0) as usize;
+    let window = ui.graph_minutes * 60_000;
+    let graph = history.graph(group, FILLS.len(), millitime(), window, cols);
+
+    // title, two panes, legend and footer //
+    let pane_height = ((LINES() - 4) / 2).max(1);
+
+    draw_pane(1, pane_height, &format!("in - last {}m", ui.graph_minutes), &graph.recv, ui.theme.attr(Style::In));
+    draw_pane(2 + pane_height, pane_height, "out", &graph.sent, ui.theme.attr(Style::Out));
+    draw_legend(LINES() - 2, &graph);
+
+    print_footer(ui, q_depth, dropped, COLS());
+
+    refresh();
+}
+
+fn draw_pane(top: i32, height: i32, title: &str, series: &[Vec<u64>], attr: attr_t) {
+    let cols = series.first().map(|s| s.len()).unwrap_or(0);
+    let totals: Vec<u64> = (0..cols).map(|col| series.iter().map(|s| s[col]).fold(0, u64::saturating_add)).collect();
+    let max = totals.iter().max().cloned().unwrap_or(0);
+    let current = totals.last().cloned().unwrap_or(0);
+
+    attron(A_BOLD());
+    mvprintw(top - 1, 0, &format!("{} now:{} peak:{}", title, speed(current, 1000), speed(max, 1000)));
+    attroff(A_BOLD());
+
+    mvprintw(top, 0, &format!("{:>8}", speed(max, 1000)));
+    mvprintw(top + height - 1, 0, &format!("{:>8}", "0"));
+
+    if max == 0 {
+        return;
+    }
+
+    attron(attr);
+    for col in 0..cols {
+        let rows = stack(series, col, max, height as u64);
+        for (row, fill) in rows.iter().enumerate() {
+            if let Some(fill) = fill {
+                mvaddch(top + height - 1 - row as i32, AXIS_WIDTH + col as i32, *fill as chtype);
+            }
+        }
+    }
+    attroff(attr);
+}
+
+// which series fills each row of a column, bottom up //
+fn stack(series: &[Vec<u64>], col: usize, max: u64, height: u64) -> Vec<Option<char>> {
+    let mut ret = vec![None; height as usize];
+    let mut sum = 0u64;
+    let mut from = 0usize;
+    for (i, s) in series.iter().enumerate() {
+        sum = sum.saturating_add(s[col]);
+        // round the running total so the stack doesn't drift //
+        let to = ((sum as u128 * height as u128 + max as u128 / 2) / max as u128) as usize;
+        for fill in ret.iter_mut().take(to).skip(from) {
+            *fill = Some(fill_for(i, series.len()));
+        }
+        from = from.max(to);
+    }
+    ret
+}
+
+fn fill_for(i: usize, len: usize) -> char {
+    match i + 1 == len {
+        true => OTHER_FILL,
+        false => FILLS[i % FILLS.len()]
+    }
+}
+
+fn draw_legend(y: i32, graph: &Graph) {
+    mv(y, 0);
+    for (i, name) in graph.names.iter().enumerate() {
+        let recv = graph.recv[i].last().cloned().unwrap_or(0);
+        let sent = graph.sent[i].last().cloned().unwrap_or(0);
+        addch(fill_for(i, graph.names.len()) as chtype);
+        let mut name = name.to_string();
+        name.truncate(16);
+        addstr(&format!(" {} {}/{}  ", name, speed(recv, 1000), speed(sent, 1000)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ui::graph_mode::stack;
+
+    #[test]
+    fn test_stack() {
+        let series = vec![vec![50, 0], vec![25, 0], vec![25, 30]];
+        assert_eq!(vec![Some('#'), Some('#'), Some('@'), Some('.')], stack(&series, 0, 100, 4));
+        assert_eq!(vec![Some('.'), None, None, None], stack(&series, 1, 100, 4));
+
+        // tiny series round away rather than push others up //
+        let series = vec![vec![1], vec![99]];
+        assert_eq!(vec![Some('.'), Some('.')], stack(&series, 0, 100, 2));
+    }
+}
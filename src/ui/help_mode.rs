@@ -16,8 +16,8 @@ pub(crate) fn print(ui: &UI, pac_vec: &Vec<PacStream>, q_depth: u64, dropped: u6
     mvaddch(LINES() - 1, 0, ACS_LLCORNER());
     mvhline(LINES() - 1, 1, ACS_HLINE(), COLS() - 2);
     mvaddch(LINES() - 1, COLS() - 1, ACS_LRCORNER());
-let bytes_sent_last: u64 = pac_vec.iter().map(|s| s.bytes_sent_last).sum();
-    let bytes_recv_last: u64 = pac_vec.iter().map(|s| s.bytes_recv_last).sum();
+let bytes_sent_last: u64 = pac_vec.iter().map(|s| s.bytes_sent_last).fold(0, u64::saturating_add);
+    let bytes_recv_last: u64 = pac_vec.iter().map(|s| s.bytes_recv_last).fold(0, u64::saturating_add);
 
     let mut tt = vec![
         format!("     q depth: {:<8} drop'd: {:<10} resolve: {:<10} pause: {:?}" ,
@@ -48,6 +48,8 @@ let bytes_sent_last: u64 = pac_vec.iter().map(|s| s.bytes_sent_last).sum();
         ));
     }
 
+    tt.push(format!("    {:9} {}", "", "up/down: select   pgup/pgdn/home/end: scroll   enter: details   esc: back"));
+
     for t in &mut tt {
         t.truncate(COLS() as usize - 2);
     }
@@ -1,20 +1,39 @@
+pub mod batch;
+pub mod columns;
 mod corp_mode;
 mod normal_mode;
 mod help_mode;
+mod detail_mode;
+mod graph_mode;
 mod stats;
+pub mod sort;
+mod spark;
+mod theme;
 
 use std::{panic};
 use std::backtrace::Backtrace;
-use std::cmp::{max, min, Ordering};
-use std::collections::{BTreeMap, HashMap};
+use std::cmp::{max, min};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::path::PathBuf;
+
+use chrono::{Local, TimeDelta, Utc};
 
 use ncurses::*;
 use pacmon::Streams;
 
+use crate::config::{Config, Value};
 use crate::etc::{fmt_millis, log, mag_fmt, millitime};
+use crate::filter::Filter;
+use crate::history::MAX_MINUTES;
+use crate::pacdat::StreamKey;
 use crate::pacmon;
 use crate::pacstream::PacStream;
+use crate::sinks::csv;
+use crate::ui::columns::Column;
 use crate::ui::Justify::{LHS, RHS};
+use crate::ui::sort::{Sort, SortKey};
+use crate::ui::spark::SPARK_LEN;
+use crate::ui::theme::Theme;
 
 pub struct UI {
     redraw_interval:i64,
@@ -22,26 +41,54 @@ pub struct UI {
     last_draw: i64,
     last_cols: i32,
     widths:Vec<i16>,
-    sort_by:i64, 
+    columns:Vec<Column>,
+    sort:Sort,
+    theme:Theme,
     commands: HashMap<char,fn(&mut UI)>,
     command_info: BTreeMap<char,String>,
     redraw_requested:bool,
     paused:bool,
     resolve:bool,
     help:bool,
-    corp_mode:bool
+    corp_mode:bool,
+    cloud_mode:bool,
+    graph:bool,
+    graph_minutes:i64,
+    flagged_only:bool,
+    filter:Option<Filter>,
+    filter_err:Option<String>,
+    editing:Option<String>,     // the filter being typed on the bottom row
+    status:Option<String>,      // the outcome of the last command, until the next key
+    cursor:Option<usize>,
+    scroll:usize,
+    rows:Vec<StreamKey>,
+    selected:Option<StreamKey>,
+    view:Vec<PacStream>,        // the rows last drawn, in order
+    pub quitting:bool,          // 'q', for the main loop to see us out
+    config:Config
 }
 
+// what gets written back to the config file //
+const SAVED: [&str; 7] = ["interval", "sort", "reverse", "mode", "resolve", "filter", "columns"];
+
 impl UI {
-    pub fn init() -> Self {
+    pub fn init(config: Config) -> Self {
         set_panic_hook();
-        UI {
+        let mut ui = UI {
             redraw_interval: 4000,
             start_time: millitime(),
             last_draw: 0,
             last_cols: 0,
             widths: vec![],
-            sort_by: 0,
+            columns: match config.path("columns") {
+                Some(path) => columns::load(&path),
+                None => columns::DEFAULT.to_vec()
+            },
+            sort: Sort::new(),
+            theme: match config.path("theme") {
+                Some(path) => Theme::load(&path),
+                None => Theme::new()
+            },
             commands: HashMap::new(),
             command_info: BTreeMap::new(),
             redraw_requested: false,
@@ -49,25 +96,163 @@ impl UI {
             resolve: true,
             help: false,
             corp_mode: false,
+            cloud_mode: false,
+            graph: false,
+            graph_minutes: 5,
+            flagged_only: false,
+            filter: None,
+            filter_err: None,
+            editing: None,
+            status: None,
+            cursor: None,
+            scroll: 0,
+            rows: vec![],
+            selected: None,
+            view: vec![],
+            quitting: false,
+            config
+        };
+        ui.configure();
+        ui
+    }
+
+    // the config file's say over the defaults above //
+    fn configure(&mut self) {
+        let config = &self.config;
+
+        for msg in self.theme.parse(&config.colours()) {
+            eprintln!("config: colours: {}", msg);
+        }
+
+        if let Some(interval) = config.get_int("interval") {
+            self.redraw_interval = interval.max(200);
+        }
+
+        if let Some(txt) = config.get_str("sort") {
+            match SortKey::parse(&txt) {
+                Some(key) => self.sort.key = key,
+                None => eprintln!("config: unknown sort [{}]", txt)
+            }
+        }
+
+        if let Some(reverse) = config.get_bool("reverse") {
+            self.sort.reverse = reverse;
+        }
+
+        if let Some(mode) = config.get_str("mode") {
+            match mode.as_str() {
+                "normal" => {}
+                "corp" => self.corp_mode = true,
+                "cloud" => (self.corp_mode, self.cloud_mode) = (true, true),
+                "graph" => self.graph = true,
+                _ => eprintln!("config: unknown mode [{}] - normal corp cloud or graph", mode)
+            }
+        }
+
+        if let Some(resolve) = config.get_bool("resolve") {
+            self.resolve = resolve;
+        }
+
+        if let Some(txt) = config.get_str("filter") {
+            match Filter::parse(&txt) {
+                Ok(filter) => self.filter = Some(filter).filter(|_| !txt.trim().is_empty()),
+                Err(msg) => eprintln!("config: filter: {}", msg)
+            }
+        }
+
+        if let Some(txt) = config.get_str("columns") {
+            match columns::parse_list(&txt) {
+                Ok(cols) => self.columns = cols,
+                Err(msg) => eprintln!("config: columns: {}", msg)
+            }
+        }
+    }
+
+    // the current settings into the config file //
+    fn save_config(&mut self) -> Result<PathBuf, String> {
+        let mode = match (self.graph, self.corp_mode, self.cloud_mode) {
+            (true, _, _) => "graph",
+            (_, true, true) => "cloud",
+            (_, true, false) => "corp",
+            _ => "normal"
+        };
+
+        self.config.set("interval", Value::Int(self.redraw_interval));
+        self.config.set("sort", Value::Str(self.sort.key.name().to_string()));
+        self.config.set("reverse", Value::Bool(self.sort.reverse));
+        self.config.set("mode", Value::Str(mode.to_string()));
+        self.config.set("resolve", Value::Bool(self.resolve));
+        self.config.set("filter", Value::Str(self.filter.as_ref().map(|f| f.to_string()).unwrap_or_default()));
+        self.config.set("columns", Value::List(self.columns.iter().map(|col| col.name().to_string()).collect()));
+
+        self.config.save(&SAVED)
+    }
+
+    // what's on screen (all of it, not just this page) to ./pacmon-<mode>-<time>.csv //
+    fn export_csv(&mut self) {
+        let mode = match (self.corp_mode, self.cloud_mode) {
+            (true, true) => "cloud",
+            (true, false) => "corp",
+            _ => "streams"
+        };
+        let path = format!("pacmon-{}-{}.csv", mode, Local::now().format("%Y%m%d-%H%M%S"));
+        self.status = Some(match csv::snapshot(&path, &self.view, self.corp_mode, self.cloud_mode) {
+            Ok(_) => format!("wrote {} rows to {}", self.view.len(), path),
+            Err(msg) => msg
+        });
+    }
+
+    fn quit(&mut self) {
+        if self.config.get_bool("save") == Some(true) {
+            if let Err(msg) = self.save_config() {
+                shutdown(1, format!("config: {}", msg));
+            }
         }
+        self.quitting = true;
     }
 
     pub fn show(&mut self) {
         initscr();
+        keypad(stdscr(), true);
+        set_escdelay(25);
+        self.theme.init();
         curs_set(CURSOR_VISIBILITY::CURSOR_INVISIBLE);
         refresh();
 
-        self.register_cmd('q', "quit",    |_ui| shutdown(0, "bye".to_string()));
+        self.register_cmd('q', "quit",    |ui| ui.quit());
         self.register_cmd('h', "help",    |ui| ui.help = ! ui.help);
         self.register_cmd('?', "help",    |ui| ui.help = ! ui.help);
         self.register_cmd('r', "resolve ip's", |ui| ui.resolve = ! ui.resolve);
         self.register_cmd(' ', "pause",   |ui| ui.paused = ! ui.paused);
         self.register_cmd('t', "trim",    |ui| ui.widths.clear() );
-        self.register_cmd('s', "sort time/total",    |ui| ui.sort_by = (ui.sort_by + 1) % 2);
+        self.register_cmd('s', "sort column", |ui| ui.sort.key = ui.sort.key.next());
+        self.register_cmd('S', "reverse sort", |ui| ui.sort.reverse = ! ui.sort.reverse);
         self.register_cmd('c', "corporate mode",   |ui| {
             ui.corp_mode = ! ui.corp_mode;
             ui.widths.clear();
         });
+        self.register_cmd('g', "group corps by cloud", |ui| {
+            ui.cloud_mode = ! ui.cloud_mode;
+            ui.widths.clear();
+        });
+        self.register_cmd('G', "graph",         |ui| ui.graph = ! ui.graph);
+        self.register_cmd('[', "graph shorter", |ui| ui.graph_minutes = max(1, ui.graph_minutes / 2));
+        self.register_cmd(']', "graph longer",  |ui| ui.graph_minutes = min(MAX_MINUTES, ui.graph_minutes * 2));
+        self.register_cmd('C', "columns",       |ui| {
+            if let Some(cols) = columns::pick(&ui.columns) {
+                ui.columns = cols;
+                ui.widths.clear();
+            }
+        });
+        self.register_cmd('F', "flagged only",  |ui| ui.flagged_only = ! ui.flagged_only);
+        self.register_cmd('/', "filter",        |ui| ui.prompt_filter());
+        self.register_cmd('W', "save settings", |ui| {
+            ui.status = Some(match ui.save_config() {
+                Ok(path) => format!("saved {}", path.display()),
+                Err(msg) => format!("config: {}", msg)
+            });
+        });
+        self.register_cmd('x', "export csv",    |ui| ui.export_csv());
         self.register_cmd('1', "1s interval",      |ui| ui.redraw_interval = 1000);
         self.register_cmd('2', "2s interval",      |ui| ui.redraw_interval = 2000);
         self.register_cmd('3', "3s interval",      |ui| ui.redraw_interval = 3000);
@@ -78,8 +263,8 @@ impl UI {
         self.register_cmd('8', "8s interval",      |ui| ui.redraw_interval = 8000);
         self.register_cmd('9', "9s interval",      |ui| ui.redraw_interval = 9000);
         self.register_cmd('0', "<1s interval",     |ui| ui.redraw_interval = 200,);
-        self.register_cmd(66 as char, "interval--", |ui| ui.redraw_interval -= 9 );
-        self.register_cmd(65 as char, "interval++", |ui| ui.redraw_interval += 9 );
+        self.register_cmd('-', "interval--", |ui| ui.redraw_interval -= 9 );
+        self.register_cmd('+', "interval++", |ui| ui.redraw_interval += 9 );
 
         self.start_time = millitime();
     }
@@ -115,26 +300,69 @@ impl UI {
     }
 
     pub fn draw(&mut self, streams: &mut Streams, q_depth: u64, dropped: u64) {
-        let now = millitime();
-        let interval = (now - self.last_draw) as u64;
+        let (pac_vec, interval) = self.prepare(streams);
+
+        let selected = match &self.selected {
+            Some(key) => pac_vec.iter().find(|s| &s.key() == key),
+            None => None
+        };
 
         if self.help {
-            let pac_vec = to_stream_vec(&mut streams.by_stream, self.sort_by);
             help_mode::print(self, &pac_vec, q_depth, dropped, interval);
+        } else if let Some(stream) = selected {
+            detail_mode::print(self, stream);
+        } else if self.graph {
+            graph_mode::print(self, &streams.history, q_depth, dropped);
         } else {
+            self.selected = None;
+            let history = streams.history.rates(SPARK_LEN);
             if self.corp_mode {
-                let pac_vec = to_stream_vec(&mut streams.by_corp, self.sort_by);
-                corp_mode::print(self, &pac_vec, q_depth, dropped, interval);
-            }
-            else {
-                let pac_vec = to_stream_vec(&mut streams.by_stream, self.sort_by);
-                normal_mode::print(self, &pac_vec, q_depth, dropped, interval);
+                corp_mode::print(self, &pac_vec, &history, q_depth, dropped, interval);
+            } else {
+                normal_mode::print(self, &pac_vec, &history, q_depth, dropped, interval);
             }
         }
 
+        if let Some(txt) = &self.editing {
+            print_prompt("filter: ", txt);
+        }
+
+        self.view = pac_vec;
         self.last_draw = millitime();
     }
 
+    // rolls the interval over and returns what to show, sorted and filtered,
+    // with how long the interval was //
+    fn prepare(&mut self, streams: &mut Streams) -> (Vec<PacStream>, u64) {
+        let interval = (millitime() - self.last_draw) as u64;
+
+        streams.push_history(interval);
+
+        let mut pac_vec = if self.corp_mode && self.cloud_mode {
+            to_stream_vec(&streams.by_cloud, &self.sort)
+        } else if self.corp_mode {
+            to_stream_vec(&streams.by_corp, &self.sort)
+        } else {
+            to_stream_vec(&streams.by_stream, &self.sort)
+        };
+
+        streams.reset_stats();
+
+        if self.flagged_only && !self.corp_mode {
+            pac_vec.retain(|s| s.flag.is_some());
+        }
+
+        if let Some(filter) = &self.filter {
+            pac_vec.retain(|s| filter.matches(s));
+        }
+
+        // remember the row order so the cursor can refer to it //
+        self.rows = pac_vec.iter().map(|s| s.key()).collect();
+        self.clamp_scroll();
+
+        (pac_vec, interval)
+    }
+
     fn register_cmd(&mut self, c: char, desc: &str, cmd: fn(&mut UI)) {
         match self.commands.insert(c, cmd) {
             None => {}
@@ -146,15 +374,115 @@ impl UI {
     pub fn check_key(&mut self) {
         nodelay(stdscr(), true);
         let c = getch();
-        if c != ERR {
-            match self.commands.get(&std::char::from_u32(c as u32).unwrap()) {
-                Some(cmd) => cmd(self),
-                None => log(format!("getch({})", c))
+        if c != ERR && self.editing.is_some() {
+            self.edit_filter(c);
+            self.request_redraw();
+        } else if c != ERR {
+            self.status = None;
+            match c {
+                KEY_UP => self.move_cursor(-1),
+                KEY_DOWN => self.move_cursor(1),
+                KEY_PPAGE => self.page(-1),
+                KEY_NPAGE => self.page(1),
+                KEY_HOME => self.page(-(self.rows.len() as i64)),
+                KEY_END => self.page(self.rows.len() as i64),
+                KEY_ENTER | 10 | 13 => self.toggle_detail(),
+                27 => {  // esc
+                    self.selected = None;
+                    self.cursor = None;
+                }
+                _ => match self.commands.get(&std::char::from_u32(c as u32).unwrap()) {
+                    Some(cmd) => cmd(self),
+                    None => log(format!("getch({})", c))
+                }
             }
             self.request_redraw();
         }
     }
 
+    // the keys come one at a time through check_key, so capture carries on while it's typed //
+    fn prompt_filter(&mut self) {
+        self.editing = Some(match &self.filter {
+            Some(filter) => filter.to_string(),
+            None => String::new()
+        });
+    }
+
+    fn edit_filter(&mut self, c: i32) {
+        let Some(txt) = &mut self.editing else { return };
+        if let Some(done) = edit(txt, c) {
+            let txt = self.editing.take().unwrap();
+            if done {
+                self.apply_filter(&txt);
+            }
+            curs_set(CURSOR_VISIBILITY::CURSOR_INVISIBLE);
+        }
+    }
+
+    fn apply_filter(&mut self, txt: &str) {
+        self.filter_err = None;
+        if txt.trim().is_empty() {
+            self.filter = None;
+        } else {
+            match Filter::parse(txt) {
+                Ok(filter) => self.filter = Some(filter),
+                Err(msg) => self.filter_err = Some(msg)
+            }
+        }
+        self.scroll = 0;
+        self.cursor = None;
+    }
+
+    fn move_cursor(&mut self, delta: i64) {
+        let last = self.rows.len().saturating_sub(1) as i64;
+        self.cursor = Some(match self.cursor {
+            Some(cursor) => (cursor as i64 + delta).clamp(0, last) as usize,
+            None => self.scroll
+        });
+        self.clamp_scroll();
+    }
+
+    // scroll by n pages, dragging the cursor along if there is one //
+    fn page(&mut self, n: i64) {
+        let delta = n.saturating_mul(page_size() as i64);
+        let last = self.rows.len().saturating_sub(1) as i64;
+        self.scroll = (self.scroll as i64).saturating_add(delta).clamp(0, last) as usize;
+        if let Some(cursor) = self.cursor {
+            self.cursor = Some((cursor as i64).saturating_add(delta).clamp(0, last) as usize);
+        }
+        self.clamp_scroll();
+    }
+
+    // keep the cursor on screen and the screen full //
+    fn clamp_scroll(&mut self) {
+        let page = page_size();
+        self.scroll = min(self.scroll, self.rows.len().saturating_sub(page));
+        if let Some(cursor) = self.cursor {
+            let cursor = min(cursor, self.rows.len().saturating_sub(1));
+            if cursor < self.scroll {
+                self.scroll = cursor;
+            } else if cursor >= self.scroll + page {
+                self.scroll = cursor + 1 - page;
+            }
+            self.cursor = Some(cursor);
+        }
+    }
+
+    // the rows that fit on screen //
+    fn visible(&self, len: usize) -> std::ops::Range<usize> {
+        min(self.scroll, len)..min(self.scroll + page_size(), len)
+    }
+
+    fn toggle_detail(&mut self) {
+        if self.selected.is_some() {
+            self.selected = None;
+        } else {
+            let cursor = self.cursor.unwrap_or(0);
+            self.selected = self.rows.get(cursor).cloned();
+            self.cursor = Some(cursor);
+        }
+    }
+
     fn store_widths(&mut self, widths: &Vec<i16>) {
         self.widths.clear();
         self.widths.extend(widths);
@@ -165,32 +493,25 @@ impl UI {
     }
 }
 
-fn to_stream_vec<K>(streams: &mut BTreeMap<K, PacStream>, sort_by:i64) -> Vec<PacStream> {
+fn to_stream_vec<K>(streams: &BTreeMap<K, PacStream>, sort:&Sort) -> Vec<PacStream> {
     let mut pac_vec: Vec<PacStream> = streams.values().cloned().collect();
 
-    if sort_by == 0 {
-        pac_vec.sort_by(sort_by_last_ts);
-    } else {
-        pac_vec.sort_by(sort_by_bytes);
-    }
-
-    for stream in streams.values_mut() {
-        stream.reset_stats();
-    }
+    sort.sort(&mut pac_vec);
 
     pac_vec
 }
 
 fn print_footer(ui:&UI, q_depth: u64, dropped: u64, cols: i32) {
     let footer = render_footer(ui, q_depth, dropped);
-    attron(A_REVERSE());
-    mvprintw(LINES() - 1, 0, &footer);
-    pad(cols - footer.len() as i32);
+    let attr = ui.theme.attr(Style::Footer);
+    attron(attr);
+    mvprintw(LINES() - 1, 0, &printable(&footer));
+    pad(cols - footer.chars().count() as i32);
     mvprintw(LINES() - 1, cols - 12, &fmt_millis(ui.last_draw));
-    attroff(A_REVERSE());
+    attroff(attr);
 }
 
-fn print_matrix(matrix: &mut Vec<Vec<Cell>>, widths: &mut Vec<i16>) {
+fn print_matrix(matrix: &mut Vec<Vec<Cell>>, widths: &mut Vec<i16>, theme: &Theme) {
     for i in 0..matrix.len() {
         let row = matrix.get(i).unwrap();
         let mut x = 0i32;
@@ -205,24 +526,29 @@ fn print_matrix(matrix: &mut Vec<Vec<Cell>>, widths: &mut Vec<i16>) {
                 RHS => (width - cell.width()) as i32
             };
 
-            // if we overshoot on the LHS we truncate (left) //
+            // if we overshoot on the LHS we truncate (left), by chars
+            // as the text is shown rather than by bytes //
+            let shown = cell.txt.replace("%%", "%");
             let txt = if x + offset < 0 {
                 offset = -x;
-                let range = (cell.txt.len() - *width as usize + 1)..cell.txt.len();
-                "#".to_string() + &cell.txt[range]
+                let chars: Vec<char> = shown.chars().collect();
+                let keep = (*width as usize).saturating_sub(1).min(chars.len());
+                "#".to_string() + &chars[chars.len() - keep..].iter().collect::<String>()
             } else {
-                cell.txt.to_string()
+                shown
             };
 
-            if i == 0 {
-                attron(A_BOLD());
-                //attron(A_REVERSE());
-            } else {
-                attroff(A_BOLD());
-                //attroff(A_REVERSE());
-            }
+            let attr = match i {
+                0 => theme.attr(Style::Header) | theme.attr(cell.style),
+                _ => theme.attr(cell.style)
+            };
 
-            mvprintw(y as i32, x + offset, &txt);
+            attron(attr);
+            match &cell.spark {
+                Some(levels) => spark::print(y as i32, x + offset, levels),
+                None => { mvprintw(y as i32, x + offset, &printable(&txt)); }
+            }
+            attroff(attr);
 
             if cell.width() > *width {
                 mvprintw(y as i32, x + offset - 1, " ");
@@ -233,26 +559,74 @@ fn print_matrix(matrix: &mut Vec<Vec<Cell>>, widths: &mut Vec<i16>) {
     }
 }
 
-fn render_footer(ui:&UI, q_depth: u64, dropped: u64) -> String {
-    let sort = match ui.sort_by {
-        0 => "time",
-        1 => "total",
-        _ => panic!("dead")
-    };
+// mvprintw takes a printf format, and hosts, procs and server names come
+// from the other end. a lone % would be a conversion and a nul a panic //
+fn printable(txt: &str) -> String {
+    txt.replace('\0', "").replace('%', "%%")
+}
 
-    let mut ret = format!("{}x{} q:{} drop'd:{} interval:{}ms sort:{}",
-            LINES(), COLS(), q_depth, dropped, ui.redraw_interval, sort);
+// line editing: Some(true) on enter, Some(false) if cancelled with esc //
+fn edit(txt: &mut String, c: i32) -> Option<bool> {
+    match c {
+        KEY_ENTER | 10 | 13 => return Some(true),
+        27 => return Some(false),
+        KEY_BACKSPACE | 127 | 8 => { txt.pop(); }
+        c if (32..127).contains(&c) => txt.push(c as u8 as char),
+        _ => {}
+    }
+    None
+}
+
+// over the footer, with the cursor at the end of the text //
+fn print_prompt(label: &str, txt: &str) {
+    curs_set(CURSOR_VISIBILITY::CURSOR_VISIBLE);
+    attron(A_REVERSE());
+    mvprintw(LINES() - 1, 0, &printable(&format!("{}{}", label, txt)));
+    pad(COLS() - (label.len() + txt.len()) as i32);
+    attroff(A_REVERSE());
+    mv(LINES() - 1, min(label.len() + txt.len(), max(COLS() - 1, 0) as usize) as i32);
+    refresh();
+}
+
+fn render_footer(ui:&UI, q_depth: u64, dropped: u64) -> String {
+    let mut ret = format!("{}x{} q:{} drop'd:{} interval:{}ms sort:{}{}",
+            LINES(), COLS(), q_depth, dropped, ui.redraw_interval, ui.sort.key.name(), ui.sort.arrow());
 
     if ui.paused {
       ret.push_str(" [paused]");
     }
 
+    if ui.flagged_only {
+      ret.push_str(" [flagged]");
+    }
+
+    if let Some(msg) = &ui.status {
+        ret.push_str(&format!(" [{}]", msg));
+    }
+
+    if let Some(msg) = &ui.filter_err {
+        ret.push_str(&format!(" filter err: {}", msg));
+    } else if let Some(filter) = &ui.filter {
+        ret.push_str(&format!(" filter:{}", filter));
+    }
+
+    let visible = ui.visible(ui.rows.len());
+    if visible.is_empty() {
+        ret.push_str(&format!(" rows 0 of {}", ui.rows.len()));
+    } else {
+        ret.push_str(&format!(" rows {}-{} of {}", visible.start + 1, visible.end, ui.rows.len()));
+    }
+
     ret
 }
 
 
-pub fn shutdown(code:i32, msg:String) {
+pub fn end() {
     endwin();
+}
+
+pub fn shutdown(code:i32, msg:String) {
+    end();
     eprintln!("{}", msg);
     std::process::exit(code);
 }
@@ -270,23 +644,8 @@ fn set_panic_hook() {
     }));
 }
 
-fn sort_by_bytes(a:&PacStream, b:&PacStream) -> Ordering {
-    let mut ret = b.bytes().cmp(&a.bytes());
-    if ret.is_eq() {
-        ret = b.ts_last.cmp(&a.ts_last);
-    }
-    ret
-}
-
-fn sort_by_last_ts(a:&PacStream, b:&PacStream) -> Ordering {
-    let mut ret = b.bytes_last().cmp(&a.bytes_last());
-    if ret.is_eq() {
-        ret = b.ts_last.cmp(&a.ts_last);
-    }
-    if ret.is_eq() {
-        ret = b.bytes().cmp(&a.bytes());
-    }
-    ret
+fn page_size() -> usize {
+    max(LINES() - 2, 1) as usize
 }
 
 fn pad(n:i32) {
@@ -351,6 +710,13 @@ fn massage_corp(txt:&mut String, target_width:usize) {
     }
 }
 
+fn opt_fmt(val: Option<u32>) -> String {
+    match val {
+        Some(val) => val.to_string(),
+        None => "-".to_string()
+    }
+}
+
 fn pct_fmt(pct:f64) -> String {
     if pct == 0.0 || pct.is_nan() {
         "-".to_string()
@@ -382,14 +748,86 @@ enum Justify {
     RHS
 }
 
+// what a cell is showing, for the theme to colour. the row wide ones
+// (new..local) give way to the cell's own (rates, direction) which give
+// way to flagged and selected rows //
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Style {
+    Plain,
+    Header,
+    Footer,
+    Local,
+    Foreign,
+    Idle,
+    New,
+    In,
+    Out,
+    Heat(u8),
+    Flagged,
+    Selected
+}
+
+impl Style {
+    fn rank(&self) -> u8 {
+        match self {
+            Style::Plain | Style::Header | Style::Footer => 0,
+            Style::Local | Style::Foreign | Style::Idle | Style::New => 1,
+            Style::In | Style::Out | Style::Heat(_) => 2,
+            Style::Flagged => 3,
+            Style::Selected => 4
+        }
+    }
+
+    // the row wide style for a stream //
+    fn of(stream: &PacStream) -> Style {
+        let now = Utc::now();
+        if stream.flag.is_some() {
+            Style::Flagged
+        } else if now - stream.ts_first < TimeDelta::seconds(NEW_SECS) {
+            Style::New
+        } else if now - stream.ts_last > TimeDelta::seconds(IDLE_SECS) {
+            Style::Idle
+        } else if stream.foreign {
+            Style::Foreign
+        } else if stream.local_traffic {
+            Style::Local
+        } else {
+            Style::Plain
+        }
+    }
+}
+
+// we don't follow tcp state so a closed stream just goes idle //
+const NEW_SECS: i64 = 10;
+const IDLE_SECS: i64 = 60;
+
 pub struct Cell {
     txt: String,
-    justify: Justify
+    justify: Justify,
+    style: Style,
+    spark: Option<Vec<u8>>     // drawn with line chars instead of txt
 }
 
 impl Cell {
     fn new(justify:Justify, txt:&str) -> Self {
-        Cell { txt: txt.to_string(), justify }
+        Cell { txt: txt.to_string(), justify, style: Style::Plain, spark: None }
+    }
+
+    fn spark(history:&VecDeque<(u64, u64)>) -> Self {
+        let levels = spark::levels(history, spark::SPARK_LEN);
+        Cell { txt: " ".repeat(levels.len()), justify: LHS, style: Style::Plain, spark: Some(levels) }
+    }
+
+    fn styled(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    // leaves cells with a more important style alone //
+    fn style_row(row: &mut [Cell], style: Style) {
+        for cell in row.iter_mut().filter(|c| c.style.rank() < style.rank()) {
+            cell.style = style;
+        }
     }
 
     fn width(&self) -> i16 {
@@ -397,16 +835,13 @@ impl Cell {
     }
 
     fn actual_width(txt:&str) -> i16 {
-        match txt.contains("%%") {
-            true => (txt.len() - 1) as i16,
-            false => txt.len() as i16
-        }
+        txt.replace("%%", "%").chars().count() as i16
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::ui::{Cell, compute_widths, pct_fmt, speed, trim_host};
+    use crate::ui::{Cell, compute_widths, edit, pct_fmt, printable, speed, trim_host};
     use crate::ui::Justify::RHS;
 
     #[test]
@@ -421,6 +856,29 @@ mod tests {
         /* jagged data shouldn't be allowed really */
         matrix.push(vec![Cell::new(RHS, "aaa")]);
         assert_eq!(vec![3, 1], compute_widths(&matrix, &vec![]));
+
+        // as shown: %% is one char, and so is an é //
+        matrix.push(vec![Cell::new(RHS, "50%%"), Cell::new(RHS, "café")]);
+        assert_eq!(vec![3, 4], compute_widths(&matrix, &vec![]));
+    }
+
+    #[test]
+    fn test_printable() {
+        assert_eq!("%%s%%n", printable("%s%n"));
+        assert_eq!("ab", printable("a\0b"));
+    }
+
+    #[test]
+    fn test_edit() {
+        let mut txt = "port".to_string();
+        for c in " 44%x".bytes() {
+            assert_eq!(None, edit(&mut txt, c as i32));
+        }
+        assert_eq!(None, edit(&mut txt, 127));
+        assert_eq!(None, edit(&mut txt, ncurses::KEY_UP));
+        assert_eq!("port 44%", txt);
+        assert_eq!(Some(true), edit(&mut txt, 10));
+        assert_eq!(Some(false), edit(&mut txt, 27));
     }
 
     #[test]
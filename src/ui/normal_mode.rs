@@ -1,32 +1,28 @@
-use std::cmp::min;
-use ncurses::{clear, COLS, LINES, refresh};
+use std::collections::VecDeque;
+use std::ops::Range;
+
+use ncurses::{clear, COLS, refresh};
 use ui::{compute_widths, print_footer, print_matrix};
+use crate::etc;
+use crate::etc::mag_fmt;
 use crate::pacstream::PacStream;
 use crate::ui;
-use crate::ui::{Cell, massage_corp, stats, trim_host, UI};
+use crate::ui::{Cell, massage_corp, opt_fmt, pct_fmt, speed, stats, Style, trim_host, UI};
+use crate::ui::columns::Column;
 use crate::ui::Justify::{LHS, RHS};
+use crate::ui::sort::SortKey;
+use crate::ui::Style::{In, Out};
 
-pub(crate) fn print(ui: &mut UI, pac_vec: &Vec<PacStream>, q_depth: u64, dropped: u64, interval: u64) {
-    let nrows = min(pac_vec.len(), (LINES() - 2) as usize);
-    let mut matrix: Vec<Vec<Cell>> = Vec::new();
-
-    let bytes_sent_last: u64 = pac_vec.iter().map(|s| s.bytes_sent_last).sum();
-    let bytes_recv_last: u64 = pac_vec.iter().map(|s| s.bytes_recv_last).sum();
-
-    matrix.push(render_header(bytes_sent_last, bytes_recv_last, interval, ui.resolve));
-
-    for i in 0..nrows {
-        let row = render_row(&pac_vec[i], bytes_sent_last, bytes_recv_last, ui.resolve, interval);
-        matrix.push(row);
-    }
+pub(crate) fn print(ui: &mut UI, pac_vec: &Vec<PacStream>, history: &VecDeque<(u64, u64)>, q_depth: u64, dropped: u64, interval: u64) {
+    let (mut matrix, flex) = render(ui, pac_vec, history, interval, ui.visible(pac_vec.len()), COLS());
 
     let mut widths = compute_widths(&matrix, &ui.widths);
 
-    hack_widths(&mut widths);
+    hack_widths(&mut widths, &flex, COLS());
 
     clear();
 
-    print_matrix(&mut matrix, &mut widths);
+    print_matrix(&mut matrix, &mut widths, &ui.theme);
 
     print_footer(ui, q_depth, dropped, COLS());
 
@@ -35,104 +31,240 @@ pub(crate) fn print(ui: &mut UI, pac_vec: &Vec<PacStream>, q_depth: u64, dropped
     ui.store_widths(&widths);
 }
 
-fn hack_widths(widths: &mut Vec<i16>) {
-    let cols = COLS();
-    if cols < 1 { // sometimes it is 0 at startup
-        return;
+// the header and the given rows, plus which columns can give or take width //
+pub(crate) fn render(ui: &UI, pac_vec: &[PacStream], history: &VecDeque<(u64, u64)>, interval: u64, rows: Range<usize>, cols: i32) -> (Vec<Vec<Cell>>, Vec<(usize, f32)>) {
+    let mut matrix: Vec<Vec<Cell>> = Vec::new();
+
+    let bytes_sent_last: u64 = pac_vec.iter().map(|s| s.bytes_sent_last).fold(0, u64::saturating_add);
+    let bytes_recv_last: u64 = pac_vec.iter().map(|s| s.bytes_recv_last).fold(0, u64::saturating_add);
+
+    // only bother with these columns if there are cloud ranges/blocklists loaded //
+    let cloud = pac_vec.iter().any(|s| s.cloud.is_some());
+    let flags = pac_vec.iter().any(|s| s.flag.is_some());
+    let columns: Vec<Column> = ui.columns.iter()
+        .filter(|col| match col {
+            Column::Cloud => cloud,
+            Column::List => flags,
+            _ => true
+        })
+        .cloned()
+        .collect();
+
+    let (header, flex) = render_header(ui, &columns, bytes_sent_last, bytes_recv_last, interval, history);
+    matrix.push(header);
+
+    for i in rows {
+        let mut row = render_row(ui, &columns, &pac_vec[i], bytes_sent_last, bytes_recv_last, interval, cols);
+        if ui.cursor == Some(i) {
+            Cell::style_row(&mut row, Style::Selected);
+        }
+        matrix.push(row);
     }
 
-    let local_col = 0;
-    let remote_col = 4;
-    let ratio = 0.45;   // local :: remote
+    (matrix, flex)
+}
+
+// share out whatever is left (or missing) between the host columns //
+pub(crate) fn hack_widths(widths: &mut [i16], flex: &[(usize, f32)], cols: i32) {
+    if cols < 1 || flex.is_empty() { // sometimes it is 0 at startup
+        return;
+    }
 
     let render_len = widths.iter().sum::<i16>();
     let deficit = render_len - cols as i16;
-    let budget = widths[local_col].wrapping_add(widths[remote_col]).wrapping_sub(deficit);
+    let budget = flex.iter().map(|(i, _)| widths[*i]).sum::<i16>().wrapping_sub(deficit);
+    let weights = flex.iter().map(|(_, weight)| weight).sum::<f32>();
 
     // todo: panic if total too small - nothing to work with //
 
-    widths[local_col] = (budget as f32 * ratio) as i16;
-    widths[remote_col] = budget - widths[local_col];
+    let mut left = budget;
+    for (n, (i, weight)) in flex.iter().enumerate() {
+        widths[*i] = match n + 1 == flex.len() {
+            true => left,
+            false => (budget as f32 * weight / weights) as i16
+        }.max(1);
+        left -= widths[*i];
+    }
 }
 
-fn render_row(stream: &PacStream, total_bytes_sent: u64, total_bytes_recv: u64, resolve: bool, elapsed: u64) -> Vec<Cell> {
+fn render_row(ui: &UI, columns: &[Column], stream: &PacStream, total_bytes_sent: u64, total_bytes_recv: u64, elapsed: u64, cols: i32) -> Vec<Cell> {
     let mut row: Vec<Cell> = Vec::new();
+    let resolve = ui.resolve;
+
+    for (n, col) in columns.iter().enumerate() {
+        if n > 0 {
+            row.push(Cell::new(RHS, " "));
+        }
+
+        match col {
+            Column::Local => {
+                if stream.foreign {
+                    row.push(Cell::new(RHS, &match resolve {
+                        true => stream.local_host.to_string(),
+                        false => stream.local_addr.to_string()
+                    }));
+                } else {
+                    row.push(Cell::new(RHS, &match resolve {
+                        true => format!("<{}>", stream.proc),
+                        false => stream.local_addr.to_string()
+                    }));
+                }
+
+                row.push(Cell::new(LHS, ":"));
+
+                row.push(Cell::new(LHS, &match resolve {
+                    true => {
+                        let mut ss = stream.local_service.to_string();
+                        ss.truncate(6);
+                        ss
+                    },
+                    false => stream.local_port.to_string()
+                }));
+            }
+            Column::Remote => {
+                row.push(Cell::new(RHS, &match resolve {
+                    true => trim_host(&stream.remote_host),
+                    false => stream.remote_addr.to_string()
+                }));
 
-    if stream.foreign {
-        row.push(Cell::new(RHS, &match resolve {
-            true => stream.local_host.to_string(),
-            false => stream.local_addr.to_string()
-        }));
-    } else {
-        row.push(Cell::new(RHS, &match resolve {
-            true => format!("<{}>", stream.proc),
-            false => stream.local_addr.to_string()
-        }));
+                row.push(Cell::new(LHS, ":"));
+
+                row.push(Cell::new(LHS, &match resolve {
+                    true => {
+                        let mut ss = stream.remote_service.to_string();
+                        ss.truncate(6);
+                        ss
+                    },
+                    false => stream.remote_port.to_string()
+                }));
+            }
+            Column::Proc => row.push(Cell::new(LHS, match stream.foreign {
+                true => "-",
+                false => &stream.proc
+            })),
+            Column::Pid => row.push(Cell::new(RHS, &opt_fmt(stream.pid))),
+            Column::User => row.push(Cell::new(LHS, &match &stream.user {
+                Some(user) => user.to_string(),
+                None => opt_fmt(stream.uid)
+            })),
+            Column::Proto => row.push(Cell::new(LHS, &etc::str(stream.ip_number))),
+            Column::In => stats::add_in(&mut row, stream, total_bytes_recv, elapsed),
+            Column::Out => stats::add_out(&mut row, stream, total_bytes_sent, elapsed),
+            Column::Pct => {
+                row.push(Cell::new(RHS, &pct_fmt(stream.bytes_recv_last as f64 / total_bytes_recv as f64)).styled(In));
+                row.push(Cell::new(RHS, " "));
+                row.push(Cell::new(RHS, &pct_fmt(stream.bytes_sent_last as f64 / total_bytes_sent as f64)).styled(Out));
+            }
+            Column::Rate => {
+                row.push(Cell::new(RHS, &speed(stream.bytes_recv_last, elapsed)).styled(stats::rate_style(stream.bytes_recv_last, elapsed, In)));
+                row.push(Cell::new(RHS, " "));
+                row.push(Cell::new(RHS, &speed(stream.bytes_sent_last, elapsed)).styled(stats::rate_style(stream.bytes_sent_last, elapsed, Out)));
+            }
+            Column::Total => {
+                row.push(Cell::new(RHS, &mag_fmt(stream.bytes_recv)).styled(In));
+                row.push(Cell::new(RHS, " "));
+                row.push(Cell::new(RHS, &mag_fmt(stream.bytes_sent)).styled(Out));
+            }
+            Column::Packets => {
+                row.push(Cell::new(RHS, &stream.packets_in.to_string()).styled(In));
+                row.push(Cell::new(RHS, " "));
+                row.push(Cell::new(RHS, &stream.packets_out.to_string()).styled(Out));
+            }
+            Column::History => row.push(Cell::spark(&stream.history)),
+            Column::Age => row.push(Cell::new(RHS, &stream.age())),
+            Column::Cc => row.push(Cell::new(RHS, &stream.cc)),
+            Column::City => row.push(Cell::new(LHS, stream.city.as_deref().unwrap_or(""))),
+            Column::Corp => {
+                let mut corp = match &stream.tag {
+                    Some(tag) => format!("{}/{}", tag, stream.corp),
+                    None => stream.corp.to_string()
+                };
+                massage_corp(&mut corp, (cols as f32 * 0.14) as usize);
+                row.push(Cell::new(RHS, &corp));
+            }
+            Column::Asn => row.push(Cell::new(RHS, &match stream.asn {
+                Some(asn) => format!("AS{}", asn),
+                None => "".to_string()
+            })),
+            Column::Sni => row.push(Cell::new(LHS, stream.sni.as_deref().unwrap_or(""))),
+            Column::Cloud => row.push(Cell::new(LHS, stream.cloud.as_deref().unwrap_or(""))),
+            Column::List => row.push(Cell::new(LHS, stream.flag.as_deref().unwrap_or("")))
+        }
     }
 
-    row.push(Cell::new(LHS, ":"));
-
-    row.push(Cell::new(LHS, &match resolve {
-        true => {
-            let mut ss = stream.local_service.to_string();
-            ss.truncate(6);
-            ss
-        },
-        false => stream.local_port.to_string()
-    }));
-
-    row.push(Cell::new(LHS, " "));
-
-    row.push(Cell::new(RHS, &match resolve {
-        true => trim_host(&stream.remote_host),
-        false => stream.remote_addr.to_string()
-    }));
-
-    row.push(Cell::new(LHS, ":"));
-
-    row.push(Cell::new(LHS, &match resolve {
-        true => {
-            let mut ss = stream.remote_service.to_string();
-            ss.truncate(6);
-            ss
-        },
-        false => stream.remote_port.to_string()
-    }));
-
-    row.push(Cell::new(RHS, " "));
-    stats::add(&mut row, &stream, total_bytes_sent, total_bytes_recv, elapsed);
-    row.push(Cell::new(RHS, " "));
-    row.push(Cell::new(RHS, &stream.age()));
-    row.push(Cell::new(RHS, " "));
-    row.push(Cell::new(RHS, &stream.cc));
-
-    let mut corp = stream.corp.to_string();
-    massage_corp(&mut corp, (COLS() as f32 * 0.14) as usize);
-    row.push(Cell::new(RHS, ""));
-    row.push(Cell::new(RHS, &corp));
+    Cell::style_row(&mut row, Style::of(stream));
 
     row
 }
 
-fn render_header(total_bytes_sent: u64, total_bytes_recv: u64, elapsed: u64, resolve: bool) -> Vec<Cell> {
+// also returns where the host columns start and their share of the spare width //
+fn render_header(ui: &UI, cols: &[Column], total_bytes_sent: u64, total_bytes_recv: u64, elapsed: u64, history: &VecDeque<(u64, u64)>) -> (Vec<Cell>, Vec<(usize, f32)>) {
+    let mark = |keys: &[SortKey]| ui.sort.mark(keys);
     let mut row: Vec<Cell> = Vec::new();
-    row.push(Cell::new(RHS, "HOST|<PROC>"));
-    row.push(Cell::new(LHS, ":"));
-    row.push(Cell::new(LHS, "PORT"));
-    row.push(Cell::new(LHS, " "));
-    row.push(Cell::new(RHS, "REMOTE-HOST"));
-    row.push(Cell::new(LHS, ":"));
-    row.push(Cell::new(LHS, match resolve {
-        true => "SVC",
-        false => "PORT"
-    }));
-    row.push(Cell::new(RHS, " "));
-    stats::add_headers(&mut row, total_bytes_sent, total_bytes_recv, elapsed);
-    row.push(Cell::new(LHS, ""));
-    row.push(Cell::new(RHS, "AGE"));
-    row.push(Cell::new(LHS, ""));
-    row.push(Cell::new(RHS, "CC"));
-    row.push(Cell::new(RHS, " "));
-    row.push(Cell::new(RHS, "CORP"));
-    row
+    let mut flex = Vec::new();
+
+    for (n, col) in cols.iter().enumerate() {
+        if n > 0 {
+            row.push(Cell::new(LHS, " "));
+        }
+
+        if let Some(weight) = col.flex() {
+            flex.push((row.len(), weight));
+        }
+
+        match col {
+            Column::Local => {
+                row.push(Cell::new(RHS, &format!("HOST|<PROC>{}", mark(&[SortKey::Proc]))));
+                row.push(Cell::new(LHS, ":"));
+                row.push(Cell::new(LHS, "PORT"));
+            }
+            Column::Remote => {
+                row.push(Cell::new(RHS, &format!("REMOTE-HOST{}", mark(&[SortKey::Host]))));
+                row.push(Cell::new(LHS, ":"));
+                row.push(Cell::new(LHS, match ui.resolve {
+                    true => "SVC",
+                    false => "PORT"
+                }));
+            }
+            Column::Proc => row.push(Cell::new(LHS, &format!("PROC{}", mark(&[SortKey::Proc])))),
+            Column::Pid => row.push(Cell::new(RHS, "PID")),
+            Column::User => row.push(Cell::new(LHS, "USER")),
+            Column::Proto => row.push(Cell::new(LHS, "PROTO")),
+            Column::In => stats::add_header(&mut row, "in", total_bytes_recv, elapsed,
+                                            mark(&[SortKey::Rate, SortKey::RecvRate, SortKey::Recv, SortKey::Total, SortKey::Packets])),
+            Column::Out => stats::add_header(&mut row, "out", total_bytes_sent, elapsed,
+                                             mark(&[SortKey::Rate, SortKey::SentRate, SortKey::Sent, SortKey::Total, SortKey::Packets])),
+            Column::Pct => {
+                row.push(Cell::new(RHS, "IN%%"));
+                row.push(Cell::new(RHS, " "));
+                row.push(Cell::new(RHS, "OUT%%"));
+            }
+            Column::Rate => {
+                row.push(Cell::new(RHS, &format!("IN/S{}", mark(&[SortKey::Rate, SortKey::RecvRate]))));
+                row.push(Cell::new(RHS, " "));
+                row.push(Cell::new(RHS, &format!("OUT/S{}", mark(&[SortKey::Rate, SortKey::SentRate]))));
+            }
+            Column::Total => {
+                row.push(Cell::new(RHS, &format!("IN-TOTAL{}", mark(&[SortKey::Recv, SortKey::Total]))));
+                row.push(Cell::new(RHS, " "));
+                row.push(Cell::new(RHS, &format!("OUT-TOTAL{}", mark(&[SortKey::Sent, SortKey::Total]))));
+            }
+            Column::Packets => {
+                row.push(Cell::new(RHS, &format!("PKTS-IN{}", mark(&[SortKey::Packets]))));
+                row.push(Cell::new(RHS, " "));
+                row.push(Cell::new(RHS, &format!("PKTS-OUT{}", mark(&[SortKey::Packets]))));
+            }
+            Column::History => row.push(Cell::spark(history)),
+            Column::Age => row.push(Cell::new(RHS, &format!("AGE{}", mark(&[SortKey::Age])))),
+            Column::Cc => row.push(Cell::new(RHS, &format!("CC{}", mark(&[SortKey::Cc])))),
+            Column::City => row.push(Cell::new(LHS, "CITY")),
+            Column::Corp => row.push(Cell::new(RHS, &format!("CORP{}", mark(&[SortKey::Corp])))),
+            Column::Asn => row.push(Cell::new(RHS, "ASN")),
+            Column::Sni => row.push(Cell::new(LHS, "SNI")),
+            Column::Cloud => row.push(Cell::new(LHS, "CLOUD")),
+            Column::List => row.push(Cell::new(LHS, "LIST"))
+        }
+    }
+
+    (row, flex)
 }
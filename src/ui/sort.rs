@@ -0,0 +1,189 @@
+use std::cmp::Ordering;
+
+use crate::pacstream::PacStream;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SortKey {
+    Rate,       // current in+out
+    RecvRate,
+    SentRate,
+    Recv,       // totals
+    Sent,
+    Total,
+    Packets,
+    Age,
+    Proc,
+    Host,
+    Cc,
+    Corp
+}
+
+use SortKey::*;
+
+const ALL: [SortKey; 12] = [Rate, RecvRate, SentRate, Recv, Sent, Total, Packets, Age, Proc, Host, Cc, Corp];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Sort {
+    pub key: SortKey,
+    pub reverse: bool
+}
+
+impl SortKey {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Rate => "rate",
+            RecvRate => "in-rate",
+            SentRate => "out-rate",
+            Recv => "in-total",
+            Sent => "out-total",
+            Total => "total",
+            Packets => "packets",
+            Age => "age",
+            Proc => "proc",
+            Host => "host",
+            Cc => "cc",
+            Corp => "corp"
+        }
+    }
+
+    pub fn parse(txt: &str) -> Option<SortKey> {
+        ALL.iter().find(|key| key.name().eq_ignore_ascii_case(txt)).copied()
+    }
+
+    pub fn next(&self) -> SortKey {
+        let pos = ALL.iter().position(|key| key == self).unwrap();
+        ALL[(pos + 1) % ALL.len()]
+    }
+
+    // numbers go biggest (or newest) first, names alphabetically //
+    fn textual(&self) -> bool {
+        matches!(self, Proc | Host | Cc | Corp)
+    }
+}
+
+impl Sort {
+    pub fn new() -> Self {
+        Sort { key: Rate, reverse: false }
+    }
+
+    pub fn sort(&self, pac_vec: &mut [PacStream]) {
+        pac_vec.sort_by(|a, b| {
+            let ret = self.compare(a, b);
+            if self.reverse { ret.reverse() } else { ret }
+        });
+    }
+
+    // "^" ascending, "v" descending - as displayed //
+    pub fn arrow(&self) -> &'static str {
+        if self.key.textual() != self.reverse { "^" } else { "v" }
+    }
+
+    // marks header cells for the given keys //
+    pub fn mark(&self, keys: &[SortKey]) -> &'static str {
+        if keys.contains(&self.key) { self.arrow() } else { "" }
+    }
+
+    fn compare(&self, a: &PacStream, b: &PacStream) -> Ordering {
+        let ret = match self.key {
+            Rate => return sort_by_last_ts(a, b),
+            Total => return sort_by_bytes(a, b),
+            RecvRate => b.bytes_recv_last.cmp(&a.bytes_recv_last),
+            SentRate => b.bytes_sent_last.cmp(&a.bytes_sent_last),
+            Recv => b.bytes_recv.cmp(&a.bytes_recv),
+            Sent => b.bytes_sent.cmp(&a.bytes_sent),
+            Packets => (b.packets_in + b.packets_out).cmp(&(a.packets_in + a.packets_out)),
+            Age => b.ts_last.cmp(&a.ts_last),
+            Proc => a.proc.to_lowercase().cmp(&b.proc.to_lowercase()),
+            Host => a.remote_host.cmp(&b.remote_host),
+            Cc => a.cc.cmp(&b.cc),
+            Corp => a.corp.to_lowercase().cmp(&b.corp.to_lowercase())
+        };
+        ret.then_with(|| sort_by_last_ts(a, b))
+    }
+}
+
+fn sort_by_bytes(a:&PacStream, b:&PacStream) -> Ordering {
+    let mut ret = b.bytes().cmp(&a.bytes());
+    if ret.is_eq() {
+        ret = b.ts_last.cmp(&a.ts_last);
+    }
+    ret
+}
+
+fn sort_by_last_ts(a:&PacStream, b:&PacStream) -> Ordering {
+    let mut ret = b.bytes_last().cmp(&a.bytes_last());
+    if ret.is_eq() {
+        ret = b.ts_last.cmp(&a.ts_last);
+    }
+    if ret.is_eq() {
+        ret = b.bytes().cmp(&a.bytes());
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use etherparse::IpNumber;
+
+    use crate::pacdat::{Dir, PacDat};
+    use crate::pacstream::PacStream;
+    use crate::subnets::addr;
+    use crate::ui::sort::{Sort, SortKey};
+
+    fn stream(proc:&str, cc:&str, sent:u64, recv:u64, sent_last:u64) -> PacStream {
+        let pac_dat = PacDat {
+            ts: Utc::now(), len: Some(0), packets: 1, ip_number: Some(IpNumber::UDP),
+            src_addr: Some(addr("10.0.0.1")), dst_addr: Some(addr("10.0.0.2")),
+            src_port: Some(1), dst_port: Some(2),
+            dir: Some(Dir::Out), foreign: Some(false), local_traffic: Some(true), sni: None
+        };
+        let mut stream = PacStream::new(&pac_dat);
+        stream.proc = proc.to_string();
+        stream.cc = cc.to_string();
+        stream.bytes_sent = sent;
+        stream.bytes_recv = recv;
+        stream.bytes_sent_last = sent_last;
+        stream
+    }
+
+    fn sorted(key:SortKey, reverse:bool) -> Vec<String> {
+        let mut pac_vec = vec![
+            stream("b", "US", 10, 500, 5),
+            stream("a", "GB", 300, 100, 0),
+            stream("c", "CN", 20, 20, 9),
+        ];
+        Sort { key, reverse }.sort(&mut pac_vec);
+        pac_vec.iter().map(|s| s.proc.to_string()).collect()
+    }
+
+    #[test]
+    fn test_sort() {
+        assert_eq!(vec!["c", "b", "a"], sorted(SortKey::Rate, false));
+        assert_eq!(vec!["b", "a", "c"], sorted(SortKey::Total, false));
+        assert_eq!(vec!["a", "c", "b"], sorted(SortKey::Sent, false));
+        assert_eq!(vec!["b", "a", "c"], sorted(SortKey::Recv, false));
+        assert_eq!(vec!["a", "b", "c"], sorted(SortKey::Proc, false));
+        assert_eq!(vec!["c", "b", "a"], sorted(SortKey::Proc, true));
+        assert_eq!(vec!["c", "a", "b"], sorted(SortKey::Cc, false));
+        assert_eq!(vec!["b", "a", "c"], sorted(SortKey::Cc, true));
+        assert_eq!(vec!["c", "a", "b"], sorted(SortKey::Total, true));
+    }
+
+    #[test]
+    fn test_keys() {
+        let mut key = SortKey::Rate;
+        let mut names = vec![];
+        for _ in 0..12 {
+            names.push(key.name());
+            key = key.next();
+        }
+        assert_eq!(SortKey::Rate, key);
+        names.dedup();
+        assert_eq!(12, names.len());
+
+        assert_eq!("v", Sort { key: SortKey::Total, reverse: false }.arrow());
+        assert_eq!("^", Sort { key: SortKey::Proc, reverse: false }.arrow());
+        assert_eq!("", Sort { key: SortKey::Proc, reverse: false }.mark(&[SortKey::Corp]));
+    }
+}
@@ -0,0 +1,71 @@
+use std::collections::VecDeque;
+
+use ncurses::{ACS_HLINE, ACS_S1, ACS_S3, ACS_S7, ACS_S9, chtype, mvaddch};
+
+// intervals shown in the sparkline column //
+pub const SPARK_LEN: usize = 12;
+
+// scan lines from the bottom of the cell to the top - these come out as
+// plain '-' on terminals without line drawing //
+fn glyph(level: u8) -> chtype {
+    match level {
+        0 => ' ' as chtype,
+        1 => ACS_S9(),
+        2 => ACS_S7(),
+        3 => ACS_HLINE(),
+        4 => ACS_S3(),
+        _ => ACS_S1()
+    }
+}
+
+const MAX_LEVEL: u64 = 5;
+
+// the same for plain text //
+pub fn text(levels: &[u8]) -> String {
+    levels.iter().map(|level| [' ', '_', '.', '-', '~', '^'][(*level as usize).min(5)]).collect()
+}
+
+// in+out per interval, oldest on the left, scaled to the busiest interval
+// shown so the shape (bursty vs steady) comes through. any traffic at all
+// gets at least the bottom line //
+pub fn levels(history: &VecDeque<(u64, u64)>, len: usize) -> Vec<u8> {
+    let totals: Vec<u64> = history.iter().take(len).map(|(recv, sent)| recv + sent).collect();
+    let max = totals.iter().max().cloned().unwrap_or(0);
+
+    let mut ret = vec![0u8; len - totals.len()];
+    for total in totals.iter().rev() {
+        ret.push(match (max, total) {
+            (0, _) | (_, 0) => 0,
+            _ => ((total * MAX_LEVEL).div_ceil(max)) as u8
+        });
+    }
+    ret
+}
+
+pub fn print(y: i32, x: i32, levels: &[u8]) {
+    for (i, level) in levels.iter().enumerate() {
+        mvaddch(y, x + i as i32, glyph(*level));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use crate::ui::spark::{levels, text};
+
+    #[test]
+    fn test_levels() {
+        assert_eq!(vec![0, 0, 0], levels(&VecDeque::new(), 3));
+
+        // newest first in, oldest first out //
+        let history = VecDeque::from(vec![(100, 0), (0, 0), (10, 10), (50, 0)]);
+        assert_eq!(vec![0, 3, 1, 0, 5], levels(&history, 5));
+        assert_eq!(vec![1, 0, 5], levels(&history, 3));
+
+        let steady = VecDeque::from(vec![(7, 7); 4]);
+        assert_eq!(vec![5, 5, 5, 5], levels(&steady, 4));
+
+        assert_eq!(" -_ ^", text(&levels(&history, 5)));
+    }
+}
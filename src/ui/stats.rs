@@ -1,32 +1,54 @@
 use crate::etc::mag_fmt;
 use crate::pacstream::PacStream;
 use crate::ui::{Cell, pct_fmt, speed};
+use crate::ui::Style::{In, Out};
+use crate::ui::Style;
+use crate::ui::theme::heat;
 use crate::ui::Justify::{LHS, RHS};
+use crate::ui::sort::Sort;
+use crate::ui::sort::SortKey::{Packets, Rate, Recv, RecvRate, Sent, SentRate, Total};
 
-pub fn add_headers(row: &mut Vec<Cell>, total_bytes_sent: u64, total_bytes_recv: u64, interval: u64) {
-    row.push(Cell::new(RHS, "in"));
-    row.push(Cell::new(RHS, ":"));
-    row.push(Cell::new(RHS, &speed(total_bytes_recv, interval)));
-    row.push(Cell::new(RHS, " "));
-    row.push(Cell::new(LHS, " "));
-    row.push(Cell::new(LHS, " "));
-    row.push(Cell::new(RHS, "out"));
-    row.push(Cell::new(RHS, ":"));
-    row.push(Cell::new(RHS, &speed(total_bytes_sent, interval)));
-    row.push(Cell::new(LHS, " "));
+pub fn add_headers(row: &mut Vec<Cell>, total_bytes_sent: u64, total_bytes_recv: u64, interval: u64, sort: &Sort) {
+    add_header(row, "in", total_bytes_recv, interval, sort.mark(&[Rate, RecvRate, Recv, Total, Packets]));
     row.push(Cell::new(LHS, " "));
+    add_header(row, "out", total_bytes_sent, interval, sort.mark(&[Rate, SentRate, Sent, Total, Packets]));
 }
 
 pub fn add(row: &mut Vec<Cell>, stream: &PacStream, total_bytes_sent: u64, total_bytes_recv: u64, interval: u64) {
-    row.push(Cell::new(RHS, &pct_fmt(stream.bytes_recv_last as f64 / total_bytes_recv as f64)));
+    add_in(row, stream, total_bytes_recv, interval);
     row.push(Cell::new(RHS, " "));
-    row.push(Cell::new(RHS, &speed(stream.bytes_recv_last, interval)));
-    row.push(Cell::new(RHS, " ("));
-    row.push(Cell::new(RHS, &format!("{})", mag_fmt(stream.bytes_recv))));
+    add_out(row, stream, total_bytes_sent, interval);
+}
+
+// each half is 5 cells, header and row alike //
+pub fn add_header(row: &mut Vec<Cell>, label: &str, total_bytes: u64, interval: u64, mark: &str) {
+    row.push(Cell::new(RHS, &format!("{}{}", label, mark)));
+    row.push(Cell::new(RHS, ":"));
+    row.push(Cell::new(RHS, &speed(total_bytes, interval)));
     row.push(Cell::new(RHS, " "));
-    row.push(Cell::new(RHS, &pct_fmt(stream.bytes_sent_last as f64 / total_bytes_sent as f64)));
+    row.push(Cell::new(LHS, " "));
+}
+
+pub fn add_in(row: &mut Vec<Cell>, stream: &PacStream, total_bytes_recv: u64, interval: u64) {
+    add_half(row, stream.bytes_recv_last, stream.bytes_recv, total_bytes_recv, interval, In);
+}
+
+pub fn add_out(row: &mut Vec<Cell>, stream: &PacStream, total_bytes_sent: u64, interval: u64) {
+    add_half(row, stream.bytes_sent_last, stream.bytes_sent, total_bytes_sent, interval, Out);
+}
+
+fn add_half(row: &mut Vec<Cell>, bytes_last: u64, bytes: u64, total_bytes: u64, interval: u64, dir: Style) {
+    row.push(Cell::new(RHS, &pct_fmt(bytes_last as f64 / total_bytes as f64)).styled(dir));
     row.push(Cell::new(RHS, " "));
-    row.push(Cell::new(RHS, &speed(stream.bytes_sent_last, interval)));
+    row.push(Cell::new(RHS, &speed(bytes_last, interval)).styled(rate_style(bytes_last, interval, dir)));
     row.push(Cell::new(RHS, " ("));
-    row.push(Cell::new(RHS, &format!("{})", mag_fmt(stream.bytes_sent))));
+    row.push(Cell::new(RHS, &format!("{})", mag_fmt(bytes))).styled(dir));
+}
+
+// busy streams stand out whichever way they're going //
+pub fn rate_style(bytes: u64, interval: u64, dir: Style) -> Style {
+    match interval {
+        0 => dir,
+        _ => heat(bytes.saturating_mul(1000) / interval).unwrap_or(dir)
+    }
 }
\ No newline at end of file
@@ -0,0 +1,242 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs::read_to_string;
+use std::path::Path;
+
+use ncurses::*;
+
+use crate::etc::log;
+use crate::ui::Style;
+
+// ~/.config/pacmon/theme - one style per line, anything left out keeps the default:
+//
+//   # name = foreground[,background][,attribute..]
+//   heat3 = red,default,bold
+//   selected = black,white
+//
+// colours: default black red green yellow blue magenta cyan white or 0-255.
+// attributes: bold dim underline reverse standout blink.
+// without colour (or with NO_COLOR set) only the attributes are used.
+const DEFAULTS: &str = "
+header   = default,default,bold
+footer   = default,default,reverse
+in       = green
+out      = cyan
+heat1    = yellow
+heat2    = red
+heat3    = red,default,bold
+new      = default,default,bold
+idle     = default,default,dim
+local    = blue
+foreign  = magenta
+flagged  = white,red,bold
+selected = default,default,reverse
+";
+
+const NAMES: [(&str, Style); 13] = [
+    ("header", Style::Header),
+    ("footer", Style::Footer),
+    ("in", Style::In),
+    ("out", Style::Out),
+    ("heat1", Style::Heat(1)),
+    ("heat2", Style::Heat(2)),
+    ("heat3", Style::Heat(3)),
+    ("new", Style::New),
+    ("idle", Style::Idle),
+    ("local", Style::Local),
+    ("foreign", Style::Foreign),
+    ("flagged", Style::Flagged),
+    ("selected", Style::Selected)
+];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Spec {
+    fg: i16,
+    bg: i16,
+    attrs: attr_t
+}
+
+pub struct Theme {
+    specs: HashMap<Style, Spec>,
+    attrs: HashMap<Style, attr_t>
+}
+
+impl Theme {
+    pub fn new() -> Self {
+        let mut theme = Theme { specs: HashMap::new(), attrs: HashMap::new() };
+        theme.parse(DEFAULTS);
+        theme
+    }
+
+    pub fn load(path: &Path) -> Self {
+        let mut theme = Theme::new();
+        match read_to_string(path) {
+            Ok(txt) => {
+                for msg in theme.parse(&txt) {
+                    eprintln!("{}: {}", path.display(), msg);
+                }
+                log(format!("theme: loaded {}", path.display()));
+            }
+            Err(err) => eprintln!("{}: {}", path.display(), err)
+        }
+        theme
+    }
+
+    // returns what it couldn't make sense of //
+    pub fn parse(&mut self, txt: &str) -> Vec<String> {
+        let mut errs = Vec::new();
+        for line in txt.lines().map(|l| l.split('#').next().unwrap().trim()).filter(|l| !l.is_empty()) {
+            let (name, value) = match line.split_once('=') {
+                Some((name, value)) => (name.trim().to_lowercase(), value),
+                None => {
+                    errs.push(format!("expected name = colour [{}]", line));
+                    continue;
+                }
+            };
+            let style = match NAMES.iter().find(|(n, _)| *n == name) {
+                Some((_, style)) => *style,
+                None => {
+                    errs.push(format!("unknown style [{}]", name));
+                    continue;
+                }
+            };
+            match parse_spec(value) {
+                Ok(spec) => { self.specs.insert(style, spec); }
+                Err(msg) => errs.push(msg)
+            }
+        }
+        errs
+    }
+
+    // call after initscr //
+    pub fn init(&mut self) {
+        let colour = has_colors() && env::var_os("NO_COLOR").is_none();
+        if colour {
+            start_color();
+            use_default_colors();
+        }
+
+        for (pair, (_, style)) in NAMES.iter().enumerate() {
+            let spec = self.specs[style];
+            let attr = if colour && (spec.fg >= 0 || spec.bg >= 0) {
+                init_pair(pair as i16 + 1, spec.fg, spec.bg);
+                COLOR_PAIR(pair as i16 + 1) | spec.attrs
+            } else if colour || spec.attrs != 0 {
+                spec.attrs
+            } else {
+                mono(*style)
+            };
+            self.attrs.insert(*style, attr);
+        }
+        log(format!("theme: colour:{}", colour));
+    }
+
+    pub fn attr(&self, style: Style) -> attr_t {
+        self.attrs.get(&style).cloned().unwrap_or(A_NORMAL())
+    }
+}
+
+// something to tell things apart when the colours are gone //
+fn mono(style: Style) -> attr_t {
+    match style {
+        Style::Heat(3) => A_BOLD(),
+        Style::Flagged => A_STANDOUT(),
+        Style::Idle => A_DIM(),
+        _ => A_NORMAL()
+    }
+}
+
+fn parse_spec(txt: &str) -> Result<Spec, String> {
+    let mut spec = Spec { fg: -1, bg: -1, attrs: A_NORMAL() };
+    for (i, word) in txt.split(',').map(|w| w.trim().to_lowercase()).enumerate() {
+        match (i, parse_colour(&word)) {
+            (0, Some(colour)) => spec.fg = colour,
+            (1, Some(colour)) => spec.bg = colour,
+            _ => spec.attrs |= parse_attr(&word).ok_or(format!("unknown colour/attribute [{}]", word))?
+        }
+    }
+    Ok(spec)
+}
+
+fn parse_colour(txt: &str) -> Option<i16> {
+    Some(match txt {
+        "default" | "-" | "" => -1,
+        "black" => COLOR_BLACK,
+        "red" => COLOR_RED,
+        "green" => COLOR_GREEN,
+        "yellow" => COLOR_YELLOW,
+        "blue" => COLOR_BLUE,
+        "magenta" => COLOR_MAGENTA,
+        "cyan" => COLOR_CYAN,
+        "white" => COLOR_WHITE,
+        _ => match txt.parse::<u8>() {
+            Ok(n) => n as i16,
+            Err(_) => return None
+        }
+    })
+}
+
+fn parse_attr(txt: &str) -> Option<attr_t> {
+    Some(match txt {
+        "bold" => A_BOLD(),
+        "dim" => A_DIM(),
+        "underline" => A_UNDERLINE(),
+        "reverse" => A_REVERSE(),
+        "standout" => A_STANDOUT(),
+        "blink" => A_BLINK(),
+        "normal" => A_NORMAL(),
+        _ => return None
+    })
+}
+
+// rates (bytes/s) worth drawing attention to //
+pub fn heat(rate: u64) -> Option<Style> {
+    match rate {
+        r if r >= 1_000_000 => Some(Style::Heat(3)),
+        r if r >= 100_000 => Some(Style::Heat(2)),
+        r if r >= 10_000 => Some(Style::Heat(1)),
+        _ => None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ncurses::{A_BOLD, A_NORMAL, A_UNDERLINE, COLOR_BLUE, COLOR_RED, COLOR_WHITE};
+
+    use crate::ui::Style;
+    use crate::ui::theme::{heat, NAMES, parse_spec, Spec, Theme};
+
+    #[test]
+    fn test_defaults() {
+        let theme = Theme::new();
+        assert_eq!(NAMES.len(), theme.specs.len());
+        assert_eq!(Spec { fg: COLOR_WHITE, bg: COLOR_RED, attrs: A_BOLD() }, theme.specs[&Style::Flagged]);
+    }
+
+    #[test]
+    fn test_parse() {
+        let mut theme = Theme::new();
+        let errs = theme.parse("
+# light terminal
+in = blue   # comment
+heat3 = 196,default,bold,underline
+selected = nosuch
+bogus = red
+no equals
+");
+        assert_eq!(3, errs.len());
+        assert_eq!(Spec { fg: COLOR_BLUE, bg: -1, attrs: A_NORMAL() }, theme.specs[&Style::In]);
+        assert_eq!(Spec { fg: 196, bg: -1, attrs: A_BOLD() | A_UNDERLINE() }, theme.specs[&Style::Heat(3)]);
+
+        assert_eq!(Ok(Spec { fg: -1, bg: -1, attrs: A_BOLD() }), parse_spec("bold"));
+        assert_eq!(Ok(Spec { fg: COLOR_RED, bg: -1, attrs: A_BOLD() }), parse_spec("red,bold"));
+        assert!(parse_spec("red,blue,green").is_err());
+    }
+
+    #[test]
+    fn test_heat() {
+        assert_eq!(None, heat(9_999));
+        assert_eq!(Some(Style::Heat(1)), heat(10_000));
+        assert_eq!(Some(Style::Heat(3)), heat(5_000_000));
+    }
+}